@@ -52,6 +52,7 @@ struct BStatsPartial {
 struct IStatsPartial {
     branches_taken: Option<usize>,
     instructions_executed: Option<usize>,
+    memory_operands: Option<usize>,
 }
 
 trait Check {
@@ -122,6 +123,16 @@ impl Check for IStats {
             }
         }
 
+        if let Some(prev) = prev.memory_operands {
+            if !(self.memory_operands <= prev) {
+                eprint!(
+                    "Statistic `memory_operands` regressed from {prev:?} to {:?} in test ",
+                    self.memory_operands
+                );
+                return false;
+            }
+        }
+
         true
     }
 }
@@ -264,8 +275,8 @@ fn write_commit(benches: &Collection<Document>, commit: &Commit<'_>, test_data:
 
 impl Stats {
     fn from_program(program: &str) -> Self {
-        let (input, _, _, _) = split_test(program);
-        let mut io = TestIO::new(input);
+        let (input, _, _, _, args) = split_test(program);
+        let mut io = TestIO::from_bytes(input).with_args(args);
 
         let tempdir = TempDir::new("cc-bench").unwrap();
         let output = tempdir.path().join("output");
@@ -275,12 +286,18 @@ impl Stats {
             .validate()
             .unwrap()
             .reveal()
+            .decide()
+            .inline()
             .atomize()
+            .fold()
             .explicate()
+            .prune()
             .eliminate()
             .select()
             .assign()
+            .unwrap()
             .patch()
+            .peephole()
             .conclude();
 
         let (_, interpreter_stats) = prg_concluded.interpret_with_stats(&mut io);
@@ -1,40 +1,190 @@
+use crate::passes::select::std_lib::{
+    O_APPEND, O_CREAT, O_EXCL, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY,
+};
 use crate::passes::validate::TLit;
 use crate::utils::gen_sym::UniqueSym;
 use derive_more::Display;
 use std::collections::HashMap;
+use std::mem;
 
 use std::vec::IntoIter;
 
 pub trait IO {
-    fn read(&mut self) -> Option<TLit>;
-    fn print(&mut self, v: TLit);
+    /// Returns the command-line arguments, starting with the program name.
+    fn args(&self) -> &[String];
+    /// Opens the file at `path`, returning a new file descriptor or a negative error code.
+    fn open(&mut self, path: &[u8], flags: i64, mode: i64) -> i64;
+    /// Reads up to `len` bytes from file descriptor `fd`, or returns a negative error code.
+    fn read(&mut self, fd: i64, len: usize) -> Result<Vec<u8>, i64>;
+    /// Writes `bytes` to file descriptor `fd`, returning the number of bytes written or a negative
+    /// error code.
+    fn write(&mut self, fd: i64, bytes: &[u8]) -> i64;
+    /// Closes file descriptor `fd`, returning 0 or a negative error code.
+    fn close(&mut self, fd: i64) -> i64;
+}
+
+const STDIN: i64 = 0;
+const STDOUT: i64 = 1;
+
+const O_ACCMODE: i64 = 0o3;
+
+const ENOENT: i64 = 2;
+const EBADF: i64 = 9;
+const EEXIST: i64 = 17;
+const EINVAL: i64 = 22;
+
+/// A file descriptor opened in the in-memory file system of `TestIO`.
+struct OpenFile {
+    path: Vec<u8>,
+    pos: usize,
+    readable: bool,
+    writable: bool,
+    append: bool,
 }
 
 pub struct TestIO {
-    inputs: IntoIter<TLit>,
-    outputs: Vec<TLit>,
+    inputs: IntoIter<u8>,
+    outputs: Vec<String>,
+    line: Vec<u8>,
+    args: Vec<String>,
+    files: HashMap<Vec<u8>, Vec<u8>>,
+    fds: HashMap<i64, OpenFile>,
+    next_fd: i64,
 }
 
 impl TestIO {
+    /// Creates an IO that feeds every input as a decimal number on its own line.
     pub fn new(inputs: Vec<TLit>) -> Self {
+        Self::from_bytes(
+            inputs
+                .into_iter()
+                .flat_map(|input| format!("{input}\n").into_bytes())
+                .collect(),
+        )
+    }
+
+    /// Creates an IO that feeds the given raw bytes.
+    pub fn from_bytes(inputs: Vec<u8>) -> Self {
         Self {
             inputs: inputs.into_iter(),
             outputs: Vec::new(),
+            line: Vec::new(),
+            args: vec!["program".to_string()],
+            files: HashMap::new(),
+            fds: HashMap::new(),
+            // 0, 1 and 2 are stdin, stdout and stderr.
+            next_fd: 3,
         }
     }
 
-    pub fn outputs(&self) -> &Vec<TLit> {
+    /// Sets the command-line arguments that follow the program name.
+    #[must_use]
+    pub fn with_args(mut self, args: impl IntoIterator<Item = String>) -> Self {
+        self.args.truncate(1);
+        self.args.extend(args);
+        self
+    }
+
+    /// Returns every line written so far, with literals in their canonical form.
+    pub fn outputs(&self) -> &Vec<String> {
         &self.outputs
     }
 }
 
+impl TestIO {
+    fn write_stdout_byte(&mut self, byte: u8) {
+        if byte == b'\n' {
+            let line = String::from_utf8(mem::take(&mut self.line)).unwrap();
+            // Literals are compared by value, so `007` is read as `7`.
+            let line = line.parse::<TLit>().map_or(line, |lit| lit.to_string());
+            self.outputs.push(line);
+        } else {
+            self.line.push(byte);
+        }
+    }
+}
+
 impl IO for TestIO {
-    fn read(&mut self) -> Option<TLit> {
-        self.inputs.next()
+    fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    fn open(&mut self, path: &[u8], flags: i64, _mode: i64) -> i64 {
+        let (readable, writable) = match flags & O_ACCMODE {
+            O_RDONLY => (true, false),
+            O_WRONLY => (false, true),
+            O_RDWR => (true, true),
+            _ => return -EINVAL,
+        };
+
+        match self.files.get_mut(path) {
+            Some(_) if flags & O_CREAT != 0 && flags & O_EXCL != 0 => return -EEXIST,
+            Some(contents) if flags & O_TRUNC != 0 && writable => contents.clear(),
+            Some(_) => {}
+            None if flags & O_CREAT != 0 => {
+                self.files.insert(path.to_vec(), Vec::new());
+            }
+            None => return -ENOENT,
+        }
+
+        let fd = self.next_fd;
+        self.next_fd += 1;
+        self.fds.insert(
+            fd,
+            OpenFile {
+                path: path.to_vec(),
+                pos: 0,
+                readable,
+                writable,
+                append: flags & O_APPEND != 0,
+            },
+        );
+        fd
+    }
+
+    fn read(&mut self, fd: i64, len: usize) -> Result<Vec<u8>, i64> {
+        if fd == STDIN {
+            return Ok(self.inputs.by_ref().take(len).collect());
+        }
+
+        let file = self
+            .fds
+            .get_mut(&fd)
+            .filter(|file| file.readable)
+            .ok_or(-EBADF)?;
+        let contents = &self.files[&file.path];
+        let start = file.pos.min(contents.len());
+        let end = (start + len).min(contents.len());
+        file.pos = end;
+        Ok(contents[start..end].to_vec())
+    }
+
+    fn write(&mut self, fd: i64, bytes: &[u8]) -> i64 {
+        if fd == STDOUT {
+            for &byte in bytes {
+                self.write_stdout_byte(byte);
+            }
+            return bytes.len() as i64;
+        }
+
+        let Some(file) = self.fds.get_mut(&fd).filter(|file| file.writable) else {
+            return -EBADF;
+        };
+        let contents = self.files.get_mut(&file.path).unwrap();
+        if file.append {
+            file.pos = contents.len();
+        }
+        let end = file.pos + bytes.len();
+        if contents.len() < end {
+            contents.resize(end, 0);
+        }
+        contents[file.pos..end].copy_from_slice(bytes);
+        file.pos = end;
+        bytes.len() as i64
     }
 
-    fn print(&mut self, v: TLit) {
-        self.outputs.push(v);
+    fn close(&mut self, fd: i64) -> i64 {
+        self.fds.remove(&fd).map_or(-EBADF, |_| 0)
     }
 }
 
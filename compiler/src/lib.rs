@@ -4,6 +4,7 @@ pub mod interpreter;
 pub mod passes;
 pub mod utils;
 
+use crate::passes::assign::Allocator;
 use crate::passes::parse::parse::parse_program;
 use clap::ValueEnum;
 use miette::{IntoDiagnostic, NamedSource, Report};
@@ -15,12 +16,21 @@ pub enum Pass {
     Parse,
     Validate,
     Reveal,
+    Decide,
+    Inline,
     Atomize,
+    Fold,
     Explicate,
+    Prune,
     Select,
 }
 
-pub fn compile(program: &str, filename: &str, output: &Path) -> miette::Result<()> {
+pub fn compile(
+    program: &str,
+    filename: &str,
+    output: &Path,
+    allocator: Allocator,
+) -> miette::Result<()> {
     let add_source =
         |error| Report::with_source_code(error, NamedSource::new(filename, program.to_string()));
 
@@ -31,12 +41,17 @@ pub fn compile(program: &str, filename: &str, output: &Path) -> miette::Result<(
         .map_err(Into::into)
         .map_err(add_source)?
         .reveal()
+        .decide()
+        .inline()
         .atomize()
+        .fold()
         .explicate()
+        .prune()
         .eliminate()
         .select()
-        .assign()
+        .assign_with(allocator)?
         .patch()
+        .peephole()
         .conclude()
         .emit()
         .write(&mut File::create(output).into_diagnostic()?);
@@ -64,9 +79,13 @@ pub fn display(program: &str, filename: &str, pass: Pass) -> miette::Result<()>
         Validate
     );
     let prg_revealed = display!(prg_validated.reveal(), pass, Reveal);
-    let prg_atomized = display!(prg_revealed.atomize(), pass, Atomize);
-    let prg_explicated = display!(prg_atomized.explicate(), pass, Explicate);
-    let _prg_select = display!(prg_explicated.eliminate().select(), pass, Select);
+    let prg_decided = display!(prg_revealed.decide(), pass, Decide);
+    let prg_inlined = display!(prg_decided.inline(), pass, Inline);
+    let prg_atomized = display!(prg_inlined.atomize(), pass, Atomize);
+    let prg_folded = display!(prg_atomized.fold(), pass, Fold);
+    let prg_explicated = display!(prg_folded.explicate(), pass, Explicate);
+    let prg_pruned = display!(prg_explicated.prune(), pass, Prune);
+    let _prg_select = display!(prg_pruned.eliminate().select(), pass, Select);
 
     Ok(())
 }
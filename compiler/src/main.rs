@@ -1,23 +1,11 @@
 use clap::Parser;
-use compiler::passes::parse::parse::PrettyParseError;
-use compiler::passes::validate::error::TypeError;
+use compiler::passes::assign::Allocator;
 use compiler::{compile, display, Pass};
-use miette::{Diagnostic, IntoDiagnostic};
+use miette::IntoDiagnostic;
 use std::fs;
 use std::io::Read;
 use std::path::Path;
 use std::process::{Command, Stdio};
-use thiserror::Error;
-
-#[derive(Debug, Error, Diagnostic)]
-enum MainError {
-    #[error(transparent)]
-    #[diagnostic(transparent)]
-    ParseError(#[from] PrettyParseError),
-    #[error(transparent)]
-    #[diagnostic(transparent)]
-    ValidateError(#[from] TypeError),
-}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -33,8 +21,17 @@ struct Args {
     #[arg(value_enum, short, long, value_name = "PASS")]
     display: Option<Pass>,
 
+    /// Specifies the register allocator. Linear scan compiles large programs faster, but produces
+    /// slower code.
+    #[arg(value_enum, short, long, value_name = "ALLOCATOR", default_value_t = Allocator::Coloring)]
+    allocator: Allocator,
+
     #[arg(short, long)]
     run: bool,
+
+    /// Arguments passed to the program when it is run, given after `--`.
+    #[arg(last = true, requires = "run")]
+    program_args: Vec<String>,
 }
 
 fn read_from_stdin() -> Result<String, std::io::Error> {
@@ -62,7 +59,7 @@ fn main() -> miette::Result<()> {
         )
     });
 
-    compile(&program, filename, Path::new(&output))?;
+    compile(&program, filename, Path::new(&output), args.allocator)?;
 
     if args.run {
         Command::new("chmod")
@@ -72,6 +69,7 @@ fn main() -> miette::Result<()> {
             .into_diagnostic()?;
 
         Command::new(format!("./{output}"))
+            .args(&args.program_args)
             .stdin(Stdio::inherit())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
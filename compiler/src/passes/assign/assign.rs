@@ -1,41 +1,90 @@
-use crate::passes::assign::{Arg, X86Assigned};
-use crate::passes::select::{Block, Instr, InstrSelected, VarArg, X86Selected};
+use crate::passes::assign::error::AssignError;
+use crate::passes::assign::layout_frames::layout_frames;
+use crate::passes::assign::{Allocator, Arg, LArg, LBlock, LX86VarProgram, X86Assigned};
+use crate::passes::patch::patch::{needs_scratch, patch_instr};
+use crate::passes::select::{handle_instr, Block, Instr, InstrSelected, Reg, VarArg, X86Selected};
 use crate::utils::gen_sym::UniqueSym;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// The registers that `patch` can use for intermediate values, in order of preference. None of
+/// them are ever assigned to variables.
+const SCRATCH: [Reg; 3] = [Reg::RAX, Reg::R11, Reg::R15];
 
 impl<'p> X86Selected<'p> {
-    #[must_use]
-    pub fn assign(self) -> X86Assigned<'p> {
+    pub fn assign(self) -> Result<X86Assigned<'p>, AssignError> {
+        self.assign_with(Allocator::Coloring)
+    }
+
+    pub fn assign_with(self, allocator: Allocator) -> Result<X86Assigned<'p>, AssignError> {
         let program = self.include_liveness();
-        let interference = program.compute_interference();
-        let (color_map, stack_space) = interference.color();
+        let color_map = match allocator {
+            Allocator::Coloring => program.compute_interference().color(&program.spill_costs()),
+            Allocator::LinearScan => program.linear_scan(),
+        };
+        program.assign_colors(&color_map)
+    }
+}
 
-        let blocks = program
+impl<'p> LX86VarProgram<'p> {
+    /// Replaces the variables by the locations in `color_map`, and lays out the stack frames.
+    pub(super) fn assign_colors(
+        self,
+        color_map: &HashMap<UniqueSym, Arg>,
+    ) -> Result<X86Assigned<'p>, AssignError> {
+        let mut blocks = self
             .blocks
             .into_iter()
-            .map(|(lbl, block)| (lbl, assign_block(block.into(), &color_map)))
-            .collect();
+            .map(|(lbl, block)| Ok((lbl, assign_block(block, color_map)?)))
+            .collect::<Result<_, _>>()?;
+        layout_frames(&mut blocks, &self.fns);
 
-        X86Assigned {
+        Ok(X86Assigned {
             blocks,
-            entry: program.entry,
-            stack_space,
-            std: program.std,
-        }
+            entry: self.entry,
+            fns: self.fns,
+            std: self.std,
+        })
     }
 }
 
+/// Assigns the locations in `color_map` to the variables in the block. Instructions in asm blocks
+/// can keep RAX live, in which case the instructions that need a scratch register are patched here
+/// with one that is free.
 fn assign_block<'p>(
-    block: Block<'p, VarArg<UniqueSym<'p>>>,
+    block: LBlock<'p>,
     color_map: &HashMap<UniqueSym, Arg>,
-) -> Block<'p, Arg> {
-    Block {
-        instrs: block
-            .instrs
-            .into_iter()
-            .map(|instr| assign_instr(instr, color_map))
-            .collect(),
+) -> Result<Block<'p, Arg>, AssignError> {
+    let mut instrs = Vec::new();
+    for (instr, live) in block.instrs {
+        let assigned = assign_instr(instr.clone(), color_map);
+        if !needs_scratch(&assigned) {
+            instrs.push(assigned);
+            continue;
+        }
+        match scratch(&instr, &live) {
+            Some(Reg::RAX) => instrs.push(assigned),
+            Some(scratch) => instrs.extend(patch_instr(assigned, scratch)),
+            None => {
+                return Err(AssignError::NoScratchRegister {
+                    instr: instr.to_string(),
+                })
+            }
+        }
     }
+    Ok(Block { instrs })
+}
+
+/// Returns a register that is neither used by the instruction nor live after it.
+fn scratch(instr: &InstrSelected, live: &HashSet<LArg>) -> Option<Reg> {
+    let mut used = Vec::new();
+    handle_instr(instr, |arg, _| {
+        if let VarArg::Reg { reg } | VarArg::Deref { reg, .. } = arg {
+            used.push(*reg);
+        }
+    });
+    SCRATCH
+        .into_iter()
+        .find(|reg| !used.contains(reg) && !live.contains(&LArg::Reg { reg: *reg }))
 }
 
 fn assign_instr<'p>(
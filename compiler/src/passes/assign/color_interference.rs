@@ -4,85 +4,133 @@ use crate::utils::gen_sym::UniqueSym;
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
 
+/// The number of colors that are registers, which are the colors `0..REGISTERS`.
+pub(super) const REGISTERS: isize = 11;
+
 impl<'p> InterferenceGraph<'p> {
+    /// Assigns a register or stack slot to every variable. Variables are removed from the graph
+    /// one at a time, and colored in reverse order. A variable that has fewer neighbors than there
+    /// are registers is removed first, since it is guaranteed to get a register. If there are no
+    /// such variables, the variable that is cheapest to spill relative to its number of neighbors
+    /// is removed instead, which makes it the most likely to end up in a stack slot.
     #[must_use]
-    pub fn color(self) -> (HashMap<UniqueSym<'p>, Arg>, usize) {
-        let graph = self.0;
-        let mut queue = Vec::new();
+    pub fn color(self, costs: &HashMap<UniqueSym<'p>, usize>) -> HashMap<UniqueSym<'p>, Arg> {
+        let graph = self.interference;
+        let moves = self.moves;
+        let mut remaining = Vec::new();
         let mut node_map = HashMap::<LArg, isize>::new();
 
         for node in graph.nodes() {
             match node {
                 LArg::Var { .. } => {
-                    queue.push(node);
+                    remaining.push(node);
                 }
                 LArg::Reg { reg } => {
-                    let node_weight = match reg {
-                        Reg::RCX => 0,
-                        Reg::RDX => 1,
-                        Reg::RSI => 2,
-                        Reg::RDI => 3,
-                        Reg::R8 => 4,
-                        Reg::R9 => 5,
-                        Reg::R10 => 6,
-                        Reg::RBX => 7,
-                        Reg::R12 => 8,
-                        Reg::R13 => 9,
-                        Reg::R14 => 10,
-                        Reg::RAX => -1,
-                        Reg::RSP => -2,
-                        Reg::RBP => -3,
-                        Reg::R11 => -4,
-                        Reg::R15 => -5,
-                    };
-                    node_map.insert(node, node_weight);
+                    node_map.insert(node, color_from_reg(reg));
+                }
+            }
+        }
+
+        // The number of neighbors of each variable that still compete for a register.
+        let mut degrees = remaining
+            .iter()
+            .map(|&node| {
+                let degree = graph
+                    .neighbors(node)
+                    .filter(|nb| {
+                        node_map
+                            .get(nb)
+                            .is_none_or(|color| (0..REGISTERS).contains(color))
+                    })
+                    .count();
+                (node, degree)
+            })
+            .collect::<HashMap<_, _>>();
+        let cost = |node: &LArg| match node {
+            LArg::Var { sym } => costs.get(sym).copied().unwrap_or_default() as u128,
+            LArg::Reg { .. } => unreachable!("Registers are precolored."),
+        };
+
+        remaining.sort();
+        let mut stack = Vec::new();
+        while !remaining.is_empty() {
+            let index = remaining
+                .iter()
+                .position(|node| degrees[node] < REGISTERS as usize)
+                .unwrap_or_else(|| {
+                    remaining
+                        .iter()
+                        .position_min_by(|a, b| {
+                            (cost(a) * degrees[b] as u128).cmp(&(cost(b) * degrees[a] as u128))
+                        })
+                        .unwrap()
+                });
+            let node = remaining.remove(index);
+            for nb in graph.neighbors(node) {
+                if let Some(degree) = degrees.get_mut(&nb) {
+                    *degree = degree.saturating_sub(1);
                 }
             }
+            stack.push(node);
         }
 
-        while let Some(node) = queue.pop() {
+        while let Some(node) = stack.pop() {
             let used_colors = graph
                 .neighbors(node)
                 .filter_map(|nb| node_map.get(&nb))
                 .collect::<HashSet<_>>();
 
-            let chosen_color = (0..)
-                .find(|i| !used_colors.contains(i))
-                .expect("there are infinite numbers, lol");
+            // Prefer the register of a location that the node is moved to or from, such that the
+            // move between them can be removed by `patch`. Stack slots are not preferred, since a
+            // register is cheaper than the move that would be saved.
+            let chosen_color = moves
+                .neighbors(node)
+                .filter_map(|nb| node_map.get(&nb).copied())
+                .filter(|color| (0..REGISTERS).contains(color) && !used_colors.contains(color))
+                .min()
+                .unwrap_or_else(|| {
+                    (0..)
+                        .find(|i| !used_colors.contains(i))
+                        .expect("there are infinite numbers, lol")
+                });
 
             node_map.insert(node, chosen_color);
-
-            queue.sort_by_key(|node| {
-                graph
-                    .neighbors(*node)
-                    .filter_map(|nb| node_map.get(&nb))
-                    .unique()
-                    .count()
-            });
         }
 
-        let used_vars = node_map
-            .values()
-            .filter(|&&n| n >= 10)
-            .map(|&n| n - 10)
-            .max()
-            .unwrap_or_default() as usize;
-
-        let stack_space = (8 * used_vars).div_ceil(16) * 16 + 256;
-
-        let colors = node_map
+        node_map
             .into_iter()
             .filter_map(|(node, color)| match node {
                 LArg::Var { sym } => Some((sym, arg_from_color(color))),
                 LArg::Reg { .. } => None,
             })
-            .collect();
+            .collect()
+    }
+}
 
-        (colors, stack_space)
+/// Returns the color of a register. Registers that cannot be assigned to variables have a
+/// negative color.
+pub(super) fn color_from_reg(reg: Reg) -> isize {
+    match reg {
+        Reg::RCX => 0,
+        Reg::RDX => 1,
+        Reg::RSI => 2,
+        Reg::RDI => 3,
+        Reg::R8 => 4,
+        Reg::R9 => 5,
+        Reg::R10 => 6,
+        Reg::RBX => 7,
+        Reg::R12 => 8,
+        Reg::R13 => 9,
+        Reg::R14 => 10,
+        Reg::RAX => -1,
+        Reg::RSP => -2,
+        Reg::RBP => -3,
+        Reg::R11 => -4,
+        Reg::R15 => -5,
     }
 }
 
-fn arg_from_color(i: isize) -> Arg {
+pub(super) fn arg_from_color(i: isize) -> Arg {
     match i {
         -5 => Arg::Reg { reg: Reg::R15 },
         -4 => Arg::Reg { reg: Reg::R11 },
@@ -105,9 +153,10 @@ fn arg_from_color(i: isize) -> Arg {
                 i > 10,
                 "Something went wrong while coloring the assign graph."
             );
+            // The slots are placed in the frame of each function by `layout_frames`.
             Arg::Deref {
                 reg: Reg::RBP,
-                off: (-8 * (i - 10)) as i64 - 256,
+                off: (-8 * (i - 10)) as i64,
             }
         }
     }
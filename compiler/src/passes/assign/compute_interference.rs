@@ -1,19 +1,31 @@
-use crate::passes::assign::include_liveness::{handle_instr, ReadWriteOp};
 use crate::passes::assign::{InterferenceGraph, LArg, LX86VarProgram};
-use crate::passes::select::VarArg;
+use crate::passes::select::{handle_instr, Instr, ReadWriteOp, VarArg};
+use crate::utils::gen_sym::UniqueSym;
 use petgraph::graphmap::GraphMap;
 use petgraph::Undirected;
-use std::collections::HashMap;
 
 impl<'p> LX86VarProgram<'p> {
     #[must_use]
     pub fn compute_interference(&self) -> InterferenceGraph<'p> {
         let mut graph = GraphMap::<LArg<'p>, (), Undirected>::new();
+        let mut moves = GraphMap::<LArg<'p>, (), Undirected>::new();
 
         for block in self.blocks.values() {
             for (instr, live_after) in &block.instrs {
-                //TODO move optimization: If instruction is a move instruction then for every in w in writes, if w != dst and v != src, add the edge (dst, w).
-                handle_instr(instr, &HashMap::new(), |arg, op| {
+                // The destination of a move holds the same value as its source afterwards, so they
+                // do not interfere, and can share a register.
+                let move_src = match instr {
+                    Instr::Movq { src, dst } => match (larg(src), larg(dst)) {
+                        (Some(src), Some(dst)) if src != dst => {
+                            moves.add_edge(src, dst, ());
+                            Some(src)
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                };
+
+                handle_instr(instr, |arg, op| {
                     let w = match (arg, op) {
                         (VarArg::Reg { reg }, ReadWriteOp::Write | ReadWriteOp::ReadWrite) => {
                             LArg::Reg { reg: *reg }
@@ -31,7 +43,7 @@ impl<'p> LX86VarProgram<'p> {
 
                     graph.add_node(w);
                     for &l in live_after {
-                        if w == l {
+                        if w == l || Some(l) == move_src {
                             continue;
                         };
                         graph.add_edge(w, l, ());
@@ -40,6 +52,17 @@ impl<'p> LX86VarProgram<'p> {
             }
         }
 
-        InterferenceGraph(graph)
+        InterferenceGraph {
+            interference: graph,
+            moves,
+        }
+    }
+}
+
+fn larg<'p>(arg: &VarArg<UniqueSym<'p>>) -> Option<LArg<'p>> {
+    match arg {
+        VarArg::XVar { sym } => Some(LArg::Var { sym: *sym }),
+        VarArg::Reg { reg } => Some(LArg::Reg { reg: *reg }),
+        VarArg::Imm { .. } | VarArg::Deref { .. } => None,
     }
 }
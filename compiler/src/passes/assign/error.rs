@@ -0,0 +1,11 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum AssignError {
+    #[error("No register is free to patch the instruction `{instr}` in an asm block.")]
+    #[diagnostic(help(
+        "The instruction needs a scratch register, but RAX, R11 and R15 are all in use. Keep one of them free around it."
+    ))]
+    NoScratchRegister { instr: String },
+}
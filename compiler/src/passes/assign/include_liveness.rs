@@ -2,189 +2,171 @@ use crate::utils::gen_sym::UniqueSym;
 
 use crate::passes::assign::{LArg, LBlock, LX86VarProgram};
 use crate::passes::select::{
-    Block, Instr, InstrSelected, Reg, VarArg, X86Selected, CALLER_SAVED, SYSCALL_REGS,
+    handle_instr, Block, Instr, InstrSelected, ReadWriteOp, VarArg, X86Selected,
 };
-use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use fixedbitset::FixedBitSet;
+use std::collections::HashMap;
 
 impl<'p> X86Selected<'p> {
+    /// Computes the locations that are live after every instruction. The locations that are live
+    /// at the start of each block are found with a backward worklist algorithm: whenever they
+    /// change for a block, its predecessors are analyzed again. Locations are numbered, such that
+    /// the sets of live locations can be stored as bitsets.
     #[must_use]
     pub(super) fn include_liveness(self) -> LX86VarProgram<'p> {
-        // maps block names to what is live before the block
-        let mut before_map = HashMap::<UniqueSym, HashSet<LArg>>::new();
-        // maps block names to blocks with liveness info added
-        let mut liveness = HashMap::<UniqueSym, LBlock>::new();
-
-        let mut changed = true;
-
-        while changed {
-            changed = false;
-
-            for (sym, block) in &self.blocks {
-                let (new_liveness, before) = block_liveness(block, &before_map);
-
-                match before_map.entry(*sym) {
-                    Entry::Occupied(mut e) => {
-                        if e.get() != &before {
-                            changed = true;
-                            e.insert(before);
-                        }
+        let syms = self.blocks.keys().copied().collect::<Vec<_>>();
+        let indices = syms
+            .iter()
+            .enumerate()
+            .map(|(i, sym)| (*sym, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut locations = Locations::default();
+        let mut preds = vec![Vec::new(); syms.len()];
+        for (i, sym) in syms.iter().enumerate() {
+            for instr in &self.blocks[sym].instrs {
+                handle_instr(instr, |arg, _| {
+                    if let Some(larg) = larg(arg) {
+                        locations.insert(larg);
                     }
-                    Entry::Vacant(e) => {
-                        changed = true;
-                        e.insert(before);
+                });
+                for lbl in successors(instr) {
+                    if let Some(&succ) = indices.get(lbl) {
+                        preds[succ].push(i);
                     }
                 }
+            }
+        }
 
-                match liveness.get(sym) {
-                    None => {
-                        liveness.insert(*sym, new_liveness);
-                        changed = true;
-                    }
-                    Some(old_liveness) => {
-                        if *old_liveness != new_liveness {
-                            liveness.insert(*sym, new_liveness);
-                            changed = true;
-                        }
+        // Maps blocks to what is live before the block.
+        let mut before = vec![FixedBitSet::with_capacity(locations.len()); syms.len()];
+
+        // Blocks are popped from the back, so the blocks that are last in `syms` are analyzed
+        // first. Since the successors of a block tend to come after it, this reverse order
+        // mostly analyzes a block after its successors.
+        let mut worklist = (0..syms.len()).collect::<Vec<_>>();
+        let mut queued = FixedBitSet::with_capacity(syms.len());
+        queued.insert_range(..);
+
+        while let Some(i) = worklist.pop() {
+            queued.set(i, false);
+
+            let live = block_liveness(
+                &self.blocks[&syms[i]],
+                &indices,
+                &before,
+                &locations,
+                |_, _| {},
+            );
+            if live != before[i] {
+                before[i] = live;
+                for &pred in &preds[i] {
+                    if !queued.put(pred) {
+                        worklist.push(pred);
                     }
                 }
             }
         }
 
+        let blocks = self
+            .blocks
+            .into_iter()
+            .map(|(sym, block)| {
+                let mut instrs = Vec::with_capacity(block.instrs.len());
+                block_liveness(&block, &indices, &before, &locations, |instr, live| {
+                    let live = live.ones().map(|i| locations.larg(i)).collect();
+                    instrs.push((instr.clone(), live));
+                });
+                instrs.reverse();
+                (sym, LBlock { instrs })
+            })
+            .collect();
+
         LX86VarProgram {
-            blocks: liveness,
+            blocks,
             entry: self.entry,
+            fns: self.fns,
             std: self.std,
         }
     }
 }
 
+/// Numbers the locations that occur in the program.
+#[derive(Default)]
+struct Locations<'p> {
+    largs: Vec<LArg<'p>>,
+    indices: HashMap<LArg<'p>, usize>,
+}
+
+impl<'p> Locations<'p> {
+    fn insert(&mut self, larg: LArg<'p>) {
+        self.indices.entry(larg).or_insert_with(|| {
+            self.largs.push(larg);
+            self.largs.len() - 1
+        });
+    }
+
+    fn larg(&self, index: usize) -> LArg<'p> {
+        self.largs[index]
+    }
+
+    fn len(&self) -> usize {
+        self.largs.len()
+    }
+}
+
+/// Walks backwards through the block, and calls `instr` on every instruction with the locations
+/// that are live after it. Returns the locations that are live before the block.
 fn block_liveness<'p>(
     block: &Block<'p, VarArg<UniqueSym<'p>>>,
-    before_map: &HashMap<UniqueSym<'p>, HashSet<LArg<'p>>>,
-) -> (LBlock<'p>, HashSet<LArg<'p>>) {
-    let mut instrs = Vec::new();
-    let mut live = HashSet::new();
-
-    for instr in block.instrs.iter().rev() {
-        let last_live = live.clone();
-
-        handle_instr(instr, before_map, |arg, op| match (arg, op) {
-            (VarArg::Imm { .. }, _) => {}
-            (VarArg::Reg { reg }, ReadWriteOp::Read | ReadWriteOp::ReadWrite) => {
-                live.insert(LArg::Reg { reg: *reg });
-            }
-            (VarArg::Reg { reg }, ReadWriteOp::Write) => {
-                live.remove(&LArg::Reg { reg: *reg });
+    indices: &HashMap<UniqueSym<'p>, usize>,
+    before: &[FixedBitSet],
+    locations: &Locations<'p>,
+    mut instr: impl FnMut(&InstrSelected<'p>, &FixedBitSet),
+) -> FixedBitSet {
+    let mut live = FixedBitSet::with_capacity(locations.len());
+
+    for i in block.instrs.iter().rev() {
+        instr(i, &live);
+
+        for lbl in successors(i) {
+            if let Some(&succ) = indices.get(lbl) {
+                live.union_with(&before[succ]);
             }
-            (VarArg::XVar { sym }, ReadWriteOp::Read | ReadWriteOp::ReadWrite) => {
-                live.insert(LArg::Var { sym: *sym });
-            }
-            (VarArg::XVar { sym }, ReadWriteOp::Write) => {
-                live.remove(&LArg::Var { sym: *sym });
-            }
-            (VarArg::Deref { reg, .. }, _) => {
-                live.insert(LArg::Reg { reg: *reg });
+        }
+        handle_instr(i, |arg, op| {
+            let Some(larg) = larg(arg) else {
+                return;
+            };
+            let index = locations.indices[&larg];
+            match (arg, op) {
+                (VarArg::Deref { .. }, _) | (_, ReadWriteOp::Read | ReadWriteOp::ReadWrite) => {
+                    live.insert(index);
+                }
+                (_, ReadWriteOp::Write) => {
+                    live.set(index, false);
+                }
             }
         });
-
-        instrs.push((instr.clone(), last_live));
     }
 
-    instrs.reverse();
-    (LBlock { instrs }, live)
+    live
 }
 
-pub enum ReadWriteOp {
-    Read,
-    Write,
-    ReadWrite,
+/// Returns the location that an argument reads or writes. Dereferences read their register.
+fn larg<'p>(arg: &VarArg<UniqueSym<'p>>) -> Option<LArg<'p>> {
+    match arg {
+        VarArg::Imm { .. } => None,
+        VarArg::Reg { reg } | VarArg::Deref { reg, .. } => Some(LArg::Reg { reg: *reg }),
+        VarArg::XVar { sym } => Some(LArg::Var { sym: *sym }),
+    }
 }
 
-pub fn handle_instr<'p>(
-    instr: &InstrSelected<'p>,
-    before_map: &HashMap<UniqueSym<'p>, HashSet<LArg<'p>>>,
-    mut arg: impl FnMut(&VarArg<UniqueSym<'p>>, ReadWriteOp),
-) {
-    use ReadWriteOp::Read as R;
-    use ReadWriteOp::ReadWrite as RW;
-    use ReadWriteOp::Write as W;
-
+/// Returns the blocks that the instruction can jump to.
+fn successors<'a, 'p>(instr: &'a InstrSelected<'p>) -> &'a [UniqueSym<'p>] {
     match instr {
-        Instr::Addq { src, dst }
-        | Instr::Subq { src, dst }
-        | Instr::Andq { src, dst }
-        | Instr::Orq { src, dst }
-        | Instr::Xorq { src, dst } => {
-            arg(dst, RW);
-            arg(src, R);
-        }
-        Instr::Cmpq { src, dst } => {
-            arg(dst, R);
-            arg(src, R);
-        }
-        Instr::Movq { src, dst } => {
-            arg(dst, W);
-            arg(src, R);
-        }
-        Instr::Pushq { src } => {
-            arg(src, R);
-        }
-        Instr::Popq { dst } => {
-            arg(dst, W);
-        }
-        Instr::Negq { dst } | Instr::Notq { dst } => {
-            arg(dst, RW);
-        }
-        Instr::CallqDirect { arity, .. } => {
-            for reg in CALLER_SAVED.into_iter().skip(*arity) {
-                arg(&VarArg::Reg { reg }, W);
-            }
-            for reg in CALLER_SAVED.into_iter().take(*arity) {
-                arg(&VarArg::Reg { reg }, RW);
-            }
-        }
-        Instr::Syscall { arity } => {
-            for reg in CALLER_SAVED {
-                arg(&VarArg::Reg { reg }, W);
-            }
-            for reg in SYSCALL_REGS.into_iter().take(*arity) {
-                arg(&VarArg::Reg { reg }, R);
-            }
-        }
-        Instr::Retq => {
-            // Because the return value of our function is in RAX, we need to consider it being read at the end of a block.
-            arg(&VarArg::Reg { reg: Reg::RAX }, R);
-        }
-        Instr::Setcc { .. } => {
-            arg(&VarArg::Reg { reg: Reg::RAX }, W);
-        }
-        Instr::Mulq { src } => {
-            arg(&VarArg::Reg { reg: Reg::RDX }, W);
-            arg(&VarArg::Reg { reg: Reg::RAX }, RW);
-            arg(src, R);
-        }
-        Instr::Divq { divisor } => {
-            arg(&VarArg::Reg { reg: Reg::RDX }, RW);
-            arg(&VarArg::Reg { reg: Reg::RAX }, RW);
-            arg(divisor, R);
-        }
-        Instr::Jmp { lbl } | Instr::Jcc { lbl, .. } => {
-            for larg in before_map.get(lbl).unwrap_or(&HashSet::new()) {
-                arg(&(*larg).into(), R);
-            }
-        }
-        Instr::LoadLbl { dst, .. } => {
-            arg(dst, W);
-        }
-        Instr::CallqIndirect { src, arity } => {
-            for reg in CALLER_SAVED.into_iter().skip(*arity) {
-                arg(&VarArg::Reg { reg }, W);
-            }
-            for reg in CALLER_SAVED.into_iter().take(*arity) {
-                arg(&VarArg::Reg { reg }, RW);
-            }
-            arg(src, R);
-        }
+        Instr::Jmp { lbl } | Instr::Jcc { lbl, .. } => std::slice::from_ref(lbl),
+        Instr::JmpTable { lbls, .. } => lbls,
+        _ => &[],
     }
 }
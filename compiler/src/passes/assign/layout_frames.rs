@@ -0,0 +1,195 @@
+use crate::passes::assign::Arg;
+use crate::passes::select::{
+    handle_instr, Block, Instr, ReadWriteOp, Reg, VarArg, CALLEE_SAVED_NO_STACK,
+};
+use crate::utils::gen_sym::UniqueSym;
+use crate::{addq, imm, movq, popq, pushq, reg, subq};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::mem;
+
+/// Lays out the stack frame of each function. The prologue of a function saves the callee-saved
+/// registers that it writes to and allocates space for its spilled variables, and its epilogue
+/// restores them. Functions that do not call other functions and do not use the stack keep the
+/// frame pointer of their caller, so they leave out the frame-pointer setup that `select` emits.
+///
+/// The stack pointer is kept 16-byte aligned at calls, assuming it was aligned at the call to the
+/// function itself.
+pub(super) fn layout_frames<'p>(
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+    fns: &HashSet<UniqueSym<'p>>,
+) {
+    for &entry in fns {
+        let fn_blocks = fn_blocks(entry, blocks);
+
+        // Remove the frame-pointer setup, which is added back below if it is needed.
+        let prologue = blocks.get_mut(&entry).unwrap().instrs.drain(..2);
+        assert!(
+            matches!(
+                prologue.as_slice(),
+                [
+                    Instr::Pushq {
+                        src: Arg::Reg { reg: Reg::RBP }
+                    },
+                    Instr::Movq {
+                        src: Arg::Reg { reg: Reg::RSP },
+                        dst: Arg::Reg { reg: Reg::RBP }
+                    }
+                ]
+            ),
+            "Functions should start with the prologue emitted by `select`."
+        );
+        drop(prologue);
+        for sym in &fn_blocks {
+            let instrs = &mut blocks.get_mut(sym).unwrap().instrs;
+            if let [.., Instr::Popq {
+                dst: Arg::Reg { reg: Reg::RBP },
+            }, Instr::Retq] = instrs.as_slice()
+            {
+                instrs.remove(instrs.len() - 2);
+            }
+        }
+
+        let slots = compact_slots(&fn_blocks, blocks);
+
+        let mut saved = Vec::new();
+        let mut frame = false;
+        for sym in &fn_blocks {
+            for instr in &blocks[sym].instrs {
+                frame |= uses_stack(instr);
+                handle_instr(&instr.clone().fmap(Into::into), |arg, op| {
+                    if let (VarArg::Reg { reg }, ReadWriteOp::Write | ReadWriteOp::ReadWrite) =
+                        (arg, op)
+                    {
+                        if CALLEE_SAVED_NO_STACK.contains(reg) && !saved.contains(reg) {
+                            saved.push(*reg);
+                        }
+                    }
+                });
+            }
+        }
+        saved.sort();
+
+        // The return address and the frame pointer take 16 bytes, so the spilled variables and
+        // the saved registers need to take a multiple of 16 bytes as well.
+        let size = 8 * (slots + (slots + saved.len()) % 2) as i64;
+
+        let mut prologue = Vec::new();
+        if frame {
+            prologue.push(pushq!(reg!(RBP)));
+            prologue.push(movq!(reg!(RSP), reg!(RBP)));
+            if size != 0 {
+                prologue.push(subq!(imm!(size), reg!(RSP)));
+            }
+        }
+        prologue.extend(saved.iter().map(|&reg| pushq!(Arg::Reg { reg })));
+        blocks.get_mut(&entry).unwrap().instrs.splice(..0, prologue);
+
+        let mut epilogue = saved
+            .iter()
+            .rev()
+            .map(|&reg| popq!(Arg::Reg { reg }))
+            .collect::<Vec<_>>();
+        if frame {
+            if size != 0 {
+                epilogue.push(addq!(imm!(size), reg!(RSP)));
+            }
+            epilogue.push(popq!(reg!(RBP)));
+        }
+        for sym in &fn_blocks {
+            let instrs = &mut blocks.get_mut(sym).unwrap().instrs;
+            if let Some(Instr::Retq) = instrs.last() {
+                let ret = instrs.len() - 1;
+                instrs.splice(ret..ret, epilogue.iter().cloned());
+            }
+        }
+    }
+}
+
+/// Renumbers the stack slots of spilled variables that the function uses, such that they are
+/// right below the frame pointer. Returns the number of slots.
+fn compact_slots<'p>(
+    fn_blocks: &HashSet<UniqueSym<'p>>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+) -> usize {
+    let offsets = RefCell::new(BTreeSet::new());
+    for sym in fn_blocks {
+        for instr in &blocks[sym].instrs {
+            instr.clone().fmap(|arg| {
+                if let Arg::Deref { reg: Reg::RBP, off } = arg {
+                    if off < 0 {
+                        offsets.borrow_mut().insert(-off);
+                    }
+                }
+                arg
+            });
+        }
+    }
+    let slots = offsets
+        .into_inner()
+        .into_iter()
+        .zip(1..)
+        .map(|(off, slot)| (-off, -8 * slot))
+        .collect::<HashMap<_, _>>();
+
+    for sym in fn_blocks {
+        let block = blocks.get_mut(sym).unwrap();
+        block.instrs = mem::take(&mut block.instrs)
+            .into_iter()
+            .map(|instr| {
+                instr.fmap(|arg| match arg {
+                    Arg::Deref { reg: Reg::RBP, off } if off < 0 => Arg::Deref {
+                        reg: Reg::RBP,
+                        off: slots[&off],
+                    },
+                    arg => arg,
+                })
+            })
+            .collect();
+    }
+    slots.len()
+}
+
+/// Returns the blocks that belong to the function, which are the blocks that can be jumped to from
+/// its entry.
+fn fn_blocks<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+) -> HashSet<UniqueSym<'p>> {
+    let mut visited = HashSet::new();
+    let mut worklist = vec![entry];
+    while let Some(sym) = worklist.pop() {
+        if !visited.insert(sym) {
+            continue;
+        }
+        for instr in &blocks[&sym].instrs {
+            match instr {
+                Instr::Jmp { lbl } | Instr::Jcc { lbl, .. } => worklist.push(*lbl),
+                Instr::JmpTable { lbls, .. } => worklist.extend(lbls),
+                _ => {}
+            }
+        }
+    }
+    visited
+}
+
+/// Whether the instruction needs a stack frame, because it calls a function or refers to the
+/// stack or frame pointer.
+fn uses_stack(instr: &Instr<Arg, UniqueSym>) -> bool {
+    if matches!(
+        instr,
+        Instr::CallqDirect { .. } | Instr::CallqIndirect { .. }
+    ) {
+        return true;
+    }
+    let uses = Cell::new(false);
+    instr.clone().fmap(|arg| {
+        if let Arg::Reg { reg } | Arg::Deref { reg, .. } = arg {
+            if matches!(reg, Reg::RSP | Reg::RBP) {
+                uses.set(true);
+            }
+        }
+        arg
+    });
+    uses.get()
+}
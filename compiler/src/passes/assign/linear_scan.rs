@@ -0,0 +1,197 @@
+use crate::passes::assign::color_interference::{arg_from_color, color_from_reg, REGISTERS};
+use crate::passes::assign::{Arg, LX86VarProgram};
+use crate::passes::select::{handle_instr, Instr, ReadWriteOp, VarArg};
+use crate::utils::gen_sym::UniqueSym;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::visit::DfsPostOrder;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+impl<'p> LX86VarProgram<'p> {
+    /// Assigns a register or stack slot to every variable by scanning the live intervals of the
+    /// variables once, in order of their start. Unlike `color`, this does not build an
+    /// interference graph, which makes it much faster on large programs at the cost of worse
+    /// assignments. A variable is live from its first to its last occurrence in the blocks, so
+    /// two variables only share a location if they are never live at the same time.
+    #[must_use]
+    pub(super) fn linear_scan(&self) -> HashMap<UniqueSym<'p>, Arg> {
+        let mut intervals = HashMap::<UniqueSym<'p>, (usize, usize)>::new();
+        // Registers that a variable cannot be assigned, because they are written while the
+        // variable is live, or are live while the variable is written.
+        let mut forbidden = HashMap::<UniqueSym<'p>, HashSet<isize>>::new();
+
+        for (position, (instr, live_after)) in self
+            .block_order()
+            .into_iter()
+            .flat_map(|sym| &self.blocks[&sym].instrs)
+            .enumerate()
+        {
+            for arg in live_after {
+                if let VarArg::XVar { sym } = (*arg).into() {
+                    extend(&mut intervals, sym, position);
+                }
+            }
+
+            // The destination of a move may share a register with its source.
+            let move_src = match instr {
+                Instr::Movq { src, .. } => Some(src.clone()),
+                _ => None,
+            };
+
+            handle_instr(instr, |arg, op| {
+                if let VarArg::XVar { sym } = arg {
+                    extend(&mut intervals, *sym, position);
+                }
+                if matches!(op, ReadWriteOp::Read) {
+                    return;
+                }
+
+                for &l in live_after {
+                    let l = VarArg::from(l);
+                    if Some(&l) == move_src.as_ref() {
+                        continue;
+                    }
+                    match (arg.clone(), l) {
+                        (VarArg::Reg { reg }, VarArg::XVar { sym })
+                        | (VarArg::XVar { sym }, VarArg::Reg { reg }) => {
+                            forbidden
+                                .entry(sym)
+                                .or_default()
+                                .insert(color_from_reg(reg));
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        let mut intervals = intervals
+            .into_iter()
+            .map(|(sym, (start, end))| (start, end, sym))
+            .collect::<Vec<_>>();
+        intervals.sort();
+
+        let mut colors = HashMap::<UniqueSym<'p>, isize>::new();
+        let mut active = BTreeSet::<(usize, UniqueSym<'p>)>::new();
+        let mut free_registers = (0..REGISTERS).collect::<BTreeSet<_>>();
+        let mut free_slots = BTreeSet::new();
+        let mut next_slot = REGISTERS;
+        let mut new_slot = |free_slots: &mut BTreeSet<isize>| {
+            free_slots.pop_first().unwrap_or_else(|| {
+                next_slot += 1;
+                next_slot - 1
+            })
+        };
+
+        for (start, end, sym) in intervals {
+            // Release the locations of the intervals that ended before this one starts.
+            while let Some(&(active_end, active_sym)) = active.first() {
+                if active_end >= start {
+                    break;
+                }
+                active.pop_first();
+                match colors[&active_sym] {
+                    color @ 0..REGISTERS => free_registers.insert(color),
+                    color => free_slots.insert(color),
+                };
+            }
+
+            let forbidden = forbidden.remove(&sym).unwrap_or_default();
+            let register = free_registers
+                .iter()
+                .copied()
+                .find(|color| !forbidden.contains(color));
+
+            let color = if let Some(color) = register {
+                free_registers.remove(&color);
+                color
+            } else {
+                // Spill the interval that ends last, which is either the current one or an active
+                // one whose register the current one can take over.
+                let victim = active
+                    .iter()
+                    .rev()
+                    .take_while(|&&(active_end, _)| active_end > end)
+                    .map(|&(_, active_sym)| active_sym)
+                    .find(|active_sym| {
+                        let color = colors[active_sym];
+                        (0..REGISTERS).contains(&color) && !forbidden.contains(&color)
+                    });
+
+                match victim {
+                    Some(victim) => {
+                        let slot = new_slot(&mut free_slots);
+                        colors.insert(victim, slot).unwrap()
+                    }
+                    None => new_slot(&mut free_slots),
+                }
+            };
+
+            colors.insert(sym, color);
+            active.insert((end, sym));
+        }
+
+        colors
+            .into_iter()
+            .map(|(sym, color)| (sym, arg_from_color(color)))
+            .collect()
+    }
+
+    /// Returns the blocks in reverse postorder of the control-flow graph of every function, such
+    /// that the intervals of variables that are local to a function or loop stay short. Blocks
+    /// that cannot be reached from a function entry come last.
+    fn block_order(&self) -> Vec<UniqueSym<'p>> {
+        let mut cfg = DiGraphMap::<UniqueSym<'p>, ()>::new();
+        for (sym, block) in &self.blocks {
+            cfg.add_node(*sym);
+            for (instr, _) in &block.instrs {
+                match instr {
+                    Instr::Jmp { lbl } | Instr::Jcc { lbl, .. } => {
+                        cfg.add_edge(*sym, *lbl, ());
+                    }
+                    Instr::JmpTable { lbls, .. } => {
+                        for lbl in lbls {
+                            cfg.add_edge(*sym, *lbl, ());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut fns = self.fns.iter().copied().collect::<Vec<_>>();
+        fns.sort();
+
+        let mut order = Vec::new();
+        let mut dfs = DfsPostOrder::empty(&cfg);
+        for entry in fns {
+            dfs.move_to(entry);
+            let start = order.len();
+            while let Some(sym) = dfs.next(&cfg) {
+                order.push(sym);
+            }
+            order[start..].reverse();
+        }
+
+        let mut unreachable = self
+            .blocks
+            .keys()
+            .filter(|sym| !dfs.finished.contains(*sym))
+            .copied()
+            .collect::<Vec<_>>();
+        unreachable.sort();
+        order.extend(unreachable);
+        order
+    }
+}
+
+/// Extends the interval of the variable to include the position.
+fn extend<'p>(
+    intervals: &mut HashMap<UniqueSym<'p>, (usize, usize)>,
+    sym: UniqueSym<'p>,
+    position: usize,
+) {
+    intervals
+        .entry(sym)
+        .and_modify(|(_, end)| *end = position)
+        .or_insert((position, position));
+}
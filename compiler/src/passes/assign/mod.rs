@@ -1,27 +1,42 @@
 mod assign;
 mod color_interference;
 mod compute_interference;
+pub mod error;
 mod include_liveness;
+mod layout_frames;
+mod linear_scan;
+mod spill_costs;
 #[cfg(test)]
 mod tests;
 
 use crate::passes::select::std_lib::Std;
 use crate::passes::select::{Block, InstrSelected, Reg, VarArg, X86Selected};
 use crate::utils::gen_sym::UniqueSym;
+use clap::ValueEnum;
 use derive_more::Display;
 use functor_derive::Functor;
 use petgraph::graphmap::GraphMap;
 use petgraph::Undirected;
 use std::collections::{HashMap, HashSet};
 
+/// The algorithm that `assign` uses to place variables in registers and stack slots.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Allocator {
+    /// Colors the interference graph of the variables, which gives the best assignments.
+    #[default]
+    Coloring,
+    /// Scans the live intervals of the variables once, which is faster on large programs.
+    LinearScan,
+}
+
 pub struct X86Assigned<'p> {
     pub blocks: HashMap<UniqueSym<'p>, Block<'p, Arg>>,
     pub entry: UniqueSym<'p>,
-    pub stack_space: usize,
+    pub fns: HashSet<UniqueSym<'p>>,
     pub std: Std<'p>,
 }
 
-#[derive(Clone, Display)]
+#[derive(Clone, PartialEq, Display)]
 pub enum Arg {
     #[display(fmt = "${val}")]
     Imm { val: i64 },
@@ -31,11 +46,17 @@ pub enum Arg {
     Deref { reg: Reg, off: i64 },
 }
 
-pub struct InterferenceGraph<'p>(GraphMap<LArg<'p>, (), Undirected>);
+pub struct InterferenceGraph<'p> {
+    /// Edges between locations that are live at the same time, which cannot share a register.
+    interference: GraphMap<LArg<'p>, (), Undirected>,
+    /// Edges between locations that are moved between, which should share a register if possible.
+    moves: GraphMap<LArg<'p>, (), Undirected>,
+}
 
 pub struct LX86VarProgram<'p> {
     pub blocks: HashMap<UniqueSym<'p>, LBlock<'p>>,
     pub entry: UniqueSym<'p>,
+    pub fns: HashSet<UniqueSym<'p>>,
     pub std: Std<'p>,
 }
 
@@ -82,6 +103,7 @@ impl<'p> From<X86Assigned<'p>> for X86Selected<'p> {
         X86Selected {
             blocks: value.blocks.fmap(|v| v.fmap(Into::into)),
             entry: value.entry,
+            fns: value.fns,
             std: value.std,
         }
     }
@@ -0,0 +1,96 @@
+use crate::passes::assign::LX86VarProgram;
+use crate::passes::select::{handle_instr, Instr, VarArg};
+use crate::utils::gen_sym::UniqueSym;
+use petgraph::algo::dominators::simple_fast;
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
+use std::collections::{HashMap, HashSet};
+
+/// Every level of loop nesting is assumed to execute its blocks this many times more often.
+pub(super) const LOOP_WEIGHT: usize = 10;
+
+impl<'p> LX86VarProgram<'p> {
+    /// Estimates the cost of spilling each variable, which is the number of times that it is used,
+    /// weighted by how often the blocks that use it are expected to execute.
+    #[must_use]
+    pub(super) fn spill_costs(&self) -> HashMap<UniqueSym<'p>, usize> {
+        self.spill_costs_with(LOOP_WEIGHT)
+    }
+
+    /// Estimates the spill costs, where every level of loop nesting multiplies the weight of its
+    /// blocks by `loop_weight`.
+    pub(super) fn spill_costs_with(&self, loop_weight: usize) -> HashMap<UniqueSym<'p>, usize> {
+        let depths = self.loop_depths();
+
+        let mut costs = HashMap::new();
+        for (sym, block) in &self.blocks {
+            let weight = loop_weight.saturating_pow(depths.get(sym).copied().unwrap_or_default());
+            for (instr, _) in &block.instrs {
+                handle_instr(instr, |arg, _| {
+                    if let VarArg::XVar { sym } = arg {
+                        let cost = costs.entry(*sym).or_default();
+                        *cost = weight.saturating_add(*cost);
+                    }
+                });
+            }
+        }
+        costs
+    }
+
+    /// Returns the number of loops that each block of the functions is nested in. The loops are
+    /// found in the control-flow graph of every function, as the natural loops of its back edges.
+    fn loop_depths(&self) -> HashMap<UniqueSym<'p>, u32> {
+        let mut cfg = DiGraphMap::<UniqueSym<'p>, ()>::new();
+        for (sym, block) in &self.blocks {
+            cfg.add_node(*sym);
+            for (instr, _) in &block.instrs {
+                match instr {
+                    Instr::Jmp { lbl } | Instr::Jcc { lbl, .. } => {
+                        cfg.add_edge(*sym, *lbl, ());
+                    }
+                    Instr::JmpTable { lbls, .. } => {
+                        for lbl in lbls {
+                            cfg.add_edge(*sym, *lbl, ());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut depths = HashMap::new();
+        for &entry in &self.fns {
+            let dominators = simple_fast(&cfg, entry);
+
+            // Loops with the same header are merged into one.
+            let mut loops = HashMap::<_, HashSet<_>>::new();
+            for (from, header, ()) in cfg.all_edges() {
+                let is_back_edge = dominators
+                    .dominators(from)
+                    .is_some_and(|mut doms| doms.any(|dom| dom == header));
+                if !is_back_edge {
+                    continue;
+                }
+
+                // The loop consists of the blocks that reach the back edge without passing
+                // through the header.
+                let body = loops
+                    .entry(header)
+                    .or_insert_with(|| HashSet::from([header]));
+                let mut worklist = vec![from];
+                while let Some(sym) = worklist.pop() {
+                    if body.insert(sym) {
+                        worklist.extend(cfg.neighbors_directed(sym, Direction::Incoming));
+                    }
+                }
+            }
+
+            for body in loops.values() {
+                for sym in body {
+                    *depths.entry(*sym).or_default() += 1;
+                }
+            }
+        }
+        depths
+    }
+}
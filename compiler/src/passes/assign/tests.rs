@@ -1,24 +1,41 @@
 use crate::interpreter::TestIO;
+use crate::passes::assign::error::AssignError;
+use crate::passes::assign::spill_costs::LOOP_WEIGHT;
+use crate::passes::assign::{Allocator, Arg};
 use crate::passes::parse::parse::parse_program;
-use crate::passes::select::X86Selected;
+use crate::passes::select::{Instr, Reg, X86Selected};
 use crate::utils::gen_sym::gen_sym;
 use crate::utils::split_test::split_test;
-use crate::{block, callq_direct, movq, reg};
+use crate::{addq, block, callq_direct, deref, imm, movq, popq, pushq, reg, retq, var};
+use std::collections::{HashMap, HashSet};
 use test_each_file::test_each_file;
 
 fn assign([test]: [&str; 1]) {
-    let (input, expected_output, expected_return, _) = split_test(test);
+    assign_with(test, Allocator::Coloring);
+}
+
+fn assign_linear_scan([test]: [&str; 1]) {
+    assign_with(test, Allocator::LinearScan);
+}
+
+fn assign_with(test: &str, allocator: Allocator) {
+    let (input, expected_output, expected_return, _, args) = split_test(test);
 
     let mut program: X86Selected = parse_program(test)
         .unwrap()
         .validate()
         .unwrap()
         .reveal()
+        .decide()
+        .inline()
         .atomize()
+        .fold()
         .explicate()
+        .prune()
         .eliminate()
         .select()
-        .assign()
+        .assign_with(allocator)
+        .unwrap()
         .into();
 
     // Redirect program to exit
@@ -26,14 +43,16 @@ fn assign([test]: [&str; 1]) {
     program.blocks.insert(
         new_entry,
         block!(
-            callq_direct!(program.entry, 0),
+            // The stack pointer points to `argc`, which is passed as the `Args` of `main`.
+            movq!(deref!(RSP, 0), reg!(RAX)),
+            callq_direct!(program.entry, 1),
             movq!(reg!(RAX), reg!(RDI)),
             callq_direct!(program.std["exit"], 1)
         ),
     );
     program.entry = new_entry;
 
-    let mut io = TestIO::new(input);
+    let mut io = TestIO::from_bytes(input).with_args(args);
     let result = program.interpret(&mut io);
 
     assert_eq!(result, expected_return.into(), "Incorrect program result.");
@@ -41,3 +60,148 @@ fn assign([test]: [&str; 1]) {
 }
 
 test_each_file! { for ["test"] in "./programs/good" as assign => assign }
+test_each_file! { for ["test"] in "./programs/good" as assign_linear_scan => assign_linear_scan }
+
+#[test]
+fn coalesce_move() {
+    let entry = gen_sym("main");
+    let a = gen_sym("a");
+    let b = gen_sym("b");
+
+    // `a` is dead after it is moved into `b`, so they do not interfere and can share a register.
+    let program = X86Selected {
+        blocks: HashMap::from([(
+            entry,
+            block!(
+                pushq!(reg!(RBP)),
+                movq!(reg!(RSP), reg!(RBP)),
+                movq!(imm!(1), var!(a)),
+                movq!(var!(a), var!(b)),
+                addq!(imm!(2), var!(b)),
+                movq!(var!(b), reg!(RAX)),
+                popq!(reg!(RBP)),
+                retq!()
+            ),
+        )]),
+        entry,
+        fns: HashSet::from([entry]),
+        std: HashMap::new(),
+    };
+
+    let is_self_move = |instr: &Instr<Arg, _>| matches!(instr, Instr::Movq { src: src @ Arg::Reg { .. }, dst } if src == dst);
+
+    let assigned = program.assign().unwrap();
+    assert_eq!(
+        assigned.blocks[&entry]
+            .instrs
+            .iter()
+            .filter(|instr| is_self_move(instr))
+            .count(),
+        1,
+        "The move from `a` to `b` should be between the same register."
+    );
+
+    let patched = assigned.patch();
+    assert!(
+        !patched.blocks[&entry].instrs.iter().any(is_self_move),
+        "The move from `a` to `b` should be removed."
+    );
+}
+
+#[test]
+fn spill_costs_keep_loop_variables_in_registers() {
+    let test = include_str!("../../../../programs/good/loops/loop_pressure.test");
+    let (input, _, expected_return, _, _) = split_test(test);
+
+    let memory_operands = |loop_weight: usize| {
+        let program = parse_program(test)
+            .unwrap()
+            .validate()
+            .unwrap()
+            .reveal()
+            .decide()
+            .inline()
+            .atomize()
+            .fold()
+            .explicate()
+            .prune()
+            .eliminate()
+            .select()
+            .include_liveness();
+
+        let costs = program.spill_costs_with(loop_weight);
+        let color_map = program.compute_interference().color(&costs);
+
+        let (result, stats) = program
+            .assign_colors(&color_map)
+            .unwrap()
+            .patch()
+            .peephole()
+            .conclude()
+            .interpret_with_stats(&mut TestIO::from_bytes(input.clone()));
+        assert_eq!(result, expected_return.into(), "Incorrect program result.");
+        stats.memory_operands
+    };
+
+    // The variables of the loops are used less often than those after the loops, so they are only
+    // kept in registers when uses in loops weigh more.
+    let weighted = memory_operands(LOOP_WEIGHT);
+    let unweighted = memory_operands(1);
+    assert!(
+        weighted < unweighted,
+        "Weighing loops should reduce the memory operands, but got {weighted} instead of {unweighted}."
+    );
+}
+
+#[test]
+fn no_scratch_register() {
+    let entry = gen_sym("main");
+    let a = gen_sym("a");
+    let b = gen_sym("b");
+
+    // Moving `a` to `b` needs a scratch register when both are spilled, but RAX, R11 and R15 are
+    // all live across the move.
+    let program = X86Selected {
+        blocks: HashMap::from([(
+            entry,
+            block!(
+                pushq!(reg!(RBP)),
+                movq!(reg!(RSP), reg!(RBP)),
+                movq!(imm!(1), reg!(RAX)),
+                movq!(imm!(2), reg!(R11)),
+                movq!(imm!(3), reg!(R15)),
+                movq!(imm!(4), var!(a)),
+                movq!(var!(a), var!(b)),
+                addq!(var!(b), reg!(RAX)),
+                addq!(reg!(R11), reg!(RAX)),
+                addq!(reg!(R15), reg!(RAX)),
+                popq!(reg!(RBP)),
+                retq!()
+            ),
+        )]),
+        entry,
+        fns: HashSet::from([entry]),
+        std: HashMap::new(),
+    };
+    let color_map = HashMap::from([
+        (
+            a,
+            Arg::Deref {
+                reg: Reg::RBP,
+                off: -8,
+            },
+        ),
+        (
+            b,
+            Arg::Deref {
+                reg: Reg::RBP,
+                off: -16,
+            },
+        ),
+    ]);
+
+    assert!(matches!(
+        program.include_liveness().assign_colors(&color_map),
+        Err(AssignError::NoScratchRegister { .. })
+    ));
+}
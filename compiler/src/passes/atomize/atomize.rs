@@ -27,6 +27,7 @@ fn atomize_def(def: DefRevealed) -> DefAtomized {
             params,
             typ,
             bdy,
+            inline,
         } => {
             let mut scope =
                 PushMap::from_iter(params.iter().map(|param| (param.sym, param.mutable)));
@@ -36,6 +37,7 @@ fn atomize_def(def: DefRevealed) -> DefAtomized {
                 params,
                 typ,
                 bdy: atomize_expr(bdy, &mut scope),
+                inline,
             }
         }
         DefRevealed::TypeDef { sym, def } => DefAtomized::TypeDef { sym, def },
@@ -77,10 +79,14 @@ fn atomize_expr<'p>(
             bnd,
             bdy,
         } => {
-            let bnd = Box::new(atomize_expr(*bnd, scope));
+            let bnd = bnd.map(|bnd| Box::new(atomize_expr(*bnd, scope)));
             let bdy = Box::new(scope.push(sym, mutable, |scope| atomize_expr(*bdy, scope)));
 
-            AExpr::Let { sym, bnd, bdy }
+            // A variable without a binding is only introduced by its first assignment.
+            match bnd {
+                Some(bnd) => AExpr::Let { sym, bnd, bdy },
+                None => bdy.inner,
+            }
         }
         RExpr::If { cnd, thn, els } => AExpr::If {
             cnd: Box::new(atomize_expr(*cnd, scope)),
@@ -102,10 +108,12 @@ fn atomize_expr<'p>(
             }
         }
         RExpr::FunRef { sym } => AExpr::FunRef { sym },
-        RExpr::Loop { bdy } => AExpr::Loop {
+        RExpr::Loop { lbl, bdy } => AExpr::Loop {
+            lbl,
             bdy: Box::new(atomize_expr(*bdy, scope)),
         },
-        RExpr::Break { bdy } => AExpr::Break {
+        RExpr::Break { lbl, bdy } => AExpr::Break {
+            lbl,
             bdy: Box::new(atomize_expr(*bdy, scope)),
         },
         RExpr::Seq { stmt, cnt } => AExpr::Seq {
@@ -116,7 +124,7 @@ fn atomize_expr<'p>(
             sym,
             bnd: Box::new(atomize_expr(*bnd, scope)),
         },
-        RExpr::Continue => AExpr::Continue,
+        RExpr::Continue { lbl } => AExpr::Continue { lbl },
         RExpr::Return { bdy } => AExpr::Return {
             bdy: Box::new(atomize_expr(*bdy, scope)),
         },
@@ -135,6 +143,21 @@ fn atomize_expr<'p>(
             field,
         },
         RExpr::Asm { instrs } => AExpr::Asm { instrs },
+        RExpr::Switch {
+            scrut,
+            lo,
+            table,
+            arms,
+        } => AExpr::Switch {
+            scrut: atomize_atom(*scrut, &mut priors, scope),
+            lo,
+            table,
+            arms: arms
+                .into_iter()
+                .map(|arm| atomize_expr(arm, scope))
+                .collect(),
+        },
+        RExpr::Match { .. } => unreachable!("`match` should have been compiled away by `decide`."),
     };
 
     // Chains all the priors with the atomized expression as the body.
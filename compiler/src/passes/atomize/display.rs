@@ -36,16 +36,27 @@ impl Display for AExpr<'_> {
             AExpr::Apply { fun, args } => {
                 write!(f, "{fun}({})", args.iter().map(|(atm, _)| atm).format(", "))
             }
-            AExpr::Loop { bdy } => {
+            AExpr::Loop { lbl, bdy } => {
+                if let Some(lbl) = lbl {
+                    write!(f, "{lbl}: ")?;
+                }
                 writeln!(f, "loop {{")?;
                 writeln!(indented(f), "{bdy}")?;
                 write!(f, "}}")
             }
-            AExpr::Break { bdy } => {
-                write!(f, "break {bdy}")
+            AExpr::Break { lbl, bdy } => {
+                write!(f, "break ")?;
+                if let Some(lbl) = lbl {
+                    write!(f, "{lbl} ")?;
+                }
+                write!(f, "{bdy}")
             }
-            AExpr::Continue => {
-                write!(f, "continue")
+            AExpr::Continue { lbl } => {
+                write!(f, "continue")?;
+                if let Some(lbl) = lbl {
+                    write!(f, " {lbl}")?;
+                }
+                Ok(())
             }
             AExpr::Return { bdy } => {
                 write!(f, "return {bdy}")
@@ -72,7 +83,29 @@ impl Display for AExpr<'_> {
             AExpr::AccessField { strct, field } => {
                 write!(f, "{strct}.{field}")
             }
-            AExpr::Asm { .. } => todo!(),
+            AExpr::Switch {
+                scrut,
+                lo,
+                table,
+                arms,
+            } => {
+                writeln!(
+                    f,
+                    "switch ({scrut} - {lo}) [{}] {{",
+                    table.iter().format(", ")
+                )?;
+                for (i, arm) in arms.iter().enumerate() {
+                    writeln!(indented(f), "{i} => {arm},")?;
+                }
+                write!(f, "}}")
+            }
+            AExpr::Asm { instrs } => {
+                writeln!(f, "asm {{")?;
+                for instr in instrs {
+                    writeln!(indented(f), "{instr}")?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
@@ -4,7 +4,7 @@ mod display;
 use crate::passes::parse::types::Type;
 use crate::passes::parse::{BinaryOp, Def, Typed, UnaryOp};
 use crate::passes::select::std_lib::Std;
-use crate::passes::select::InstrSelected;
+use crate::passes::select::AsmItemSelected;
 use crate::passes::validate::TLit;
 use crate::utils::gen_sym::UniqueSym;
 use derive_more::Display;
@@ -51,12 +51,16 @@ pub enum AExpr<'p> {
         sym: UniqueSym<'p>,
     },
     Loop {
+        lbl: Option<UniqueSym<'p>>,
         bdy: Box<Typed<'p, AExpr<'p>>>,
     },
     Break {
+        lbl: Option<UniqueSym<'p>>,
         bdy: Box<Typed<'p, AExpr<'p>>>,
     },
-    Continue,
+    Continue {
+        lbl: Option<UniqueSym<'p>>,
+    },
     Seq {
         stmt: Box<Typed<'p, AExpr<'p>>>,
         cnt: Box<Typed<'p, AExpr<'p>>>,
@@ -76,8 +80,14 @@ pub enum AExpr<'p> {
         strct: Atom<'p>,
         field: &'p str,
     },
+    Switch {
+        scrut: Atom<'p>,
+        lo: i64,
+        table: Vec<usize>,
+        arms: Vec<Typed<'p, AExpr<'p>>>,
+    },
     Asm {
-        instrs: Vec<InstrSelected<'p>>,
+        instrs: Vec<AsmItemSelected<'p>>,
     },
 }
 
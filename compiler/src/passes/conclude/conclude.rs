@@ -1,29 +1,38 @@
+use crate::deref;
 use crate::passes::conclude::X86Concluded;
-use crate::passes::patch::X86Patched;
+use crate::passes::peephole::X86Peepholed;
+use crate::passes::select::std_lib::{INITIAL_RSP, RUNTIME_OFFSET};
 use crate::utils::gen_sym::gen_sym;
 use crate::*;
 
-impl<'p> X86Patched<'p> {
+impl<'p> X86Peepholed<'p> {
     #[must_use]
     pub fn conclude(mut self) -> X86Concluded<'p> {
         let entry = gen_sym("main");
         self.blocks.insert(
             entry,
             block!(
-                pushq!(reg!(RBP)),
-                movq!(reg!(RSP), reg!(RBP)),
-                subq!(imm!(self.stack_space as i64), reg!(RSP)),
-                callq_direct!(self.entry, 0),
+                // Keep the initial stack pointer, it points to `argc`, `argv` and `envp`.
+                movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+                movq!(reg!(RSP), deref!(RCX, INITIAL_RSP)),
+                // The stack pointer is 16-byte aligned at process entry, as it should be at calls.
+                // Every function allocates its own stack frame.
+                // `argc` is passed to `main` as the count of its `Args`, if it takes them.
+                movq!(deref!(RSP, 0), reg!(RAX)),
+                callq_direct!(self.entry, 1),
                 movq!(reg!(RAX), reg!(RDI)),
-                addq!(imm!(self.stack_space as i64), reg!(RSP)),
-                popq!(reg!(RBP)),
                 callq_direct!(self.std["exit"], 1)
             ),
         );
 
+        // The new entry ends in a call to `exit`, so it never falls through to another block.
+        self.order.insert(0, entry);
+
         X86Concluded {
             blocks: self.blocks,
+            order: self.order,
             entry,
+            fns: self.fns,
             std: self.std,
         }
     }
@@ -1,9 +1,10 @@
 use crate::passes::assign::Arg;
+use crate::passes::peephole::explicit_jumps;
 use crate::passes::select::std_lib::Std;
 use crate::passes::select::{Block, X86Selected};
 use crate::utils::gen_sym::UniqueSym;
 use functor_derive::Functor;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub mod conclude;
 #[cfg(test)]
@@ -11,15 +12,19 @@ mod tests;
 
 pub struct X86Concluded<'p> {
     pub blocks: HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+    /// The order in which the blocks are laid out, see `X86Peepholed`.
+    pub order: Vec<UniqueSym<'p>>,
     pub entry: UniqueSym<'p>,
+    pub fns: HashSet<UniqueSym<'p>>,
     pub std: Std<'p>,
 }
 
 impl<'p> From<X86Concluded<'p>> for X86Selected<'p> {
     fn from(value: X86Concluded<'p>) -> Self {
         X86Selected {
-            blocks: value.blocks.fmap(|v| v.fmap(Into::into)),
+            blocks: explicit_jumps(value.blocks, &value.order).fmap(|v| v.fmap(Into::into)),
             entry: value.entry,
+            fns: value.fns,
             std: value.std,
         }
     }
@@ -5,23 +5,29 @@ use crate::utils::split_test::split_test;
 use test_each_file::test_each_file;
 
 fn conclude([test]: [&str; 1]) {
-    let (input, expected_output, expected_return, _) = split_test(test);
+    let (input, expected_output, expected_return, _, args) = split_test(test);
 
     let program: X86Selected = parse_program(test)
         .unwrap()
         .validate()
         .unwrap()
         .reveal()
+        .decide()
+        .inline()
         .atomize()
+        .fold()
         .explicate()
+        .prune()
         .eliminate()
         .select()
         .assign()
+        .unwrap()
         .patch()
+        .peephole()
         .conclude()
         .into();
 
-    let mut io = TestIO::new(input);
+    let mut io = TestIO::from_bytes(input).with_args(args);
     let result = program.interpret(&mut io);
 
     assert_eq!(result, expected_return.into(), "Incorrect program result.");
@@ -0,0 +1,426 @@
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{BinaryOp, Meta, Pattern, Typed};
+use crate::passes::reveal::{DefRevealed, PrgRevealed, RArm, RExpr};
+use crate::passes::validate::{PatternValidated, TLit};
+use crate::utils::gen_sym::{gen_sym, UniqueSym};
+
+impl<'p> PrgRevealed<'p> {
+    /// Compiles every `match` expression into a chain of `if` expressions, or into a jump table
+    /// when it matches a dense range of integers.
+    #[must_use]
+    pub fn decide(self) -> PrgRevealed<'p> {
+        PrgRevealed {
+            defs: self
+                .defs
+                .into_iter()
+                .map(|(sym, def)| (sym, decide_def(def)))
+                .collect(),
+            entry: self.entry,
+            std: self.std,
+        }
+    }
+}
+
+fn decide_def(def: DefRevealed) -> DefRevealed {
+    match def {
+        DefRevealed::Fn {
+            sym,
+            params,
+            typ,
+            bdy,
+            inline,
+        } => DefRevealed::Fn {
+            sym,
+            params,
+            typ,
+            bdy: decide_expr(bdy),
+            inline,
+        },
+        DefRevealed::TypeDef { sym, def } => DefRevealed::TypeDef { sym, def },
+    }
+}
+
+fn decide_expr<'p>(expr: Typed<'p, RExpr<'p>>) -> Typed<'p, RExpr<'p>> {
+    let boxed = |expr: Box<Typed<'p, RExpr<'p>>>| Box::new(decide_expr(*expr));
+
+    let inner = match expr.inner {
+        RExpr::Lit { .. }
+        | RExpr::Var { .. }
+        | RExpr::FunRef { .. }
+        | RExpr::Continue { .. }
+        | RExpr::Asm { .. } => expr.inner,
+        RExpr::BinaryOp {
+            op,
+            exprs: [lhs, rhs],
+        } => RExpr::BinaryOp {
+            op,
+            exprs: [boxed(lhs), boxed(rhs)],
+        },
+        RExpr::UnaryOp { op, expr } => RExpr::UnaryOp {
+            op,
+            expr: boxed(expr),
+        },
+        RExpr::Let {
+            sym,
+            mutable,
+            bnd,
+            bdy,
+        } => RExpr::Let {
+            sym,
+            mutable,
+            bnd: bnd.map(boxed),
+            bdy: boxed(bdy),
+        },
+        RExpr::If { cnd, thn, els } => RExpr::If {
+            cnd: boxed(cnd),
+            thn: boxed(thn),
+            els: boxed(els),
+        },
+        RExpr::Apply { fun, args } => RExpr::Apply {
+            fun: boxed(fun),
+            args: args.into_iter().map(decide_expr).collect(),
+        },
+        RExpr::Loop { lbl, bdy } => RExpr::Loop {
+            lbl,
+            bdy: boxed(bdy),
+        },
+        RExpr::Break { lbl, bdy } => RExpr::Break {
+            lbl,
+            bdy: boxed(bdy),
+        },
+        RExpr::Return { bdy } => RExpr::Return { bdy: boxed(bdy) },
+        RExpr::Seq { stmt, cnt } => RExpr::Seq {
+            stmt: boxed(stmt),
+            cnt: boxed(cnt),
+        },
+        RExpr::Assign { sym, bnd } => RExpr::Assign {
+            sym,
+            bnd: boxed(bnd),
+        },
+        RExpr::Struct { sym, fields } => RExpr::Struct {
+            sym,
+            fields: fields
+                .into_iter()
+                .map(|(field, expr)| (field, decide_expr(expr)))
+                .collect(),
+        },
+        RExpr::AccessField { strct, field } => RExpr::AccessField {
+            strct: boxed(strct),
+            field,
+        },
+        RExpr::Switch {
+            scrut,
+            lo,
+            table,
+            arms,
+        } => RExpr::Switch {
+            scrut: boxed(scrut),
+            lo,
+            table,
+            arms: arms.into_iter().map(decide_expr).collect(),
+        },
+        RExpr::Match { scrut, arms } => return decide_match(decide_expr(*scrut), arms, expr.meta),
+    };
+
+    Meta {
+        meta: expr.meta,
+        inner,
+    }
+}
+
+/// Binds the scrutinee to a fresh variable, and then decides which arm to take.
+fn decide_match<'p>(
+    scrut: Typed<'p, RExpr<'p>>,
+    arms: Vec<RArm<'p>>,
+    typ: Type<UniqueSym<'p>>,
+) -> Typed<'p, RExpr<'p>> {
+    // Only a scrutinee that never produces a value can be matched without arms.
+    if arms.is_empty() {
+        return scrut;
+    }
+
+    let scrut_sym = gen_sym("scrut");
+    let root = Path {
+        sym: scrut_sym,
+        typ: scrut.meta.clone(),
+        fields: Vec::new(),
+    };
+
+    Meta {
+        meta: typ.clone(),
+        inner: RExpr::Let {
+            sym: scrut_sym,
+            mutable: false,
+            bnd: Some(Box::new(scrut)),
+            bdy: Box::new(decide_arms(arms, &root, typ)),
+        },
+    }
+}
+
+/// The minimum number of arms before a jump table is used instead of a chain of comparisons.
+const SWITCH_MIN_ARMS: usize = 4;
+/// The maximum number of entries in a jump table.
+const SWITCH_MAX_SIZE: i128 = 1024;
+
+/// Decides which of the `arms` to take for the scrutinee at `root`. Leading arms that match
+/// integers are compiled into a jump table if they cover a dense range of values.
+fn decide_arms<'p>(
+    mut arms: Vec<RArm<'p>>,
+    root: &Path<'p>,
+    typ: Type<UniqueSym<'p>>,
+) -> Typed<'p, RExpr<'p>> {
+    let bounds = arms
+        .iter()
+        .map_while(|arm| arm.guard.is_none().then(|| int_bounds(&arm.pat)).flatten())
+        .collect::<Vec<_>>();
+
+    // The arms after the jump table handle all values that are not in it.
+    if bounds.len() < SWITCH_MIN_ARMS || bounds.len() == arms.len() {
+        return decide_chain(arms, root, typ);
+    }
+
+    let non_empty = bounds.iter().filter(|(lo, hi)| lo <= hi);
+    let (Some(lo), Some(hi)) = (
+        non_empty.clone().map(|(lo, _)| *lo).min(),
+        non_empty.map(|(_, hi)| *hi).max(),
+    ) else {
+        return decide_chain(arms, root, typ);
+    };
+
+    let size = i128::from(hi) - i128::from(lo) + 1;
+    if size > SWITCH_MAX_SIZE {
+        return decide_chain(arms, root, typ);
+    }
+
+    // Fill the table in reverse, so that earlier arms take precedence.
+    let default = bounds.len();
+    let mut table = vec![default; size as usize];
+    for (arm, (arm_lo, arm_hi)) in bounds.iter().enumerate().rev() {
+        for val in *arm_lo..=*arm_hi {
+            table[(i128::from(val) - i128::from(lo)) as usize] = arm;
+        }
+    }
+
+    // Sparse tables waste space, so require at least half of the entries to be covered.
+    if table.iter().filter(|arm| **arm != default).count() * 2 < table.len() {
+        return decide_chain(arms, root, typ);
+    }
+
+    let rest = arms.split_off(bounds.len());
+    let arms = arms
+        .into_iter()
+        .map(|arm| decide_expr(arm.bdy))
+        .chain([decide_arms(rest, root, typ.clone())])
+        .collect();
+
+    Meta {
+        meta: typ,
+        inner: RExpr::Switch {
+            scrut: Box::new(root.to_expr()),
+            lo,
+            table,
+            arms,
+        },
+    }
+}
+
+/// Returns the inclusive bounds of the integers matched by a literal or range pattern.
+fn int_bounds(pat: &Typed<PatternValidated>) -> Option<(i64, i64)> {
+    let int = |val: &TLit| match val {
+        TLit::I64 { val } => Some(*val),
+        TLit::U64 { val } => i64::try_from(*val).ok(),
+        TLit::Bool { .. } | TLit::Unit => None,
+    };
+
+    match &pat.inner {
+        Pattern::Lit { val } => int(val).map(|val| (val, val)),
+        Pattern::Range {
+            lo,
+            hi,
+            inclusive: true,
+        } => Some((int(lo)?, int(hi)?)),
+        Pattern::Range {
+            lo,
+            hi,
+            inclusive: false,
+        } => Some((int(lo)?, int(hi)?.checked_sub(1)?)),
+        _ => None,
+    }
+}
+
+/// Tries the arms one after the other. The last arm is taken without testing its pattern, since
+/// validation made sure that the match is exhaustive.
+fn decide_chain<'p>(
+    arms: Vec<RArm<'p>>,
+    root: &Path<'p>,
+    typ: Type<UniqueSym<'p>>,
+) -> Typed<'p, RExpr<'p>> {
+    let mut arms = arms.into_iter().rev();
+    let last = arms.next().unwrap();
+
+    let mut bdy = decide_expr(last.bdy);
+    if let Some(guard) = last.guard {
+        bdy = Meta {
+            meta: typ.clone(),
+            inner: RExpr::Seq {
+                stmt: Box::new(decide_expr(guard)),
+                cnt: Box::new(bdy),
+            },
+        };
+    }
+    bdy = bind(&last.pat, root, bdy);
+
+    for arm in arms {
+        let thn = decide_expr(arm.bdy);
+        let tests = tests(&arm.pat, root);
+        let guard = arm.guard.map(decide_expr);
+
+        let cnd = match (tests, guard) {
+            // The arm always matches, so the arms after it are never tried.
+            (None, None) => {
+                bdy = bind(&arm.pat, root, thn);
+                continue;
+            }
+            (Some(tests), None) => tests,
+            (None, Some(guard)) => guard,
+            // The guard can only be evaluated once the pattern is known to match.
+            (Some(tests), Some(guard)) => Meta {
+                meta: Type::Bool,
+                inner: RExpr::If {
+                    cnd: Box::new(tests),
+                    thn: Box::new(guard),
+                    els: Box::new(Meta {
+                        meta: Type::Bool,
+                        inner: RExpr::Lit {
+                            val: TLit::Bool { val: false },
+                        },
+                    }),
+                },
+            },
+        };
+
+        // Reading the fields is harmless even if the pattern does not match, so the bindings can
+        // be made before the tests.
+        bdy = bind(
+            &arm.pat,
+            root,
+            Meta {
+                meta: typ.clone(),
+                inner: RExpr::If {
+                    cnd: Box::new(cnd),
+                    thn: Box::new(thn),
+                    els: Box::new(bdy),
+                },
+            },
+        );
+    }
+
+    bdy
+}
+
+/// The location of a sub-pattern in the scrutinee, as a chain of field accesses.
+#[derive(Clone)]
+struct Path<'p> {
+    sym: UniqueSym<'p>,
+    typ: Type<UniqueSym<'p>>,
+    fields: Vec<(&'p str, Type<UniqueSym<'p>>)>,
+}
+
+impl<'p> Path<'p> {
+    fn field(&self, field: &'p str, typ: Type<UniqueSym<'p>>) -> Self {
+        let mut path = self.clone();
+        path.fields.push((field, typ));
+        path
+    }
+
+    fn to_expr(&self) -> Typed<'p, RExpr<'p>> {
+        self.fields.iter().fold(
+            Meta {
+                meta: self.typ.clone(),
+                inner: RExpr::Var { sym: self.sym },
+            },
+            |strct, (field, typ)| Meta {
+                meta: typ.clone(),
+                inner: RExpr::AccessField {
+                    strct: Box::new(strct),
+                    field,
+                },
+            },
+        )
+    }
+}
+
+/// Wraps `bdy` in a `let` for each binding in the pattern.
+fn bind<'p>(
+    pat: &Typed<'p, PatternValidated<'p>>,
+    path: &Path<'p>,
+    bdy: Typed<'p, RExpr<'p>>,
+) -> Typed<'p, RExpr<'p>> {
+    match &pat.inner {
+        Pattern::Binding { sym } => Meta {
+            meta: bdy.meta.clone(),
+            inner: RExpr::Let {
+                sym: *sym,
+                mutable: false,
+                bnd: Some(Box::new(path.to_expr())),
+                bdy: Box::new(bdy),
+            },
+        },
+        Pattern::Struct { fields, .. } => fields.iter().rev().fold(bdy, |bdy, (field, pat)| {
+            bind(pat, &path.field(field, pat.meta.clone()), bdy)
+        }),
+        Pattern::Wildcard | Pattern::Lit { .. } | Pattern::Range { .. } => bdy,
+    }
+}
+
+/// Returns a condition that holds if the value at `path` matches the pattern, or `None` if it
+/// always matches.
+fn tests<'p>(
+    pat: &Typed<'p, PatternValidated<'p>>,
+    path: &Path<'p>,
+) -> Option<Typed<'p, RExpr<'p>>> {
+    match &pat.inner {
+        Pattern::Wildcard | Pattern::Binding { .. } | Pattern::Lit { val: TLit::Unit } => None,
+        Pattern::Lit { val } => Some(compare(BinaryOp::EQ, path.to_expr(), *val)),
+        Pattern::Range { lo, hi, inclusive } => {
+            let op = if *inclusive {
+                BinaryOp::LE
+            } else {
+                BinaryOp::LT
+            };
+            Some(and(
+                compare(BinaryOp::GE, path.to_expr(), *lo),
+                compare(op, path.to_expr(), *hi),
+            ))
+        }
+        Pattern::Struct { fields, .. } => fields
+            .iter()
+            .filter_map(|(field, pat)| tests(pat, &path.field(field, pat.meta.clone())))
+            .reduce(and),
+    }
+}
+
+fn compare<'p>(op: BinaryOp, lhs: Typed<'p, RExpr<'p>>, val: TLit) -> Typed<'p, RExpr<'p>> {
+    let rhs = Meta {
+        meta: lhs.meta.clone(),
+        inner: RExpr::Lit { val },
+    };
+
+    Meta {
+        meta: Type::Bool,
+        inner: RExpr::BinaryOp {
+            op,
+            exprs: [Box::new(lhs), Box::new(rhs)],
+        },
+    }
+}
+
+fn and<'p>(lhs: Typed<'p, RExpr<'p>>, rhs: Typed<'p, RExpr<'p>>) -> Typed<'p, RExpr<'p>> {
+    Meta {
+        meta: Type::Bool,
+        inner: RExpr::BinaryOp {
+            op: BinaryOp::LAnd,
+            exprs: [Box::new(lhs), Box::new(rhs)],
+        },
+    }
+}
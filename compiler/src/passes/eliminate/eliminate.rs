@@ -1,24 +1,36 @@
 use crate::passes::eliminate::eliminate_params::eliminate_params;
 use crate::passes::eliminate::eliminate_tail::eliminate_tail;
 use crate::passes::eliminate::PrgEliminated;
-use crate::passes::explicate::PrgExplicated;
+use crate::passes::explicate::{CTail, PrgExplicated};
+use crate::passes::parse::types::Type;
 use crate::utils::gen_sym::UniqueSym;
 use std::collections::HashMap;
 
 // (Old variable name, field name) -> New variable name
 pub type Ctx<'p> = HashMap<(UniqueSym<'p>, &'p str), UniqueSym<'p>>;
 
+// Variable name -> Struct type, for every variable that holds a struct
+pub type Structs<'p> = HashMap<UniqueSym<'p>, Type<UniqueSym<'p>>>;
+
 impl<'p> PrgExplicated<'p> {
     pub fn eliminate(self) -> PrgEliminated<'p> {
         let mut ctx = Ctx::new();
 
+        let mut structs = Structs::new();
+        for param in self.fn_params.values().flatten() {
+            collect_struct(param.sym, &param.typ, &mut structs);
+        }
+        for tail in self.blocks.values() {
+            collect_structs(tail, &mut structs);
+        }
+
         let fn_params = eliminate_params(self.fn_params, &mut ctx, &self.defs);
 
         PrgEliminated {
             blocks: self
                 .blocks
                 .into_iter()
-                .map(|(sym, tail)| (sym, eliminate_tail(tail, &mut ctx, &self.defs)))
+                .map(|(sym, tail)| (sym, eliminate_tail(tail, &mut ctx, &structs, &self.defs)))
                 .collect(),
             fn_params,
             defs: self.defs,
@@ -27,3 +39,16 @@ impl<'p> PrgExplicated<'p> {
         }
     }
 }
+
+fn collect_structs<'p>(tail: &CTail<'p>, structs: &mut Structs<'p>) {
+    if let CTail::Seq { sym, bnd, tail } = tail {
+        collect_struct(*sym, &bnd.meta, structs);
+        collect_structs(tail, structs);
+    }
+}
+
+fn collect_struct<'p>(sym: UniqueSym<'p>, typ: &Type<UniqueSym<'p>>, structs: &mut Structs<'p>) {
+    if let Type::Var { .. } = typ {
+        structs.insert(sym, typ.clone());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::passes::atomize::Atom;
+use crate::passes::eliminate::eliminate::Ctx;
+use crate::passes::eliminate::eliminate_params::flatten_type;
+use crate::passes::eliminate::{EExpr, ETail};
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{BinaryOp, Meta, TypeDef};
+use crate::passes::validate::TLit;
+use crate::utils::gen_sym::UniqueSym;
+use std::collections::HashMap;
+
+/// Lowers `sym = lhs op rhs`, where `lhs` and `rhs` are structs of type `typ` and `op` is `==` or
+/// `!=`, into a comparison of each pair of flattened fields. For `==` the comparisons are combined
+/// with `&&`, and for `!=` they are combined with `||`.
+pub fn eliminate_eq<'p>(
+    sym: UniqueSym<'p>,
+    op: BinaryOp,
+    [lhs, rhs]: [UniqueSym<'p>; 2],
+    typ: &Type<UniqueSym<'p>>,
+    tail: ETail<'p>,
+    ctx: &mut Ctx<'p>,
+    defs: &HashMap<UniqueSym<'p>, TypeDef<UniqueSym<'p>, &'p str>>,
+) -> ETail<'p> {
+    let combine = match op {
+        BinaryOp::EQ => BinaryOp::LAnd,
+        BinaryOp::NE => BinaryOp::LOr,
+        _ => unreachable!("Only `==` and `!=` are defined on structs."),
+    };
+
+    let lhs = flatten_type(lhs, typ, ctx, defs);
+    let rhs = flatten_type(rhs, typ, ctx, defs);
+
+    let mut bnds = Vec::new();
+    let mut result = None;
+    for ((lhs, _), (rhs, _)) in lhs.into_iter().zip(rhs) {
+        let cmp = sym.fresh();
+        bnds.push((
+            cmp,
+            EExpr::BinaryOp {
+                op,
+                exprs: [Atom::Var { sym: lhs }, Atom::Var { sym: rhs }],
+            },
+        ));
+
+        result = Some(match result {
+            None => cmp,
+            Some(acc) => {
+                let new_acc = sym.fresh();
+                bnds.push((
+                    new_acc,
+                    EExpr::BinaryOp {
+                        op: combine,
+                        exprs: [Atom::Var { sym: acc }, Atom::Var { sym: cmp }],
+                    },
+                ));
+                new_acc
+            }
+        });
+    }
+
+    // Structs without fields are always equal.
+    let atm = match result {
+        Some(sym) => Atom::Var { sym },
+        None => Atom::Val {
+            val: TLit::Bool {
+                val: op == BinaryOp::EQ,
+            },
+        },
+    };
+    bnds.push((sym, EExpr::Atom { atm }));
+
+    bnds.into_iter()
+        .rev()
+        .fold(tail, |tail, (sym, bnd)| ETail::Seq {
+            syms: vec![sym],
+            bnd: Meta {
+                meta: vec![Type::Bool],
+                inner: bnd,
+            },
+            tail: Box::new(tail),
+        })
+}
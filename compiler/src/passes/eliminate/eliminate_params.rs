@@ -23,6 +23,7 @@ pub fn eliminate_params<'p>(
                                 sym,
                                 typ,
                                 mutable: param.mutable,
+                                by_ref: param.by_ref,
                             })
                     })
                     .collect(),
@@ -43,8 +44,8 @@ pub fn flatten_type<'p>(
         Type::I64 | Type::U64 | Type::Bool | Type::Unit | Type::Never | Type::Fn { .. } => {
             vec![(sym, typ.clone())]
         }
-        Type::Var { sym: def_sym } => match &defs[&def_sym] {
-            TypeDef::Struct { fields } => fields
+        Type::Var { sym: def_sym } => match &defs[def_sym] {
+            TypeDef::Struct { fields, .. } => fields
                 .iter()
                 .flat_map(|(field_name, field_type)| {
                     let new_sym = *ctx.entry((sym, field_name)).or_insert_with(|| sym.fresh());
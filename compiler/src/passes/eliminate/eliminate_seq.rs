@@ -1,11 +1,12 @@
 use crate::passes::atomize::Atom;
-use crate::passes::eliminate::eliminate::Ctx;
+use crate::passes::eliminate::eliminate::{Ctx, Structs};
+use crate::passes::eliminate::eliminate_eq::eliminate_eq;
 use crate::passes::eliminate::eliminate_expr::eliminate_expr;
 use crate::passes::eliminate::eliminate_params::flatten_type;
 use crate::passes::eliminate::{EExpr, ETail};
 use crate::passes::explicate::CExpr;
 use crate::passes::parse::types::Type;
-use crate::passes::parse::{Meta, TypeDef, Typed};
+use crate::passes::parse::{BinaryOp, Meta, TypeDef, Typed};
 use crate::utils::gen_sym::UniqueSym;
 use std::collections::HashMap;
 
@@ -14,6 +15,7 @@ pub fn eliminate_seq<'p>(
     ctx: &mut Ctx<'p>,
     bnd: Typed<'p, CExpr<'p>>,
     tail: ETail<'p>,
+    structs: &Structs<'p>,
     defs: &HashMap<UniqueSym<'p>, TypeDef<UniqueSym<'p>, &'p str>>,
 ) -> ETail<'p> {
     let typ = bnd.meta;
@@ -34,6 +36,7 @@ pub fn eliminate_seq<'p>(
                     },
                 },
                 tail,
+                structs,
                 defs,
             );
         }
@@ -52,6 +55,12 @@ pub fn eliminate_seq<'p>(
 
             CExpr::Apply { fun, args }
         }
+        CExpr::BinaryOp {
+            op: op @ (BinaryOp::EQ | BinaryOp::NE),
+            exprs: [Atom::Var { sym: lhs }, Atom::Var { sym: rhs }],
+        } if structs.contains_key(&lhs) => {
+            return eliminate_eq(sym, op, [lhs, rhs], &structs[&lhs], tail, ctx, defs);
+        }
         inner => inner,
     };
 
@@ -70,7 +79,9 @@ pub fn eliminate_seq<'p>(
         }
         Type::Var { sym: def_sym } => match &defs[&def_sym] {
             // Changes needed, since LHS is a struct
-            TypeDef::Struct { fields: def_fields } => match bnd {
+            TypeDef::Struct {
+                fields: def_fields, ..
+            } => match bnd {
                 CExpr::Atom { atm, .. } => {
                     def_fields.iter().fold(tail, |tail, (field, field_type)| {
                         let sym_lhs = *ctx.entry((sym, field)).or_insert_with(|| sym.fresh());
@@ -88,6 +99,7 @@ pub fn eliminate_seq<'p>(
                                 },
                             },
                             tail,
+                            structs,
                             defs,
                         )
                     })
@@ -107,6 +119,7 @@ pub fn eliminate_seq<'p>(
                                 inner: CExpr::Atom { atm: atom_rhs },
                             },
                             tail,
+                            structs,
                             defs,
                         )
                     })
@@ -1,18 +1,22 @@
 use crate::passes::atomize::Atom;
-use crate::passes::eliminate::eliminate::Ctx;
+use crate::passes::eliminate::eliminate::{Ctx, Structs};
+use crate::passes::eliminate::eliminate_eq::eliminate_eq;
 use crate::passes::eliminate::eliminate_expr::eliminate_expr;
 use crate::passes::eliminate::eliminate_params::flatten_type;
 use crate::passes::eliminate::eliminate_seq::eliminate_seq;
-use crate::passes::eliminate::ETail;
+use crate::passes::eliminate::{EExpr, ETail};
+use crate::passes::explicate::CExpr;
 use crate::passes::explicate::CTail;
-use crate::passes::parse::TypeDef;
-use crate::utils::gen_sym::UniqueSym;
+use crate::passes::parse::{BinaryOp, TypeDef};
+use crate::passes::validate::TLit;
+use crate::utils::gen_sym::{gen_sym, UniqueSym};
 use functor_derive::Functor;
 use std::collections::HashMap;
 
 pub fn eliminate_tail<'p>(
     tail: CTail<'p>,
     ctx: &mut Ctx<'p>,
+    structs: &Structs<'p>,
     defs: &HashMap<UniqueSym<'p>, TypeDef<UniqueSym<'p>, &'p str>>,
 ) -> ETail<'p> {
     match tail {
@@ -21,13 +25,38 @@ pub fn eliminate_tail<'p>(
                 exprs: vec![(Atom::Val { val })],
             },
             Atom::Var { sym } => ETail::Return {
-                exprs: flatten_type(sym, &expr.meta, ctx, defs)
-                    .fmap(|(sym, _)| (Atom::Var { sym })),
+                exprs: flatten_type(sym, &expr.meta, ctx, defs).fmap(|(sym, _)| Atom::Var { sym }),
             },
         },
         CTail::Seq { sym, bnd, tail } => {
-            let tail = eliminate_tail(*tail, ctx, defs);
-            eliminate_seq(sym, ctx, bnd, tail, defs)
+            let tail = eliminate_tail(*tail, ctx, structs, defs);
+            eliminate_seq(sym, ctx, bnd, tail, structs, defs)
+        }
+        CTail::IfStmt {
+            cnd:
+                CExpr::BinaryOp {
+                    op: op @ (BinaryOp::EQ | BinaryOp::NE),
+                    exprs: [Atom::Var { sym: lhs }, Atom::Var { sym: rhs }],
+                },
+            thn,
+            els,
+        } if structs.contains_key(&lhs) => {
+            // Compare the structs first, and then branch on the result.
+            let tmp = gen_sym("tmp");
+            let tail = ETail::IfStmt {
+                cnd: EExpr::BinaryOp {
+                    op: BinaryOp::EQ,
+                    exprs: [
+                        Atom::Var { sym: tmp },
+                        Atom::Val {
+                            val: TLit::Bool { val: true },
+                        },
+                    ],
+                },
+                thn,
+                els,
+            };
+            eliminate_eq(tmp, op, [lhs, rhs], &structs[&lhs], tail, ctx, defs)
         }
         CTail::IfStmt { cnd, thn, els } => ETail::IfStmt {
             cnd: eliminate_expr(cnd),
@@ -35,5 +64,16 @@ pub fn eliminate_tail<'p>(
             els,
         },
         CTail::Goto { lbl } => ETail::Goto { lbl },
+        CTail::Switch {
+            scrut,
+            lo,
+            table,
+            default,
+        } => ETail::Switch {
+            scrut,
+            lo,
+            table,
+            default,
+        },
     }
 }
@@ -1,4 +1,5 @@
 pub mod eliminate;
+mod eliminate_eq;
 mod eliminate_expr;
 mod eliminate_params;
 mod eliminate_seq;
@@ -8,7 +9,7 @@ use crate::passes::atomize::Atom;
 use crate::passes::parse::types::Type;
 use crate::passes::parse::{BinaryOp, Meta, Param, TypeDef, UnaryOp};
 use crate::passes::select::std_lib::Std;
-use crate::passes::select::InstrSelected;
+use crate::passes::select::AsmItemSelected;
 use crate::utils::gen_sym::UniqueSym;
 use std::collections::HashMap;
 
@@ -37,6 +38,12 @@ pub enum ETail<'p> {
     Goto {
         lbl: UniqueSym<'p>,
     },
+    Switch {
+        scrut: Atom<'p>,
+        lo: i64,
+        table: Vec<UniqueSym<'p>>,
+        default: UniqueSym<'p>,
+    },
 }
 
 pub enum EExpr<'p> {
@@ -45,5 +52,5 @@ pub enum EExpr<'p> {
     UnaryOp { op: UnaryOp, expr: Atom<'p> },
     Apply { fun: Atom<'p>, args: Vec<Atom<'p>> },
     FunRef { sym: UniqueSym<'p> },
-    Asm { instrs: Vec<InstrSelected<'p>> },
+    Asm { instrs: Vec<AsmItemSelected<'p>> },
 }
@@ -62,6 +62,20 @@ pub const MOVQ_INFO: BinaryOpInfo = BinaryOpInfo {
     pad: 0x00,
 };
 
+pub const TESTQ_INFO: BinaryOpInfo = BinaryOpInfo {
+    r_rm: 0x85,
+    rm_r: 0x85, // `test` is symmetric, so the operands can be swapped.
+    i_rm: 0xF7,
+    pad: 0x00,
+};
+
+pub const LEAQ_INFO: BinaryOpInfo = BinaryOpInfo {
+    r_rm: 0, //Unreachable
+    rm_r: 0x8D,
+    i_rm: 0, //Unreachable
+    pad: 0x00,
+};
+
 pub fn encode_binary_instr(op_info: BinaryOpInfo, src: &Arg, dst: &Arg) -> Vec<u8> {
     match (src, dst) {
         (Arg::Reg { reg: src }, Arg::Reg { reg: dst }) => {
@@ -99,7 +113,7 @@ pub fn encode_binary_instr(op_info: BinaryOpInfo, src: &Arg, dst: &Arg) -> Vec<u
                 op_info.r_rm,
                 0b10_000_000 | sss << 3 | ddd,
             ];
-            if matches!(src, Reg::RSP | Reg::R12) {
+            if matches!(dst, Reg::RSP | Reg::R12) {
                 v.push(0x24);
             }
             v.extend(off.to_le_bytes());
@@ -143,12 +157,22 @@ pub fn encode_binary_instr(op_info: BinaryOpInfo, src: &Arg, dst: &Arg) -> Vec<u
     }
 }
 
+/// Encodes `movq` of an immediate that does not fit in 32 bits into a register, also known as `movabs`.
+pub fn encode_movabs(imm: i64, dst: &Reg) -> Vec<u8> {
+    let (d, ddd) = encode_reg(dst);
+
+    let mut v = vec![0b0100_1000 | d, 0xB8 | ddd];
+    v.extend(imm.to_le_bytes());
+    v
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
 
     mod add {
         use super::*;
+        use crate::deref;
 
         check!(reg_reg, addq!(reg!(RSP), reg!(RDX)), vec![0x48, 0x01, 0xE2]);
         check!(
@@ -185,6 +209,7 @@ mod tests {
 
     mod sub {
         use super::*;
+        use crate::deref;
 
         check!(reg_reg, subq!(reg!(RSP), reg!(RDX)), vec![0x48, 0x29, 0xE2]);
         check!(
@@ -221,6 +246,7 @@ mod tests {
 
     mod r#move {
         use super::*;
+        use crate::deref;
 
         check!(reg_reg, movq!(reg!(RSP), reg!(RDX)), vec![0x48, 0x89, 0xE2]);
         check!(
@@ -253,5 +279,62 @@ mod tests {
             movq!(imm!((i32::MAX - 0xFF) as i64), deref!(RDX, i32::MAX as i64)),
             vec![0x48, 0xC7, 0x82, 0xFF, 0xFF, 0xFF, 0x7F, 0x00, 0xFF, 0xFF, 0x7F]
         );
+        check!(
+            reg_deref2,
+            movq!(reg!(RSP), deref!(RCX, 24)),
+            vec![0x48, 0x89, 0xA1, 0x18, 0x00, 0x00, 0x00]
+        );
+        check!(
+            reg_deref3,
+            movq!(reg!(RAX), deref!(RSP, 8)),
+            vec![0x48, 0x89, 0x84, 0x24, 0x08, 0x00, 0x00, 0x00]
+        );
+        check!(
+            imm64_reg1,
+            movq!(imm!(0x1122_3344_5566_7788_i64), reg!(RCX)),
+            vec![0x48, 0xB9, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11]
+        );
+        check!(
+            imm64_reg2,
+            movq!(imm!(i64::MIN), reg!(R9)),
+            vec![0x49, 0xB9, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]
+        );
+    }
+
+    mod test {
+        use super::*;
+        use crate::deref;
+
+        check!(
+            reg_reg,
+            testq!(reg!(RAX), reg!(RCX)),
+            vec![0x48, 0x85, 0xC1]
+        );
+        check!(
+            imm_reg,
+            testq!(imm!(1), reg!(RBX)),
+            vec![0x48, 0xF7, 0xC3, 0x01, 0x00, 0x00, 0x00]
+        );
+        check!(
+            deref_reg,
+            testq!(deref!(RBX, 8), reg!(RDI)),
+            vec![0x48, 0x85, 0xBB, 0x08, 0x00, 0x00, 0x00]
+        );
+    }
+
+    mod lea {
+        use super::*;
+        use crate::deref;
+
+        check!(
+            deref_reg1,
+            leaq!(deref!(RBX, 8), reg!(RDI)),
+            vec![0x48, 0x8D, 0xBB, 0x08, 0x00, 0x00, 0x00]
+        );
+        check!(
+            deref_reg2,
+            leaq!(deref!(RSP, 16), reg!(R15)),
+            vec![0x4C, 0x8D, 0xBC, 0x24, 0x10, 0x00, 0x00, 0x00]
+        );
     }
 }
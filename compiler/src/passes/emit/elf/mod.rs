@@ -7,39 +7,64 @@ mod section;
 
 use crate::passes::emit::elf::header::ElfHeader;
 use crate::passes::emit::elf::program::ProgramHeader;
+use crate::passes::select::std_lib::{RUNTIME_OFFSET, RUNTIME_SIZE};
 use std::io::Write;
 use std::mem::size_of;
 use zerocopy::AsBytes;
 
 pub const PRG_OFFSET: usize = 0x0040_0000;
+pub const PAGE_SIZE: usize = 0x1000;
 
 pub struct ElfFile {
     header: ElfHeader,
     p_headers: Vec<ProgramHeader>,
     program: Vec<u8>,
+    rodata: Vec<u8>,
 }
 
 impl ElfFile {
-    pub fn new(entry: usize, program: Vec<u8>) -> Self {
-        let p_headers = vec![ProgramHeader::new(0x1000, program.len() as u64)];
+    /// Creates an executable from the `program` and the read-only data that follows it on the next
+    /// page.
+    pub fn new(entry: usize, program: Vec<u8>, rodata: Vec<u8>) -> Self {
+        let mut p_headers = vec![
+            ProgramHeader::new(0x1000, program.len() as u64),
+            ProgramHeader::bss(RUNTIME_OFFSET as u64, RUNTIME_SIZE as u64),
+        ];
+        if !rodata.is_empty() {
+            let offset = program.len().next_multiple_of(PAGE_SIZE);
+            p_headers.push(ProgramHeader::read_only(
+                (0x1000 + offset) as u64,
+                (PRG_OFFSET + offset) as u64,
+                rodata.len() as u64,
+            ));
+        }
 
         Self {
             header: ElfHeader::new((PRG_OFFSET + entry) as u64, p_headers.len() as u16),
             p_headers,
             program,
+            rodata,
         }
     }
 
     pub fn write(self, w: &mut impl Write) {
         w.write_all(self.header.as_bytes()).unwrap();
-        for pheader in self.p_headers {
+        for pheader in &self.p_headers {
             w.write_all(pheader.as_bytes()).unwrap();
         }
 
-        for _ in 0..0x1000 - size_of::<ElfHeader>() - size_of::<ProgramHeader>() {
+        for _ in
+            0..0x1000 - size_of::<ElfHeader>() - size_of::<ProgramHeader>() * self.p_headers.len()
+        {
             w.write_all(&[0]).unwrap();
         }
 
         w.write_all(&self.program).unwrap();
+
+        if !self.rodata.is_empty() {
+            let padding = self.program.len().next_multiple_of(PAGE_SIZE) - self.program.len();
+            w.write_all(&vec![0; padding]).unwrap();
+            w.write_all(&self.rodata).unwrap();
+        }
     }
 }
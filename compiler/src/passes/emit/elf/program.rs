@@ -79,4 +79,32 @@ impl ProgramHeader {
             p_align: 0,
         }
     }
+
+    /// Creates a segment that is only readable, for data that is loaded from the file.
+    pub fn read_only(offset: u64, vaddr: u64, len: u64) -> Self {
+        Self {
+            p_type: ProgramType::PT_LOAD,
+            p_flags: ProgramFlags::Readable,
+            p_offset: offset,
+            p_vaddr: vaddr,
+            p_paddr: vaddr,
+            p_filesz: len,
+            p_memsz: len,
+            p_align: 0,
+        }
+    }
+
+    /// Creates a zero-initialized, writable segment that does not occupy space in the file.
+    pub fn bss(vaddr: u64, len: u64) -> Self {
+        Self {
+            p_type: ProgramType::PT_LOAD,
+            p_flags: ProgramFlags::Writable | ProgramFlags::Readable,
+            p_offset: 0,
+            p_vaddr: vaddr,
+            p_paddr: vaddr,
+            p_filesz: 0,
+            p_memsz: len,
+            p_align: 0,
+        }
+    }
 }
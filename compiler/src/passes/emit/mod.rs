@@ -2,6 +2,7 @@ mod binary;
 pub mod elf;
 mod mul_div;
 mod push_pop;
+mod shift;
 mod special;
 mod unary;
 
@@ -9,13 +10,17 @@ use crate::imm;
 use crate::passes::assign::Arg;
 use crate::passes::conclude::X86Concluded;
 use crate::passes::emit::binary::{
-    encode_binary_instr, ADDQ_INFO, ANDQ_INFO, CMPQ_INFO, MOVQ_INFO, ORQ_INFO, SUBQ_INFO, XORQ_INFO,
+    encode_binary_instr, encode_movabs, ADDQ_INFO, ANDQ_INFO, CMPQ_INFO, LEAQ_INFO, MOVQ_INFO,
+    ORQ_INFO, SUBQ_INFO, TESTQ_INFO, XORQ_INFO,
 };
-use crate::passes::emit::elf::{ElfFile, PRG_OFFSET};
-use crate::passes::emit::mul_div::{encode_muldiv_instr, MulDivOpInfo};
+use crate::passes::emit::elf::{ElfFile, PAGE_SIZE, PRG_OFFSET};
+use crate::passes::emit::mul_div::{encode_imul_instr, encode_muldiv_instr, MulDivOpInfo};
 use crate::passes::emit::push_pop::{encode_push_pop, POPQ_INFO, PUSHQ_INFO};
-use crate::passes::emit::special::encode_setcc;
-use crate::passes::emit::unary::{encode_unary_instr, CALLQ_INDIRECT_INFO, NEGQ_INFO};
+use crate::passes::emit::shift::{encode_shift_instr, SARQ_INFO, SHLQ_INFO};
+use crate::passes::emit::special::{encode_jmp_table, encode_setcc};
+use crate::passes::emit::unary::{
+    encode_unary_instr, CALLQ_INDIRECT_INFO, DECQ_INFO, INCQ_INFO, NEGQ_INFO, NOTQ_INFO,
+};
 use crate::passes::select::{Block, Cnd, Instr, Reg};
 use crate::utils::gen_sym::UniqueSym;
 use std::collections::HashMap;
@@ -27,11 +32,18 @@ impl<'p> X86Concluded<'p> {
 
         let mut rel_jumps = HashMap::new();
         let mut abs_jumps = HashMap::new();
+        let mut jump_tables = Vec::new();
         let mut addresses = HashMap::new();
 
-        for (name, block) in &self.blocks {
+        for name in &self.order {
             addresses.insert(name, machine_code.len());
-            emit_block(block, &mut machine_code, &mut rel_jumps, &mut abs_jumps);
+            emit_block(
+                &self.blocks[name],
+                &mut machine_code,
+                &mut rel_jumps,
+                &mut abs_jumps,
+                &mut jump_tables,
+            );
         }
 
         for (addr, block) in rel_jumps {
@@ -48,7 +60,20 @@ impl<'p> X86Concluded<'p> {
             machine_code[addr..addr + 4].copy_from_slice(&target.to_le_bytes());
         }
 
-        ElfFile::new(addresses[&self.entry], machine_code)
+        // The jump tables are stored in a read-only segment on the first page after the program.
+        let mut rodata = Vec::new();
+        let rodata_offset = PRG_OFFSET + machine_code.len().next_multiple_of(PAGE_SIZE);
+        for (addr, lbls) in jump_tables {
+            let table = (rodata_offset + rodata.len()) as i32;
+            machine_code[addr..addr + 4].copy_from_slice(&table.to_le_bytes());
+
+            for lbl in lbls {
+                let target = (PRG_OFFSET + addresses[&lbl]) as u64;
+                rodata.extend(target.to_le_bytes());
+            }
+        }
+
+        ElfFile::new(addresses[&self.entry], machine_code, rodata)
     }
 }
 
@@ -57,9 +82,10 @@ fn emit_block<'p>(
     machine_code: &mut Vec<u8>,
     rel_jumps: &mut HashMap<usize, UniqueSym<'p>>,
     abs_jumps: &mut HashMap<usize, UniqueSym<'p>>,
+    jump_tables: &mut Vec<(usize, Vec<UniqueSym<'p>>)>,
 ) {
     for instr in &block.instrs {
-        emit_instr(instr, machine_code, rel_jumps, abs_jumps);
+        emit_instr(instr, machine_code, rel_jumps, abs_jumps, jump_tables);
     }
 }
 
@@ -68,19 +94,34 @@ fn emit_instr<'p>(
     machine_code: &mut Vec<u8>,
     rel_jumps: &mut HashMap<usize, UniqueSym<'p>>,
     abs_jumps: &mut HashMap<usize, UniqueSym<'p>>,
+    jump_tables: &mut Vec<(usize, Vec<UniqueSym<'p>>)>,
 ) {
     let v = match instr {
         Instr::Addq { src, dst } => encode_binary_instr(ADDQ_INFO, src, dst),
         Instr::Subq { src, dst } => encode_binary_instr(SUBQ_INFO, src, dst),
+        Instr::Movq {
+            src: Arg::Imm { val },
+            dst: Arg::Reg { reg },
+        } if i32::try_from(*val).is_err() => encode_movabs(*val, reg),
         Instr::Movq { src, dst } => encode_binary_instr(MOVQ_INFO, src, dst),
         Instr::Negq { dst } => encode_unary_instr(NEGQ_INFO, dst),
+        Instr::Incq { dst } => encode_unary_instr(INCQ_INFO, dst),
+        Instr::Decq { dst } => encode_unary_instr(DECQ_INFO, dst),
         Instr::Pushq { src } => encode_push_pop(PUSHQ_INFO, src),
         Instr::Popq { dst } => encode_push_pop(POPQ_INFO, dst),
         Instr::Cmpq { src, dst } => encode_binary_instr(CMPQ_INFO, src, dst),
         Instr::Andq { src, dst } => encode_binary_instr(ANDQ_INFO, src, dst),
         Instr::Orq { src, dst } => encode_binary_instr(ORQ_INFO, src, dst),
         Instr::Xorq { src, dst } => encode_binary_instr(XORQ_INFO, src, dst),
-        Instr::Notq { .. } => todo!(),
+        Instr::Notq { dst } => encode_unary_instr(NOTQ_INFO, dst),
+        Instr::Shlq { src, dst } => encode_shift_instr(SHLQ_INFO, src, dst),
+        Instr::Sarq { src, dst } => encode_shift_instr(SARQ_INFO, src, dst),
+        Instr::Imulq { src, dst } => encode_imul_instr(src, dst),
+        Instr::Leaq { src, dst } => {
+            assert!(matches!((src, dst), (Arg::Deref { .. }, Arg::Reg { .. })));
+            encode_binary_instr(LEAQ_INFO, src, dst)
+        }
+        Instr::Testq { src, dst } => encode_binary_instr(TESTQ_INFO, src, dst),
         Instr::Setcc { cnd } => encode_setcc(cnd),
         Instr::CallqDirect { lbl, .. } => {
             rel_jumps.insert(machine_code.len() + 1, *lbl);
@@ -95,6 +136,11 @@ fn emit_instr<'p>(
             rel_jumps.insert(machine_code.len() + 2, *lbl);
             vec![0x0F, encode_cnd(*cnd), 0x00, 0x00, 0x00, 0x00]
         }
+        Instr::JmpTable { idx, lbls } => {
+            let v = encode_jmp_table(idx);
+            jump_tables.push((machine_code.len() + v.len() - 4, lbls.clone()));
+            v
+        }
         Instr::Retq => vec![0xC3],
         Instr::Syscall { .. } => vec![0x0F, 0x05],
         Instr::Divq { divisor } => encode_muldiv_instr(
@@ -177,6 +223,7 @@ macro_rules! check {
                 &mut output,
                 &mut HashMap::new(),
                 &mut HashMap::new(),
+                &mut Vec::new(),
             );
 
             assert_eq!(output, $expected);
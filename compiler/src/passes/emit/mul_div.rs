@@ -1,5 +1,6 @@
 use crate::passes::assign::Arg;
 use crate::passes::emit::encode_reg;
+use crate::passes::select::Reg;
 
 pub struct MulDivOpInfo {
     pub op: u8,
@@ -21,6 +22,51 @@ pub fn encode_muldiv_instr(op_info: MulDivOpInfo, reg: &Arg) -> Vec<u8> {
     }
 }
 
+pub fn encode_imul_instr(src: &Arg, dst: &Arg) -> Vec<u8> {
+    let Arg::Reg { reg: dst } = dst else {
+        unreachable!("Found imul with a non-register destination.");
+    };
+    let (d, ddd) = encode_reg(dst);
+
+    match src {
+        Arg::Imm { val } => {
+            // use: REX.W + 69 /r id
+            let mut v = vec![
+                0b0100_1000 | (d << 2) | d,
+                0x69,
+                0b11_000_000 | ddd << 3 | ddd,
+            ];
+            v.extend((*val as i32).to_le_bytes());
+            v
+        }
+        Arg::Reg { reg: src } => {
+            // use: REX.W + 0F AF /r
+            let (s, sss) = encode_reg(src);
+            vec![
+                0b0100_1000 | (d << 2) | s,
+                0x0F,
+                0xAF,
+                0b11_000_000 | ddd << 3 | sss,
+            ]
+        }
+        Arg::Deref { reg: src, off } => {
+            // use: REX.W + 0F AF /r
+            let (s, sss) = encode_reg(src);
+            let mut v = vec![
+                0b0100_1000 | (d << 2) | s,
+                0x0F,
+                0xAF,
+                0b10_000_000 | ddd << 3 | sss,
+            ];
+            if matches!(src, Reg::RSP | Reg::R12) {
+                v.push(0x24);
+            }
+            v.extend((*off as i32).to_le_bytes());
+            v
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     mod division {
@@ -36,4 +82,30 @@ mod tests {
         check!(mul1, mulq!(reg!(R15)), vec![0x49, 0xF7, 0xE7]);
         check!(mul2, mulq!(reg!(RDX)), vec![0x48, 0xF7, 0xE2]);
     }
+
+    mod signed_multiply {
+        use crate::deref;
+        use crate::*;
+
+        check!(
+            imul1,
+            imulq!(reg!(RBX), reg!(RAX)),
+            vec![0x48, 0x0F, 0xAF, 0xC3]
+        );
+        check!(
+            imul2,
+            imulq!(reg!(R12), reg!(RDX)),
+            vec![0x49, 0x0F, 0xAF, 0xD4]
+        );
+        check!(
+            imul3,
+            imulq!(imm!(10), reg!(RCX)),
+            vec![0x48, 0x69, 0xC9, 0x0A, 0x00, 0x00, 0x00]
+        );
+        check!(
+            imul4,
+            imulq!(deref!(RBP, -16), reg!(RAX)),
+            vec![0x48, 0x0F, 0xAF, 0x85, 0xF0, 0xFF, 0xFF, 0xFF]
+        );
+    }
 }
@@ -67,6 +67,7 @@ mod tests {
 
     mod push {
         use super::*;
+        use crate::deref;
 
         check!(reg1, pushq!(reg!(RAX)), vec![0x50]);
         check!(reg2, pushq!(reg!(R14)), vec![0x41, 0x56]);
@@ -91,6 +92,7 @@ mod tests {
 
     mod pop {
         use super::*;
+        use crate::deref;
 
         check!(reg1, popq!(reg!(RAX)), vec![0x58]);
         check!(reg2, popq!(reg!(R14)), vec![0x41, 0x5E]);
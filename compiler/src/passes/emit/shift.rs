@@ -0,0 +1,70 @@
+use crate::passes::assign::Arg;
+use crate::passes::emit::unary::{encode_unary_instr, UnaryOpInfo};
+use crate::passes::select::Reg;
+
+pub struct ShiftOpInfo {
+    pub pad: u8,
+}
+
+pub const SHLQ_INFO: ShiftOpInfo = ShiftOpInfo { pad: 0x4 };
+
+pub const SARQ_INFO: ShiftOpInfo = ShiftOpInfo { pad: 0x7 };
+
+pub fn encode_shift_instr(op_info: ShiftOpInfo, src: &Arg, dst: &Arg) -> Vec<u8> {
+    match src {
+        Arg::Imm { val } => {
+            // use: REX.W + C1 /r ib
+            let mut v = encode_unary_instr(
+                UnaryOpInfo {
+                    op: 0xC1,
+                    pad: op_info.pad,
+                },
+                dst,
+            );
+            v.push(*val as u8);
+            v
+        }
+        Arg::Reg { reg: Reg::RCX } => {
+            // use: REX.W + D3 /r
+            encode_unary_instr(
+                UnaryOpInfo {
+                    op: 0xD3,
+                    pad: op_info.pad,
+                },
+                dst,
+            )
+        }
+        _ => unreachable!("The shift count should be an immediate or `%RCX`."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod shift_left {
+        use crate::deref;
+        use crate::*;
+
+        check!(
+            imm_reg,
+            shlq!(imm!(3), reg!(RAX)),
+            vec![0x48, 0xC1, 0xE0, 0x03]
+        );
+        check!(
+            imm_deref,
+            shlq!(imm!(1), deref!(RBP, -8)),
+            vec![0x48, 0xC1, 0xA5, 0xF8, 0xFF, 0xFF, 0xFF, 0x01]
+        );
+        check!(rcx_reg, shlq!(reg!(RCX), reg!(RDX)), vec![0x48, 0xD3, 0xE2]);
+    }
+
+    mod shift_right {
+        use crate::*;
+
+        check!(
+            imm_reg,
+            sarq!(imm!(63), reg!(RBX)),
+            vec![0x48, 0xC1, 0xFB, 0x3F]
+        );
+        check!(rcx_reg, sarq!(reg!(RCX), reg!(R9)), vec![0x49, 0xD3, 0xF9]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::passes::assign::Arg;
+use crate::passes::emit::encode_reg;
 use crate::passes::select::Cnd;
 
 pub fn encode_setcc(cnd: &Cnd) -> Vec<u8> {
@@ -14,6 +16,31 @@ pub fn encode_setcc(cnd: &Cnd) -> Vec<u8> {
     vec![0x0F, cnd, 0xC0]
 }
 
+/// Encodes `jmp [table + idx * 8]`. The address of the table is left as zeroes in the last 4 bytes.
+pub fn encode_jmp_table(idx: &Arg) -> Vec<u8> {
+    let Arg::Reg { reg } = idx else {
+        unreachable!("The index of a jump table should be a register after `patch`.");
+    };
+    let (x, idx) = encode_reg(reg);
+
+    let mut v = Vec::new();
+    if x == 1 {
+        // REX.X extends the index in the SIB byte.
+        v.push(0x42);
+    }
+    // The SIB byte scales the index by 8 and has no base, only a 32-bit displacement.
+    v.extend([
+        0xFF,
+        0x24,
+        0b11_000_101 | (idx << 3),
+        0x00,
+        0x00,
+        0x00,
+        0x00,
+    ]);
+    v
+}
+
 #[cfg(test)]
 mod tests {
     mod retq {
@@ -22,4 +49,19 @@ mod tests {
 
         check!(retq, retq!(), vec![0xC3]);
     }
+
+    mod jmp_table {
+        use crate::*;
+
+        check!(
+            rcx,
+            jmp_table!(reg!(RCX), vec![]),
+            vec![0xFF, 0x24, 0xCD, 0x00, 0x00, 0x00, 0x00]
+        );
+        check!(
+            r9,
+            jmp_table!(reg!(R9), vec![]),
+            vec![0x42, 0xFF, 0x24, 0xCD, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
 }
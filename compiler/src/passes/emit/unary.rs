@@ -9,6 +9,12 @@ pub struct UnaryOpInfo {
 
 pub const NEGQ_INFO: UnaryOpInfo = UnaryOpInfo { op: 0xF7, pad: 0x3 };
 
+pub const NOTQ_INFO: UnaryOpInfo = UnaryOpInfo { op: 0xF7, pad: 0x2 };
+
+pub const INCQ_INFO: UnaryOpInfo = UnaryOpInfo { op: 0xFF, pad: 0x0 };
+
+pub const DECQ_INFO: UnaryOpInfo = UnaryOpInfo { op: 0xFF, pad: 0x1 };
+
 pub const CALLQ_INDIRECT_INFO: UnaryOpInfo = UnaryOpInfo { op: 0xFF, pad: 0x2 };
 
 pub fn encode_unary_instr(op_info: UnaryOpInfo, dst: &Arg) -> Vec<u8> {
@@ -47,6 +53,7 @@ pub fn encode_unary_instr(op_info: UnaryOpInfo, dst: &Arg) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     mod neg {
+        use crate::deref;
         use crate::*;
 
         check!(nreg1, negq!(reg!(RSP)), vec![0x48, 0xF7, 0xDC]);
@@ -68,4 +75,36 @@ mod tests {
             vec![0x49, 0xFF, 0xD5]
         );
     }
+
+    mod not {
+        use crate::*;
+
+        check!(reg, notq!(reg!(RCX)), vec![0x48, 0xF7, 0xD1]);
+    }
+
+    mod inc {
+        use crate::deref;
+        use crate::*;
+
+        check!(reg1, incq!(reg!(RCX)), vec![0x48, 0xFF, 0xC1]);
+        check!(reg2, incq!(reg!(R14)), vec![0x49, 0xFF, 0xC6]);
+        check!(
+            deref,
+            incq!(deref!(RBP, -8)),
+            vec![0x48, 0xFF, 0x85, 0xF8, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    mod dec {
+        use crate::deref;
+        use crate::*;
+
+        check!(reg1, decq!(reg!(RCX)), vec![0x48, 0xFF, 0xC9]);
+        check!(reg2, decq!(reg!(R14)), vec![0x49, 0xFF, 0xCE]);
+        check!(
+            deref,
+            decq!(deref!(RBP, -8)),
+            vec![0x48, 0xFF, 0x8D, 0xF8, 0xFF, 0xFF, 0xFF]
+        );
+    }
 }
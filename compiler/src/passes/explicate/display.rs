@@ -20,6 +20,18 @@ impl Display for CTail<'_> {
             CTail::Goto { lbl } => {
                 write!(indented(f), "jmp {lbl}")
             }
+            CTail::Switch {
+                scrut,
+                lo,
+                table,
+                default,
+            } => {
+                write!(
+                    indented(f),
+                    "switch ({scrut} - {lo}) [{}] else jmp {default}",
+                    table.iter().format(", ")
+                )
+            }
         }
     }
 }
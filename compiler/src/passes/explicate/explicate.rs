@@ -7,10 +7,28 @@ use std::collections::HashMap;
 
 pub struct Env<'a, 'p> {
     pub blocks: &'a mut HashMap<UniqueSym<'p>, CTail<'p>>,
+    /// Targets of the enclosing loops, with the innermost loop last.
+    pub loops: Vec<LoopTargets<'p>>,
+}
+
+pub struct LoopTargets<'p> {
+    pub lbl: Option<UniqueSym<'p>>,
     /// (block to jump to, variable to write to)
-    pub break_target: Option<(UniqueSym<'p>, UniqueSym<'p>)>,
+    pub break_target: (UniqueSym<'p>, UniqueSym<'p>),
     /// block to jump to
-    pub continue_target: Option<UniqueSym<'p>>,
+    pub continue_target: UniqueSym<'p>,
+}
+
+impl<'p> Env<'_, 'p> {
+    /// Returns the targets of the loop with the given label, or of the innermost loop if there is
+    /// no label.
+    pub fn loop_targets(&self, lbl: Option<UniqueSym<'p>>) -> &LoopTargets<'p> {
+        self.loops
+            .iter()
+            .rev()
+            .find(|targets| lbl.is_none() || targets.lbl == lbl)
+            .expect("Jumps outside loops should have been rejected by `constrain`.")
+    }
 }
 
 impl<'p> PrgAtomized<'p> {
@@ -19,8 +37,7 @@ impl<'p> PrgAtomized<'p> {
         let mut blocks = HashMap::new();
         let mut env = Env {
             blocks: &mut blocks,
-            break_target: None,
-            continue_target: None,
+            loops: Vec::new(),
         };
 
         let mut fn_params = HashMap::new();
@@ -1,5 +1,5 @@
 use crate::passes::atomize::{AExpr, Atom};
-use crate::passes::explicate::explicate::Env;
+use crate::passes::explicate::explicate::{Env, LoopTargets};
 use crate::passes::explicate::{explicate_pred, CExpr, CTail};
 
 use crate::passes::parse::{Meta, Typed};
@@ -74,14 +74,14 @@ pub fn explicate_assign<'p>(
                 env,
             )
         }
-        AExpr::Loop { bdy, .. } => {
+        AExpr::Loop { lbl, bdy } => {
             let loop_block_sym = gen_sym("tmp");
             let tail = create_block(tail);
-            let mut env = Env {
-                blocks: env.blocks,
-                break_target: Some((tail, sym)),
-                continue_target: Some(loop_block_sym),
-            };
+            env.loops.push(LoopTargets {
+                lbl,
+                break_target: (tail, sym),
+                continue_target: loop_block_sym,
+            });
 
             let loop_block = explicate_assign(
                 gen_sym("ignore"),
@@ -89,15 +89,16 @@ pub fn explicate_assign<'p>(
                 CTail::Goto {
                     lbl: loop_block_sym,
                 },
-                &mut env,
+                env,
             );
+            env.loops.pop();
             env.blocks.insert(loop_block_sym, loop_block);
             CTail::Goto {
                 lbl: loop_block_sym,
             }
         }
-        AExpr::Break { bdy, .. } => {
-            let (break_sym, break_var) = env.break_target.unwrap();
+        AExpr::Break { lbl, bdy } => {
+            let (break_sym, break_var) = env.loop_targets(lbl).break_target;
             let break_goto = CTail::Goto { lbl: break_sym };
             explicate_assign(break_var, *bdy, break_goto, env)
         }
@@ -127,14 +128,15 @@ pub fn explicate_assign<'p>(
             ),
             env,
         ),
-        AExpr::Continue { .. } => CTail::Goto {
-            lbl: env.continue_target.unwrap(),
+        AExpr::Continue { lbl } => CTail::Goto {
+            lbl: env.loop_targets(lbl).continue_target,
         },
         AExpr::Return { bdy, .. } => {
             let tmp = gen_sym("return");
+            // The type of the returned value, which is needed to flatten returned structs.
             let tail = CTail::Return {
                 expr: Meta {
-                    meta: bnd.meta,
+                    meta: bdy.meta.clone(),
                     inner: Atom::Var { sym: tmp },
                 },
             };
@@ -156,6 +158,32 @@ pub fn explicate_assign<'p>(
             },
             tail: Box::new(tail),
         },
+        AExpr::Switch {
+            scrut,
+            lo,
+            table,
+            arms,
+        } => {
+            let tb = gen_sym("tmp");
+            env.blocks.insert(tb, tail);
+
+            let arms = arms
+                .into_iter()
+                .map(|arm| {
+                    let arm_block = gen_sym("tmp");
+                    let arm_tail = explicate_assign(sym, arm, CTail::Goto { lbl: tb }, env);
+                    env.blocks.insert(arm_block, arm_tail);
+                    arm_block
+                })
+                .collect::<Vec<_>>();
+
+            CTail::Switch {
+                scrut,
+                lo,
+                table: table.into_iter().map(|arm| arms[arm]).collect(),
+                default: *arms.last().unwrap(),
+            }
+        }
         AExpr::Asm { instrs } => CTail::Seq {
             sym,
             bnd: Meta {
@@ -134,7 +134,7 @@ pub fn explicate_pred<'p>(
                 env,
             )
         }
-        AExpr::Loop { .. } => {
+        AExpr::Loop { .. } | AExpr::Switch { .. } => {
             let tmp = gen_sym("tmp");
             let cnd_ = AExpr::Atom {
                 atm: Atom::Var { sym: tmp },
@@ -155,6 +155,25 @@ pub fn explicate_pred<'p>(
             explicate_pred(cnt.inner, thn, els, env),
             env,
         ),
+        AExpr::Asm { instrs } => {
+            let tmp = gen_sym("tmp");
+            explicate_assign(
+                tmp,
+                Meta {
+                    meta: Type::Bool,
+                    inner: AExpr::Asm { instrs },
+                },
+                explicate_pred(
+                    AExpr::Atom {
+                        atm: Atom::Var { sym: tmp },
+                    },
+                    thn,
+                    els,
+                    env,
+                ),
+                env,
+            )
+        }
         AExpr::AccessField { strct, field, .. } => {
             let tmp = gen_sym("tmp");
             explicate_assign(
@@ -188,7 +207,6 @@ pub fn explicate_pred<'p>(
         | AExpr::Break { .. }
         | AExpr::Continue { .. }
         | AExpr::Return { .. }
-        | AExpr::Struct { .. }
-        | AExpr::Asm { .. } => unreachable!(),
+        | AExpr::Struct { .. } => unreachable!(),
     }
 }
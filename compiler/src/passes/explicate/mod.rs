@@ -9,7 +9,7 @@ use crate::passes::atomize::Atom;
 use crate::passes::parse::types::Type;
 use crate::passes::parse::{BinaryOp, Param, TypeDef, Typed, UnaryOp};
 use crate::passes::select::std_lib::Std;
-use crate::passes::select::InstrSelected;
+use crate::passes::select::AsmItemSelected;
 use crate::utils::gen_sym::UniqueSym;
 use derive_more::Display;
 use itertools::Itertools;
@@ -45,6 +45,13 @@ pub enum CTail<'p> {
     Goto {
         lbl: UniqueSym<'p>,
     },
+    /// Jumps to `table[scrut - lo]`, or to `default` if `scrut - lo` is outside of the table.
+    Switch {
+        scrut: Atom<'p>,
+        lo: i64,
+        table: Vec<UniqueSym<'p>>,
+        default: UniqueSym<'p>,
+    },
 }
 
 pub enum CExpr<'p> {
@@ -76,6 +83,6 @@ pub enum CExpr<'p> {
         field: &'p str,
     },
     Asm {
-        instrs: Vec<InstrSelected<'p>>,
+        instrs: Vec<AsmItemSelected<'p>>,
     },
 }
@@ -0,0 +1,304 @@
+use crate::passes::atomize::{AExpr, Atom, DefAtomized, PrgAtomized};
+use crate::passes::parse::{BinaryOp, Meta, Typed, UnaryOp};
+use crate::passes::select::AsmItem;
+use crate::passes::validate::TLit;
+use crate::utils::gen_sym::UniqueSym;
+use std::collections::{HashMap, HashSet};
+
+struct Env<'p> {
+    /// Variables that are assigned to after they are bound, which are never propagated.
+    assigned: HashSet<UniqueSym<'p>>,
+    /// Variables that are bound to a constant.
+    consts: HashMap<UniqueSym<'p>, TLit>,
+}
+
+impl<'p> PrgAtomized<'p> {
+    /// Evaluates operations on constants at compile time, propagates the constants that variables
+    /// are bound to if those variables are never assigned to, and removes the branches of `if`
+    /// expressions that can never be taken.
+    ///
+    /// The bindings themselves are kept, since inline assembly can still refer to them.
+    #[must_use]
+    pub fn fold(self) -> PrgAtomized<'p> {
+        PrgAtomized {
+            defs: self
+                .defs
+                .into_iter()
+                .map(|(sym, def)| (sym, fold_def(def)))
+                .collect(),
+            entry: self.entry,
+            std: self.std,
+        }
+    }
+}
+
+fn fold_def(def: DefAtomized) -> DefAtomized {
+    match def {
+        DefAtomized::Fn {
+            sym,
+            params,
+            typ,
+            bdy,
+            inline,
+        } => {
+            let mut assigned = HashSet::new();
+            collect_assigned(&bdy, &mut assigned);
+            let mut env = Env {
+                assigned,
+                consts: HashMap::new(),
+            };
+
+            DefAtomized::Fn {
+                sym,
+                params,
+                typ,
+                bdy: fold_expr(bdy, &mut env),
+                inline,
+            }
+        }
+        DefAtomized::TypeDef { sym, def } => DefAtomized::TypeDef { sym, def },
+    }
+}
+
+fn fold_expr<'p>(expr: Typed<'p, AExpr<'p>>, env: &mut Env<'p>) -> Typed<'p, AExpr<'p>> {
+    let inner = match expr.inner {
+        AExpr::Atom { atm } => AExpr::Atom {
+            atm: fold_atom(atm, env),
+        },
+        AExpr::BinaryOp { op, exprs } => match exprs.map(|atm| fold_atom(atm, env)) {
+            [Atom::Val { val: lhs }, Atom::Val { val: rhs }] => {
+                match fold_binary_op(op, lhs, rhs) {
+                    Some(val) => AExpr::Atom {
+                        atm: Atom::Val { val },
+                    },
+                    None => AExpr::BinaryOp {
+                        op,
+                        exprs: [Atom::Val { val: lhs }, Atom::Val { val: rhs }],
+                    },
+                }
+            }
+            exprs => AExpr::BinaryOp { op, exprs },
+        },
+        AExpr::UnaryOp { op, expr: atm } => match fold_atom(atm, env) {
+            Atom::Val { val } => AExpr::Atom {
+                atm: Atom::Val {
+                    val: fold_unary_op(op, val),
+                },
+            },
+            atm => AExpr::UnaryOp { op, expr: atm },
+        },
+        AExpr::Let { sym, bnd, bdy } => {
+            let bnd = Box::new(fold_expr(*bnd, env));
+            if let Some(val) = const_value(&bnd) {
+                if !env.assigned.contains(&sym) {
+                    env.consts.insert(sym, val);
+                }
+            }
+            AExpr::Let {
+                sym,
+                bnd,
+                bdy: Box::new(fold_expr(*bdy, env)),
+            }
+        }
+        AExpr::If { cnd, thn, els } => {
+            let cnd = Box::new(fold_expr(*cnd, env));
+            match cnd.inner {
+                AExpr::Atom {
+                    atm: Atom::Val { val },
+                } => {
+                    let bdy = if val.bool() { thn } else { els };
+                    return Meta {
+                        meta: expr.meta,
+                        inner: fold_expr(*bdy, env).inner,
+                    };
+                }
+                _ => AExpr::If {
+                    cnd,
+                    thn: Box::new(fold_expr(*thn, env)),
+                    els: Box::new(fold_expr(*els, env)),
+                },
+            }
+        }
+        AExpr::Apply { fun, args } => AExpr::Apply {
+            fun: fold_atom(fun, env),
+            args: args
+                .into_iter()
+                .map(|(atm, typ)| (fold_atom(atm, env), typ))
+                .collect(),
+        },
+        AExpr::Loop { lbl, bdy } => AExpr::Loop {
+            lbl,
+            bdy: Box::new(fold_expr(*bdy, env)),
+        },
+        AExpr::Break { lbl, bdy } => AExpr::Break {
+            lbl,
+            bdy: Box::new(fold_expr(*bdy, env)),
+        },
+        AExpr::Seq { stmt, cnt } => {
+            let stmt = Box::new(fold_expr(*stmt, env));
+            // Atoms have no side effects, so they can be left out.
+            if let AExpr::Atom { .. } = stmt.inner {
+                return Meta {
+                    meta: expr.meta,
+                    inner: fold_expr(*cnt, env).inner,
+                };
+            }
+            AExpr::Seq {
+                stmt,
+                cnt: Box::new(fold_expr(*cnt, env)),
+            }
+        }
+        AExpr::Assign { sym, bnd } => AExpr::Assign {
+            sym,
+            bnd: Box::new(fold_expr(*bnd, env)),
+        },
+        AExpr::Return { bdy } => AExpr::Return {
+            bdy: Box::new(fold_expr(*bdy, env)),
+        },
+        AExpr::Struct { sym, fields } => AExpr::Struct {
+            sym,
+            fields: fields
+                .into_iter()
+                .map(|(field, atm)| (field, fold_atom(atm, env)))
+                .collect(),
+        },
+        AExpr::AccessField { strct, field } => AExpr::AccessField {
+            strct: fold_atom(strct, env),
+            field,
+        },
+        AExpr::Switch {
+            scrut,
+            lo,
+            table,
+            mut arms,
+        } => match fold_atom(scrut, env) {
+            Atom::Val { val } => {
+                // Values outside of the table take the last arm.
+                let index = i64::from(val).wrapping_sub(lo) as u64;
+                let arm = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| table.get(index))
+                    .copied()
+                    .unwrap_or(arms.len() - 1);
+                return Meta {
+                    meta: expr.meta,
+                    inner: fold_expr(arms.swap_remove(arm), env).inner,
+                };
+            }
+            scrut => AExpr::Switch {
+                scrut,
+                lo,
+                table,
+                arms: arms.into_iter().map(|arm| fold_expr(arm, env)).collect(),
+            },
+        },
+        AExpr::FunRef { .. } | AExpr::Continue { .. } | AExpr::Asm { .. } => expr.inner,
+    };
+
+    Meta {
+        meta: expr.meta,
+        inner,
+    }
+}
+
+/// Returns the constant that the expression evaluates to, if it ends in one.
+fn const_value(expr: &Typed<AExpr>) -> Option<TLit> {
+    match &expr.inner {
+        AExpr::Atom {
+            atm: Atom::Val { val },
+        } => Some(*val),
+        AExpr::Let { bdy, .. } => const_value(bdy),
+        AExpr::Seq { cnt, .. } => const_value(cnt),
+        _ => None,
+    }
+}
+
+fn fold_atom<'p>(atm: Atom<'p>, env: &Env<'p>) -> Atom<'p> {
+    match atm {
+        Atom::Var { sym } => env.consts.get(&sym).map_or(atm, |&val| Atom::Val { val }),
+        Atom::Val { .. } => atm,
+    }
+}
+
+/// Evaluates the operation in the same way as the instructions that `select` emits for it, or
+/// returns `None` if it would fault at runtime.
+fn fold_binary_op(op: BinaryOp, lhs: TLit, rhs: TLit) -> Option<TLit> {
+    let (a, b) = (i64::from(lhs), i64::from(rhs));
+    let val = match op {
+        BinaryOp::Add => a.wrapping_add(b),
+        BinaryOp::Sub => a.wrapping_sub(b),
+        BinaryOp::Mul => a.wrapping_mul(b),
+        // Division uses the unsigned `divq` instruction.
+        BinaryOp::Div => (a as u64).checked_div(b as u64)? as i64,
+        BinaryOp::Mod => (a as u64).checked_rem(b as u64)? as i64,
+        BinaryOp::LAnd => a & b,
+        BinaryOp::LOr => a | b,
+        BinaryOp::Xor => a ^ b,
+        BinaryOp::GT => return Some(TLit::Bool { val: a > b }),
+        BinaryOp::GE => return Some(TLit::Bool { val: a >= b }),
+        BinaryOp::EQ => return Some(TLit::Bool { val: a == b }),
+        BinaryOp::LE => return Some(TLit::Bool { val: a <= b }),
+        BinaryOp::LT => return Some(TLit::Bool { val: a < b }),
+        BinaryOp::NE => return Some(TLit::Bool { val: a != b }),
+    };
+    Some(lit_like(lhs, val))
+}
+
+fn fold_unary_op(op: UnaryOp, val: TLit) -> TLit {
+    match op {
+        UnaryOp::Neg => lit_like(val, i64::from(val).wrapping_neg()),
+        UnaryOp::Not => TLit::Bool { val: !val.bool() },
+    }
+}
+
+/// Returns a literal of the same type as `lit`, with the given value.
+fn lit_like(lit: TLit, val: i64) -> TLit {
+    match lit {
+        TLit::I64 { .. } => TLit::I64 { val },
+        TLit::U64 { .. } => TLit::U64 { val: val as u64 },
+        TLit::Bool { .. } => TLit::Bool { val: val != 0 },
+        TLit::Unit => TLit::Unit,
+    }
+}
+
+fn collect_assigned<'p>(expr: &Typed<'p, AExpr<'p>>, assigned: &mut HashSet<UniqueSym<'p>>) {
+    match &expr.inner {
+        AExpr::Assign { sym, bnd } => {
+            assigned.insert(*sym);
+            collect_assigned(bnd, assigned);
+        }
+        AExpr::Let { bnd, bdy, .. } => {
+            collect_assigned(bnd, assigned);
+            collect_assigned(bdy, assigned);
+        }
+        AExpr::If { cnd, thn, els } => {
+            collect_assigned(cnd, assigned);
+            collect_assigned(thn, assigned);
+            collect_assigned(els, assigned);
+        }
+        AExpr::Seq { stmt, cnt } => {
+            collect_assigned(stmt, assigned);
+            collect_assigned(cnt, assigned);
+        }
+        AExpr::Loop { bdy, .. } | AExpr::Break { bdy, .. } | AExpr::Return { bdy } => {
+            collect_assigned(bdy, assigned);
+        }
+        AExpr::Switch { arms, .. } => {
+            for arm in arms {
+                collect_assigned(arm, assigned);
+            }
+        }
+        AExpr::Asm { instrs } => {
+            // Inline assembly can write to any of the variables that it refers to.
+            assigned.extend(instrs.iter().flat_map(AsmItem::vars));
+        }
+        AExpr::Atom { .. }
+        | AExpr::BinaryOp { .. }
+        | AExpr::UnaryOp { .. }
+        | AExpr::Apply { .. }
+        | AExpr::FunRef { .. }
+        | AExpr::Continue { .. }
+        | AExpr::Struct { .. }
+        | AExpr::AccessField { .. } => {}
+    }
+}
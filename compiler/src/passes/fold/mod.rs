@@ -0,0 +1,3 @@
+pub mod fold;
+#[cfg(test)]
+mod tests;
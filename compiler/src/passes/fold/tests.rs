@@ -0,0 +1,136 @@
+use crate::passes::atomize::{AExpr, Atom, PrgAtomized};
+use crate::passes::parse::parse::parse_program;
+use crate::passes::parse::{BinaryOp, Def, Typed};
+use crate::passes::validate::TLit;
+
+fn atomize(program: &str) -> PrgAtomized<'_> {
+    parse_program(program)
+        .unwrap()
+        .validate()
+        .unwrap()
+        .reveal()
+        .decide()
+        .inline()
+        .atomize()
+}
+
+/// Returns the body of the entry function.
+fn entry<'a, 'p>(program: &'a PrgAtomized<'p>) -> &'a Typed<'p, AExpr<'p>> {
+    let Def::Fn { bdy, .. } = &program.defs[&program.entry] else {
+        panic!("The entry should be a function.");
+    };
+    bdy
+}
+
+/// Collects the expression and all expressions nested in it.
+fn collect<'a, 'p>(expr: &'a Typed<'p, AExpr<'p>>, exprs: &mut Vec<&'a AExpr<'p>>) {
+    exprs.push(&expr.inner);
+    match &expr.inner {
+        AExpr::Let {
+            bnd: e1, bdy: e2, ..
+        }
+        | AExpr::Seq { stmt: e1, cnt: e2 } => {
+            collect(e1, exprs);
+            collect(e2, exprs);
+        }
+        AExpr::If { cnd, thn, els } => {
+            collect(cnd, exprs);
+            collect(thn, exprs);
+            collect(els, exprs);
+        }
+        AExpr::Loop { bdy, .. }
+        | AExpr::Break { bdy, .. }
+        | AExpr::Return { bdy }
+        | AExpr::Assign { bnd: bdy, .. } => collect(bdy, exprs),
+        AExpr::Switch { arms, .. } => {
+            for arm in arms {
+                collect(arm, exprs);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the binary operations with their operands, and whether any `if` is left.
+fn binary_ops_and_ifs<'p>(program: &PrgAtomized<'p>) -> (Vec<(BinaryOp, [Atom<'p>; 2])>, bool) {
+    let mut exprs = Vec::new();
+    collect(entry(program), &mut exprs);
+
+    let ops = exprs
+        .iter()
+        .filter_map(|expr| match expr {
+            AExpr::BinaryOp { op, exprs } => Some((*op, *exprs)),
+            _ => None,
+        })
+        .collect();
+    let ifs = exprs.iter().any(|expr| matches!(expr, AExpr::If { .. }));
+    (ops, ifs)
+}
+
+/// Returns the constant that the expression evaluates to, if it ends in one.
+fn result(expr: &Typed<AExpr>) -> Option<TLit> {
+    match &expr.inner {
+        AExpr::Atom {
+            atm: Atom::Val { val },
+        } => Some(*val),
+        AExpr::Let { bdy, .. } => result(bdy),
+        AExpr::Seq { cnt, .. } => result(cnt),
+        _ => None,
+    }
+}
+
+#[test]
+fn fold_constants() {
+    let program = atomize(
+        "fn main() -> I64 {
+            let x = 2 * 3;
+            if x > 5 { x + 1 } else { x - 1 }
+        }",
+    );
+
+    let (ops, ifs) = binary_ops_and_ifs(&program);
+    assert_eq!(
+        ops.len(),
+        4,
+        "The program should start with four operations."
+    );
+    assert!(ifs, "The program should start with an `if`.");
+
+    let program = program.fold();
+
+    let (ops, ifs) = binary_ops_and_ifs(&program);
+    assert!(ops.is_empty(), "Every operation should be folded.");
+    assert!(
+        !ifs,
+        "The `if` should be replaced by the branch that is taken."
+    );
+    assert_eq!(result(entry(&program)), Some(TLit::I64 { val: 7 }));
+}
+
+#[test]
+fn fold_keeps_division_by_zero() {
+    let program = atomize(
+        "fn main() -> I64 {
+            let a = 7 / 2;
+            let b: I64 = 7 / 0;
+            let c: I64 = 7 % 0;
+            a
+        }",
+    )
+    .fold();
+
+    // Dividing by zero faults at runtime, so those operations must be left in place.
+    let (ops, _) = binary_ops_and_ifs(&program);
+    let ops = ops
+        .into_iter()
+        .map(|(op, [lhs, rhs])| (op, [lhs, rhs].map(|atm| atm.to_string())))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ops,
+        [
+            (BinaryOp::Div, ["7".to_string(), "0".to_string()]),
+            (BinaryOp::Mod, ["7".to_string(), "0".to_string()]),
+        ]
+    );
+    assert_eq!(result(entry(&program)), Some(TLit::I64 { val: 3 }));
+}
@@ -0,0 +1,523 @@
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{Def, Inline, Meta, Param, Typed};
+use crate::passes::reveal::{DefRevealed, PrgRevealed, RExpr};
+use crate::utils::gen_sym::{gen_sym, UniqueSym};
+use std::collections::{HashMap, HashSet};
+
+/// The size of a call: loading the function, calling it and returning, moving the result into and
+/// out of `%rax`, and moving two arguments into and out of their registers. A small callee adds no
+/// prologue or epilogue, as it keeps the frame pointer of its caller and only saves the
+/// callee-saved registers that it writes to.
+pub(super) const CALL_SIZE: usize = 9;
+
+/// The number of expressions that inlining a function may add to the program, beyond what is
+/// saved by leaving out the calls.
+pub(super) const MAX_GROWTH: usize = 48;
+
+/// What a function body contains, as far as inlining is concerned.
+#[derive(Default)]
+struct Scan<'p> {
+    /// The number of expressions in the body.
+    size: usize,
+    /// The functions that are called directly, once for every call.
+    calls: Vec<UniqueSym<'p>>,
+    /// The functions that are referred to other than by calling them directly.
+    escapes: Vec<UniqueSym<'p>>,
+    /// Whether the body contains inline assembly, whose labels cannot be duplicated.
+    asm: bool,
+}
+
+/// A function that is inlined at its call sites.
+struct Callee<'p> {
+    params: Vec<Param<UniqueSym<'p>>>,
+    bdy: Typed<'p, RExpr<'p>>,
+}
+
+impl<'p> PrgRevealed<'p> {
+    /// Replaces direct calls of small, non-recursive functions by their bodies. Whether a function
+    /// is inlined is decided by the size of its body and the number of calls to it, unless it is
+    /// annotated with `#[inline]` or `#[inline(never)]`.
+    ///
+    /// Functions that are no longer called afterwards are left for `prune` to remove.
+    #[must_use]
+    pub fn inline(self) -> PrgRevealed<'p> {
+        let mut scans = HashMap::new();
+        for (sym, def) in &self.defs {
+            if let Def::Fn { bdy, .. } = def {
+                let mut scan = Scan::default();
+                scan_expr(bdy, &mut scan);
+                scans.insert(*sym, scan);
+            }
+        }
+
+        let mut calls = HashMap::<_, usize>::new();
+        let mut escaping = HashSet::new();
+        for scan in scans.values() {
+            for callee in &scan.calls {
+                *calls.entry(*callee).or_default() += 1;
+            }
+            escaping.extend(scan.escapes.iter().copied());
+        }
+
+        // Callees are inlined into a function before it is inlined itself, so every function is
+        // visited after the functions that it calls, except along cycles of calls.
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        let mut syms = scans.keys().copied().collect::<Vec<_>>();
+        syms.sort();
+        for sym in syms {
+            post_order(sym, &scans, &mut visited, &mut order);
+        }
+
+        let mut defs = self.defs;
+        let mut callees = HashMap::new();
+        for sym in order {
+            let Some(Def::Fn {
+                sym,
+                params,
+                typ,
+                bdy,
+                inline,
+            }) = defs.remove(&sym)
+            else {
+                unreachable!("Only functions are scanned.");
+            };
+            let bdy = inline_expr(bdy, &callees);
+
+            let mut scan = Scan::default();
+            scan_expr(&bdy, &mut scan);
+            let candidate = !scan.asm && !is_recursive(sym, &scans);
+            let profitable = match inline {
+                Inline::Always => true,
+                Inline::Never => false,
+                Inline::Auto => profitable(
+                    scan.size,
+                    calls.get(&sym).copied().unwrap_or_default(),
+                    escaping.contains(&sym),
+                ),
+            };
+            if candidate && profitable {
+                callees.insert(
+                    sym,
+                    Callee {
+                        params: params.clone(),
+                        bdy: copy_expr(&bdy, &mut Rename::default()),
+                    },
+                );
+            }
+
+            defs.insert(
+                sym,
+                DefRevealed::Fn {
+                    sym,
+                    params,
+                    typ,
+                    bdy,
+                    inline,
+                },
+            );
+        }
+
+        PrgRevealed {
+            defs,
+            entry: self.entry,
+            std: self.std,
+        }
+    }
+}
+
+/// Whether inlining a function of the given size at each of its calls pays off. Every inlined call
+/// saves the cost of the call, while the body is copied once per call. The original function can
+/// be removed if every call to it is inlined and it is not referred to otherwise.
+fn profitable(size: usize, calls: usize, escapes: bool) -> bool {
+    let copies = if escapes {
+        calls
+    } else {
+        calls.saturating_sub(1)
+    };
+    size * copies <= CALL_SIZE * calls + MAX_GROWTH
+}
+
+fn post_order<'p>(
+    sym: UniqueSym<'p>,
+    scans: &HashMap<UniqueSym<'p>, Scan<'p>>,
+    visited: &mut HashSet<UniqueSym<'p>>,
+    order: &mut Vec<UniqueSym<'p>>,
+) {
+    if !visited.insert(sym) {
+        return;
+    }
+    for callee in &scans[&sym].calls {
+        if scans.contains_key(callee) {
+            post_order(*callee, scans, visited, order);
+        }
+    }
+    order.push(sym);
+}
+
+/// Whether the function can call itself, directly or through other functions.
+fn is_recursive<'p>(sym: UniqueSym<'p>, scans: &HashMap<UniqueSym<'p>, Scan<'p>>) -> bool {
+    let mut visited = HashSet::new();
+    let mut worklist = scans[&sym].calls.clone();
+    while let Some(callee) = worklist.pop() {
+        if callee == sym {
+            return true;
+        }
+        if visited.insert(callee) {
+            if let Some(scan) = scans.get(&callee) {
+                worklist.extend(scan.calls.iter().copied());
+            }
+        }
+    }
+    false
+}
+
+fn scan_expr<'p>(expr: &Typed<'p, RExpr<'p>>, scan: &mut Scan<'p>) {
+    scan.size += 1;
+    match &expr.inner {
+        RExpr::Lit { .. } | RExpr::Var { .. } | RExpr::Continue { .. } => {}
+        RExpr::FunRef { sym } => scan.escapes.push(*sym),
+        RExpr::Apply { fun, args } => {
+            match &fun.inner {
+                RExpr::FunRef { sym } => scan.calls.push(*sym),
+                _ => scan_expr(fun, scan),
+            }
+            for arg in args {
+                scan_expr(arg, scan);
+            }
+        }
+        RExpr::BinaryOp { exprs, .. } => {
+            for expr in exprs {
+                scan_expr(expr, scan);
+            }
+        }
+        RExpr::UnaryOp { expr, .. } => scan_expr(expr, scan),
+        RExpr::Let { bnd, bdy, .. } => {
+            if let Some(bnd) = bnd {
+                scan_expr(bnd, scan);
+            }
+            scan_expr(bdy, scan);
+        }
+        RExpr::If { cnd, thn, els } => {
+            scan_expr(cnd, scan);
+            scan_expr(thn, scan);
+            scan_expr(els, scan);
+        }
+        RExpr::Loop { bdy, .. }
+        | RExpr::Break { bdy, .. }
+        | RExpr::Return { bdy }
+        | RExpr::Assign { bnd: bdy, .. } => scan_expr(bdy, scan),
+        RExpr::Seq { stmt, cnt } => {
+            scan_expr(stmt, scan);
+            scan_expr(cnt, scan);
+        }
+        RExpr::Struct { fields, .. } => {
+            for (_, field) in fields {
+                scan_expr(field, scan);
+            }
+        }
+        RExpr::AccessField { strct, .. } => scan_expr(strct, scan),
+        RExpr::Switch { scrut, arms, .. } => {
+            scan_expr(scrut, scan);
+            for arm in arms {
+                scan_expr(arm, scan);
+            }
+        }
+        RExpr::Asm { .. } => scan.asm = true,
+        RExpr::Match { .. } => unreachable!("`match` should have been compiled away by `decide`."),
+    }
+}
+
+fn inline_expr<'p>(
+    expr: Typed<'p, RExpr<'p>>,
+    callees: &HashMap<UniqueSym<'p>, Callee<'p>>,
+) -> Typed<'p, RExpr<'p>> {
+    let inline_box = |expr: Box<Typed<'p, RExpr<'p>>>| Box::new(inline_expr(*expr, callees));
+
+    let inner = match expr.inner {
+        RExpr::Apply { fun, args } => {
+            let args = args
+                .into_iter()
+                .map(|arg| inline_expr(arg, callees))
+                .collect::<Vec<_>>();
+            match &fun.inner {
+                RExpr::FunRef { sym } if callees.contains_key(sym) => {
+                    return inline_call(&callees[sym], args, expr.meta);
+                }
+                _ => RExpr::Apply {
+                    fun: inline_box(fun),
+                    args,
+                },
+            }
+        }
+        RExpr::BinaryOp {
+            op,
+            exprs: [lhs, rhs],
+        } => RExpr::BinaryOp {
+            op,
+            exprs: [inline_box(lhs), inline_box(rhs)],
+        },
+        RExpr::UnaryOp { op, expr } => RExpr::UnaryOp {
+            op,
+            expr: inline_box(expr),
+        },
+        RExpr::Let {
+            sym,
+            mutable,
+            bnd,
+            bdy,
+        } => RExpr::Let {
+            sym,
+            mutable,
+            bnd: bnd.map(inline_box),
+            bdy: inline_box(bdy),
+        },
+        RExpr::If { cnd, thn, els } => RExpr::If {
+            cnd: inline_box(cnd),
+            thn: inline_box(thn),
+            els: inline_box(els),
+        },
+        RExpr::Loop { lbl, bdy } => RExpr::Loop {
+            lbl,
+            bdy: inline_box(bdy),
+        },
+        RExpr::Break { lbl, bdy } => RExpr::Break {
+            lbl,
+            bdy: inline_box(bdy),
+        },
+        RExpr::Return { bdy } => RExpr::Return {
+            bdy: inline_box(bdy),
+        },
+        RExpr::Seq { stmt, cnt } => RExpr::Seq {
+            stmt: inline_box(stmt),
+            cnt: inline_box(cnt),
+        },
+        RExpr::Assign { sym, bnd } => RExpr::Assign {
+            sym,
+            bnd: inline_box(bnd),
+        },
+        RExpr::Struct { sym, fields } => RExpr::Struct {
+            sym,
+            fields: fields
+                .into_iter()
+                .map(|(field, expr)| (field, inline_expr(expr, callees)))
+                .collect(),
+        },
+        RExpr::AccessField { strct, field } => RExpr::AccessField {
+            strct: inline_box(strct),
+            field,
+        },
+        RExpr::Switch {
+            scrut,
+            lo,
+            table,
+            arms,
+        } => RExpr::Switch {
+            scrut: inline_box(scrut),
+            lo,
+            table,
+            arms: arms
+                .into_iter()
+                .map(|arm| inline_expr(arm, callees))
+                .collect(),
+        },
+        inner @ (RExpr::Lit { .. }
+        | RExpr::Var { .. }
+        | RExpr::FunRef { .. }
+        | RExpr::Continue { .. }
+        | RExpr::Asm { .. }) => inner,
+        RExpr::Match { .. } => unreachable!("`match` should have been compiled away by `decide`."),
+    };
+
+    Meta {
+        meta: expr.meta,
+        inner,
+    }
+}
+
+/// Binds the arguments to fresh copies of the parameters, followed by a fresh copy of the body.
+/// If the body returns early, it is wrapped in a loop that the returns break out of.
+fn inline_call<'p>(
+    callee: &Callee<'p>,
+    args: Vec<Typed<'p, RExpr<'p>>>,
+    typ: Type<UniqueSym<'p>>,
+) -> Typed<'p, RExpr<'p>> {
+    let ret = gen_sym("return");
+    let mut rename = Rename {
+        syms: callee
+            .params
+            .iter()
+            .map(|param| (param.sym, gen_sym(param.sym.sym)))
+            .collect(),
+        ret: Some(ret),
+        returns: false,
+    };
+    let bdy = copy_expr(&callee.bdy, &mut rename);
+
+    let bdy = if rename.returns {
+        Meta {
+            meta: typ.clone(),
+            inner: RExpr::Loop {
+                lbl: Some(ret),
+                bdy: Box::new(Meta {
+                    meta: Type::Never,
+                    inner: RExpr::Break {
+                        lbl: Some(ret),
+                        bdy: Box::new(bdy),
+                    },
+                }),
+            },
+        }
+    } else {
+        bdy
+    };
+
+    callee
+        .params
+        .iter()
+        .zip(args)
+        .rev()
+        .fold(bdy, |bdy, (param, arg)| Meta {
+            meta: typ.clone(),
+            inner: RExpr::Let {
+                sym: rename.syms[&param.sym],
+                mutable: param.mutable,
+                bnd: Some(Box::new(arg)),
+                bdy: Box::new(bdy),
+            },
+        })
+}
+
+/// The fresh symbols of a copy of a function body.
+#[derive(Default)]
+struct Rename<'p> {
+    /// Fresh symbols of the variables and loop labels that are bound in the body.
+    syms: HashMap<UniqueSym<'p>, UniqueSym<'p>>,
+    /// The label that returns break out of, or `None` if returns are kept.
+    ret: Option<UniqueSym<'p>>,
+    /// Whether a return was replaced by a break.
+    returns: bool,
+}
+
+impl<'p> Rename<'p> {
+    fn get(&self, sym: UniqueSym<'p>) -> UniqueSym<'p> {
+        self.syms.get(&sym).copied().unwrap_or(sym)
+    }
+
+    fn fresh(&mut self, sym: UniqueSym<'p>) -> UniqueSym<'p> {
+        let fresh = gen_sym(sym.sym);
+        self.syms.insert(sym, fresh);
+        fresh
+    }
+}
+
+/// Copies the expression, giving fresh symbols to the variables and loop labels that it binds.
+fn copy_expr<'p>(expr: &Typed<'p, RExpr<'p>>, rename: &mut Rename<'p>) -> Typed<'p, RExpr<'p>> {
+    let inner = match &expr.inner {
+        RExpr::Lit { val } => RExpr::Lit { val: *val },
+        RExpr::Var { sym } => RExpr::Var {
+            sym: rename.get(*sym),
+        },
+        RExpr::FunRef { sym } => RExpr::FunRef { sym: *sym },
+        RExpr::BinaryOp {
+            op,
+            exprs: [lhs, rhs],
+        } => RExpr::BinaryOp {
+            op: *op,
+            exprs: [
+                Box::new(copy_expr(lhs, rename)),
+                Box::new(copy_expr(rhs, rename)),
+            ],
+        },
+        RExpr::UnaryOp { op, expr } => RExpr::UnaryOp {
+            op: *op,
+            expr: Box::new(copy_expr(expr, rename)),
+        },
+        RExpr::Let {
+            sym,
+            mutable,
+            bnd,
+            bdy,
+        } => {
+            let bnd = bnd.as_deref().map(|bnd| Box::new(copy_expr(bnd, rename)));
+            RExpr::Let {
+                sym: rename.fresh(*sym),
+                mutable: *mutable,
+                bnd,
+                bdy: Box::new(copy_expr(bdy, rename)),
+            }
+        }
+        RExpr::If { cnd, thn, els } => RExpr::If {
+            cnd: Box::new(copy_expr(cnd, rename)),
+            thn: Box::new(copy_expr(thn, rename)),
+            els: Box::new(copy_expr(els, rename)),
+        },
+        RExpr::Apply { fun, args } => RExpr::Apply {
+            fun: Box::new(copy_expr(fun, rename)),
+            args: args.iter().map(|arg| copy_expr(arg, rename)).collect(),
+        },
+        RExpr::Loop { lbl, bdy } => {
+            let lbl = lbl.map(|lbl| rename.fresh(lbl));
+            RExpr::Loop {
+                lbl,
+                bdy: Box::new(copy_expr(bdy, rename)),
+            }
+        }
+        RExpr::Break { lbl, bdy } => RExpr::Break {
+            lbl: lbl.map(|lbl| rename.get(lbl)),
+            bdy: Box::new(copy_expr(bdy, rename)),
+        },
+        RExpr::Continue { lbl } => RExpr::Continue {
+            lbl: lbl.map(|lbl| rename.get(lbl)),
+        },
+        RExpr::Return { bdy } => match rename.ret {
+            Some(ret) => {
+                rename.returns = true;
+                RExpr::Break {
+                    lbl: Some(ret),
+                    bdy: Box::new(copy_expr(bdy, rename)),
+                }
+            }
+            None => RExpr::Return {
+                bdy: Box::new(copy_expr(bdy, rename)),
+            },
+        },
+        RExpr::Seq { stmt, cnt } => RExpr::Seq {
+            stmt: Box::new(copy_expr(stmt, rename)),
+            cnt: Box::new(copy_expr(cnt, rename)),
+        },
+        RExpr::Assign { sym, bnd } => RExpr::Assign {
+            sym: rename.get(*sym),
+            bnd: Box::new(copy_expr(bnd, rename)),
+        },
+        RExpr::Struct { sym, fields } => RExpr::Struct {
+            sym: *sym,
+            fields: fields
+                .iter()
+                .map(|(field, expr)| (*field, copy_expr(expr, rename)))
+                .collect(),
+        },
+        RExpr::AccessField { strct, field } => RExpr::AccessField {
+            strct: Box::new(copy_expr(strct, rename)),
+            field,
+        },
+        RExpr::Switch {
+            scrut,
+            lo,
+            table,
+            arms,
+        } => RExpr::Switch {
+            scrut: Box::new(copy_expr(scrut, rename)),
+            lo: *lo,
+            table: table.clone(),
+            arms: arms.iter().map(|arm| copy_expr(arm, rename)).collect(),
+        },
+        RExpr::Asm { .. } => unreachable!("Functions with inline assembly are not inlined."),
+        RExpr::Match { .. } => unreachable!("`match` should have been compiled away by `decide`."),
+    };
+
+    Meta {
+        meta: expr.meta.clone(),
+        inner,
+    }
+}
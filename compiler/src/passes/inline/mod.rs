@@ -0,0 +1,4 @@
+pub mod inline;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,123 @@
+use crate::passes::inline::inline::{CALL_SIZE, MAX_GROWTH};
+use crate::passes::parse::parse::parse_program;
+use crate::passes::parse::{Def, Typed};
+use crate::passes::reveal::{PrgRevealed, RExpr};
+
+fn reveal(program: &str) -> PrgRevealed<'_> {
+    parse_program(program)
+        .unwrap()
+        .validate()
+        .unwrap()
+        .reveal()
+        .decide()
+}
+
+/// Collects the names of the functions that the expression calls directly.
+fn collect<'p>(expr: &Typed<'p, RExpr<'p>>, calls: &mut Vec<&'p str>) {
+    match &expr.inner {
+        RExpr::Apply { fun, args } => {
+            if let RExpr::FunRef { sym } = &fun.inner {
+                calls.push(sym.sym);
+            }
+            for arg in args {
+                collect(arg, calls);
+            }
+        }
+        RExpr::BinaryOp {
+            exprs: [e1, e2], ..
+        }
+        | RExpr::Seq { stmt: e1, cnt: e2 } => {
+            collect(e1, calls);
+            collect(e2, calls);
+        }
+        RExpr::Let { bnd, bdy, .. } => {
+            if let Some(bnd) = bnd {
+                collect(bnd, calls);
+            }
+            collect(bdy, calls);
+        }
+        RExpr::If { cnd, thn, els } => {
+            collect(cnd, calls);
+            collect(thn, calls);
+            collect(els, calls);
+        }
+        RExpr::UnaryOp { expr: bdy, .. }
+        | RExpr::Loop { bdy, .. }
+        | RExpr::Break { bdy, .. }
+        | RExpr::Return { bdy }
+        | RExpr::Assign { bnd: bdy, .. } => collect(bdy, calls),
+        _ => {}
+    }
+}
+
+/// Returns the names of the functions that the entry calls directly.
+fn calls<'p>(program: &PrgRevealed<'p>) -> Vec<&'p str> {
+    let Def::Fn { bdy, .. } = &program.defs[&program.entry] else {
+        panic!("The entry should be a function.");
+    };
+    let mut calls = Vec::new();
+    collect(bdy, &mut calls);
+    calls
+}
+
+#[test]
+fn inline_small_function() {
+    let program = reveal(
+        "fn add(a: I64, b: I64) -> I64 { a + b }
+        fn main() -> I64 { add(1, 2) + add(3, 4) }",
+    );
+    assert_eq!(calls(&program), ["add", "add"]);
+
+    let program = program.inline();
+    assert!(
+        calls(&program).is_empty(),
+        "Both calls should be replaced by the body of `add`."
+    );
+}
+
+#[test]
+fn inline_never() {
+    let program = reveal(
+        "#[inline(never)]
+        fn add(a: I64, b: I64) -> I64 { a + b }
+        fn main() -> I64 { add(1, 2) + add(3, 4) }",
+    )
+    .inline();
+    assert_eq!(calls(&program), ["add", "add"]);
+}
+
+#[test]
+fn inline_max_growth() {
+    // A function that is called twice is copied once more than it is removed, so its body may be
+    // as large as the two calls it saves plus the allowed growth.
+    let limit = 2 * CALL_SIZE + MAX_GROWTH;
+    // A sum of `n` terms has `n` variables and `n - 1` additions.
+    let program = |attr: &str, n: usize| {
+        format!(
+            "{attr} fn big(x: I64) -> I64 {{ {} }}
+            fn main() -> I64 {{ big(1) + big(2) }}",
+            vec!["x"; n].join(" + ")
+        )
+    };
+    let largest = limit.div_ceil(2);
+    assert!(2 * largest - 1 <= limit && 2 * largest + 1 > limit);
+
+    let inlined = program("", largest);
+    assert!(
+        calls(&reveal(&inlined).inline()).is_empty(),
+        "A body within the limit should be inlined."
+    );
+
+    let too_large = program("", largest + 1);
+    assert_eq!(
+        calls(&reveal(&too_large).inline()),
+        ["big", "big"],
+        "A body beyond the limit should not be inlined."
+    );
+
+    let forced = program("#[inline]", largest + 1);
+    assert!(
+        calls(&reveal(&forced).inline()).is_empty(),
+        "`#[inline]` should override the limit."
+    );
+}
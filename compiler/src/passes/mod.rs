@@ -1,11 +1,16 @@
 pub mod assign;
 pub mod atomize;
 pub mod conclude;
+pub mod decide;
 pub mod eliminate;
 pub mod emit;
 pub mod explicate;
+pub mod fold;
+pub mod inline;
 pub mod parse;
 pub mod patch;
+pub mod peephole;
+pub mod prune;
 pub mod reveal;
 pub mod select;
 pub mod validate;
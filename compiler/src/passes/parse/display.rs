@@ -1,11 +1,42 @@
-use crate::passes::parse::{Def, Expr, TypeDef};
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{Def, Expr, Inline, MatchArm, Param, Pattern, TypeDef};
 use indenter::indented;
 use itertools::Itertools;
 use std::fmt::Write;
 use std::fmt::{Display, Formatter};
 
-impl<IdentVars: Display, IdentFields: Display, Expr: Display> Display
-    for Def<IdentVars, IdentFields, Expr>
+/// A type annotation, which is left out of the output if it is inferred.
+pub trait Annotation {
+    fn fmt_annotation(&self, f: &mut Formatter<'_>, prefix: &str) -> std::fmt::Result;
+}
+
+impl<A: Display> Annotation for Type<A> {
+    fn fmt_annotation(&self, f: &mut Formatter<'_>, prefix: &str) -> std::fmt::Result {
+        write!(f, "{prefix}{self}")
+    }
+}
+
+impl<A: Display> Annotation for Option<Type<A>> {
+    fn fmt_annotation(&self, f: &mut Formatter<'_>, prefix: &str) -> std::fmt::Result {
+        match self {
+            Some(typ) => typ.fmt_annotation(f, prefix),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<A: Display, Typ: Annotation> Display for Param<A, Typ> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Parameters that are passed by reference are always mutable.
+        let mutable = self.mutable && !self.by_ref;
+        write!(f, "{}{}", if mutable { "mut " } else { "" }, self.sym)?;
+        self.typ
+            .fmt_annotation(f, if self.by_ref { ": &mut " } else { ": " })
+    }
+}
+
+impl<IdentVars: Display, IdentFields: Display, Expr: Display, Typ: Annotation, Lit: Display> Display
+    for Def<IdentVars, IdentFields, Expr, Typ, Lit>
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -14,21 +45,37 @@ impl<IdentVars: Display, IdentFields: Display, Expr: Display> Display
                 params,
                 typ,
                 bdy,
+                inline,
             } => {
-                writeln!(f, "fn {sym}({}) -> {typ} {{", params.iter().format(", "))?;
+                match inline {
+                    Inline::Auto => {}
+                    Inline::Always => writeln!(f, "#[inline]")?,
+                    Inline::Never => writeln!(f, "#[inline(never)]")?,
+                }
+                write!(f, "fn {sym}({})", params.iter().format(", "))?;
+                typ.fmt_annotation(f, " -> ")?;
+                writeln!(f, " {{")?;
                 writeln!(indented(f), "{bdy}")?;
                 writeln!(f, "}}")?;
                 Ok(())
             }
             Def::TypeDef { sym, def } => match def {
-                TypeDef::Struct { fields } => {
+                TypeDef::Struct { fields, defaults } => {
                     writeln!(f, "struct {sym} {{")?;
                     writeln!(
                         indented(f),
                         "{}",
                         fields
                             .iter()
-                            .map(|(sym, bnd)| format!("{sym}: {bnd},"))
+                            .map(|(sym, bnd)| {
+                                let default = defaults
+                                    .iter()
+                                    .find(|(default_sym, _)| {
+                                        default_sym.to_string() == sym.to_string()
+                                    })
+                                    .map_or(String::new(), |(_, val)| format!(" = {val}"));
+                                format!("{sym}: {bnd}{default},")
+                            })
                             .format("\n")
                     )?;
                     writeln!(f, "}}")
@@ -66,14 +113,18 @@ impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
                 bnd,
                 bdy,
             } => {
-                writeln!(
+                write!(
                     f,
-                    "let {}{sym}{} = {bnd};",
+                    "let {}{sym}{}",
                     if *mutable { "mut " } else { "" },
                     typ.as_ref()
                         .map(|typ| format!(": {typ}"))
                         .unwrap_or("".to_string())
                 )?;
+                if let Some(bnd) = bnd {
+                    write!(f, " = {bnd}")?;
+                }
+                writeln!(f, ";")?;
                 write!(f, "{bdy}")
             }
             Expr::If { cnd, thn, els } => {
@@ -86,16 +137,27 @@ impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
             Expr::Apply { fun, args } => {
                 write!(f, "{fun}({})", args.iter().format(", "))
             }
-            Expr::Loop { bdy } => {
+            Expr::Loop { lbl, bdy } => {
+                if let Some(lbl) = lbl {
+                    write!(f, "{lbl}: ")?;
+                }
                 writeln!(f, "loop {{")?;
                 writeln!(indented(f), "{bdy}")?;
                 write!(f, "}}")
             }
-            Expr::Break { bdy } => {
-                write!(f, "break {bdy}")
+            Expr::Break { lbl, bdy } => {
+                write!(f, "break ")?;
+                if let Some(lbl) = lbl {
+                    write!(f, "{lbl} ")?;
+                }
+                write!(f, "{bdy}")
             }
-            Expr::Continue => {
-                write!(f, "continue")
+            Expr::Continue { lbl } => {
+                write!(f, "continue")?;
+                if let Some(lbl) = lbl {
+                    write!(f, " {lbl}")?;
+                }
+                Ok(())
             }
             Expr::Return { bdy } => {
                 write!(f, "return {bdy}")
@@ -107,7 +169,8 @@ impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
             Expr::Assign { sym, bnd } => {
                 write!(f, "{sym} = {bnd}")
             }
-            Expr::Struct { sym, fields } => {
+            Expr::RefMut { sym } => write!(f, "&mut {sym}"),
+            Expr::Struct { sym, fields, base } => {
                 writeln!(f, "{sym} {{")?;
                 writeln!(
                     indented(f),
@@ -117,6 +180,9 @@ impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
                         .map(|(sym, bnd)| format!("{sym}: {bnd},"))
                         .format("\n")
                 )?;
+                if let Some(base) = base {
+                    writeln!(indented(f), "..{base}")?;
+                }
                 write!(f, "}}")
             }
             Expr::AccessField { strct, field } => {
@@ -124,7 +190,70 @@ impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
             }
             Expr::Variant { .. } => todo!(),
             Expr::Switch { .. } => todo!(),
-            Expr::Asm { .. } => todo!(),
+            Expr::Match { scrut, arms } => {
+                writeln!(f, "match {scrut} {{")?;
+                for arm in arms {
+                    writeln!(indented(f), "{arm},")?;
+                }
+                write!(f, "}}")
+            }
+            Expr::Asm {
+                operands,
+                typ,
+                instrs,
+            } => {
+                writeln!(
+                    f,
+                    "asm({}) -> {typ} {{",
+                    operands
+                        .iter()
+                        .map(|(sym, typ, bnd)| format!("{sym}: {typ} = {bnd}"))
+                        .format(", ")
+                )?;
+                for instr in instrs {
+                    writeln!(indented(f), "{instr}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
+    for MatchArm<IdentVars, IdentFields, Lit, M>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.pat)?;
+        if let Some(guard) = &self.guard {
+            write!(f, " if {guard}")?;
+        }
+        write!(f, " => {}", self.bdy)
+    }
+}
+
+impl<IdentVars: Display, IdentFields: Display, Lit: Display, M> Display
+    for Pattern<IdentVars, IdentFields, Lit, M>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding { sym } => write!(f, "{sym}"),
+            Pattern::Lit { val } => write!(f, "{val}"),
+            Pattern::Range { lo, hi, inclusive } => {
+                write!(f, "{lo}{}{hi}", if *inclusive { "..=" } else { ".." })
+            }
+            Pattern::Struct { sym, fields, rest } => {
+                let fields = fields.iter().map(|(sym, pat)| format!("{sym}: {pat}"));
+                if *rest {
+                    write!(
+                        f,
+                        "{sym} {{ {} }}",
+                        fields.chain(["..".to_string()]).format(", ")
+                    )
+                } else {
+                    write!(f, "{sym} {{ {} }}", fields.format(", "))
+                }
+            }
         }
     }
 }
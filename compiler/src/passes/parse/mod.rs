@@ -10,8 +10,8 @@ pub mod parse;
 mod tests;
 pub mod types;
 
-use crate::passes::select::{Instr, VarArg};
-use crate::passes::validate::MetaConstrained;
+use crate::passes::select::{AsmItem, Instr, VarArg};
+use crate::passes::validate::{MetaConstrained, TLit};
 use crate::utils::gen_sym::UniqueSym;
 use derive_more::Display;
 use functor_derive::Functor;
@@ -30,35 +30,53 @@ pub struct PrgParsed<'p> {
 }
 
 /// A definition.
-#[derive(Debug)]
-pub enum Def<IdentVars, IdentFields, Expr> {
+///
+/// The types of function parameters and return values are `Typ`, which is an optional type
+/// annotation before validation and a resolved [`Type`] afterwards. Default values of struct fields
+/// are literals of type `Lit`.
+#[derive(Clone, Debug)]
+pub enum Def<IdentVars, IdentFields, Expr, Typ = Type<IdentVars>, Lit = TLit> {
     /// A function definition.
     Fn {
         /// Symbol representing the function.
         sym: IdentVars,
         /// Parameters of the function.
-        params: Vec<Param<IdentVars>>,
+        params: Vec<Param<IdentVars, Typ>>,
         /// Return type of the function.
-        typ: Type<IdentVars>,
+        typ: Typ,
         /// Function body.
         bdy: Expr,
+        /// Whether the function should be inlined at its call sites.
+        inline: Inline,
     },
     TypeDef {
         sym: IdentVars,
-        def: TypeDef<IdentVars, IdentFields>,
+        def: TypeDef<IdentVars, IdentFields, Lit>,
     },
 }
 
-pub type DefParsed<'p> = Def<Spanned<&'p str>, Spanned<&'p str>, Spanned<ExprParsed<'p>>>;
+pub type DefParsed<'p> = Def<
+    Spanned<&'p str>,
+    Spanned<&'p str>,
+    Spanned<ExprParsed<'p>>,
+    Option<Type<Spanned<&'p str>>>,
+    Lit<'p>,
+>;
+pub type ParamParsed<'p> = Param<Spanned<&'p str>, Option<Type<Spanned<&'p str>>>>;
 pub type ExprParsed<'p> = Expr<Spanned<&'p str>, Spanned<&'p str>, Lit<'p>, Span>;
+pub type PatternParsed<'p> = Pattern<Spanned<&'p str>, Spanned<&'p str>, Lit<'p>, Span>;
+pub type MatchArmParsed<'p> = MatchArm<Spanned<&'p str>, Spanned<&'p str>, Lit<'p>, Span>;
 pub type InstrParsed<'p> = Instr<VarArg<Spanned<&'p str>>, Spanned<&'p str>>;
+pub type AsmItemParsed<'p> = AsmItem<VarArg<Spanned<&'p str>>, Spanned<&'p str>>;
 
 #[derive(Clone, Debug)]
-pub enum TypeDef<IdentVars, IdentFields> {
+pub enum TypeDef<IdentVars, IdentFields, Lit = TLit> {
     /// A struct definition.
     Struct {
         /// Fields of the struct, consisting of field symbols and their types.
         fields: Vec<(IdentFields, Type<IdentVars>)>,
+        /// Default values of fields, which may be left out when the struct is constructed.
+        defaults: Vec<(IdentFields, Lit)>,
     },
     /// An enum definition.
     Enum {
@@ -67,7 +85,7 @@ pub enum TypeDef<IdentVars, IdentFields> {
     },
 }
 
-impl<IdentVars, IdentFields, Expr> Def<IdentVars, IdentFields, Expr> {
+impl<IdentVars, IdentFields, Expr, Typ, Lit> Def<IdentVars, IdentFields, Expr, Typ, Lit> {
     /// Returns the symbol representing the definition.
     pub fn sym(&self) -> &IdentVars {
         match self {
@@ -77,27 +95,40 @@ impl<IdentVars, IdentFields, Expr> Def<IdentVars, IdentFields, Expr> {
     }
 }
 
+/// An inlining attribute of a function, such as `#[inline]` or `#[inline(never)]`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Inline {
+    /// Leave the decision to the heuristic of `inline`.
+    #[default]
+    Auto,
+    /// Inline the function wherever possible.
+    Always,
+    /// Never inline the function.
+    Never,
+}
+
 /// A parameter used in functions.
 ///
 /// Parameters are generic and can use symbols that are either `&str` or
 /// [`UniqueSym`](UniqueSym) for all passes after uniquify.
-#[derive(Clone, Display, Debug)]
-#[display(bound = "A: Display")]
-#[display(fmt = "{}{sym}: {typ}", r#"if *mutable { "mut " } else { "" }"#)]
-pub struct Param<A> {
+#[derive(Clone, Debug)]
+pub struct Param<A, Typ = Type<A>> {
     /// Symbol representing the parameter.
     pub sym: A,
     /// The type of the parameter. See [`Type`]
-    pub typ: Type<A>,
+    pub typ: Typ,
     /// Indicates whether the parameter is mutable (true) or immutable (false).
     pub mutable: bool,
+    /// Indicates whether the parameter is passed by reference (`&mut`), in which case assignments
+    /// to it are visible to the caller after the call returns.
+    pub by_ref: bool,
 }
 
 /// An expression.
 ///
 /// Expressions are generic and can use symbols that are either `&str` or
 /// [`UniqueSym`](UniqueSym) for all passes after uniquify.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Expr<IdentVars: Display, IdentFields, Lit, M> {
     /// A literal value. See [`Lit`].
     Lit {
@@ -123,14 +154,16 @@ pub enum Expr<IdentVars: Display, IdentFields, Lit, M> {
     /// The `Let` expression introduces a new variable with the symbol `sym` to which it binds
     /// the `bnd` expression. It then evaluates the `bdy` expression using this new binding.
     /// The variable can be immutable or mutable depending on the presence of the `mut` keyword.
+    /// If `bnd` is omitted, the variable must be assigned on every path before it is read.
     Let {
         /// Symbol representing the newly introduced variable.
         sym: IdentVars,
         /// Indicates whether the variable is mutable (true) or immutable (false).
         mutable: bool,
         typ: Option<Type<IdentVars>>,
-        /// The expression to which the variable is bound.
-        bnd: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
+        /// The expression to which the variable is bound, if it is initialized immediately.
+        #[allow(clippy::type_complexity)]
+        bnd: Option<Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>>,
         /// The expression that is evaluated using the new variable binding.
         bdy: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
     },
@@ -161,22 +194,31 @@ pub enum Expr<IdentVars: Display, IdentFields, Lit, M> {
     /// The `Loop` expression repeatedly evaluates the `bdy` expression until a `break` or `return`
     /// expression is evaluated.
     Loop {
+        /// The optional label of the loop, which `break` and `continue` can refer to.
+        lbl: Option<IdentVars>,
         /// The expression that defines the body of the loop.
         bdy: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
     },
     /// A break statement.
     ///
     /// The `Break` expression affects the control flow of a loop construct. It exits the
-    /// current loop and returns the value of the `bdy` expression from the loop upon termination.
+    /// labeled loop, or the innermost loop if there is no label, and returns the value of the
+    /// `bdy` expression from that loop upon termination.
     Break {
+        /// The label of the loop to exit.
+        lbl: Option<IdentVars>,
         /// The expression to be evaluated and returned from the loop.
         bdy: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
     },
     /// A continue statement.
     ///
     /// The `Continue` expression affects the control flow of a loop construct. It skips to
-    /// the next iteration of the loop. It does not return a value.
-    Continue,
+    /// the next iteration of the labeled loop, or the innermost loop if there is no label. It
+    /// does not return a value.
+    Continue {
+        /// The label of the loop to continue.
+        lbl: Option<IdentVars>,
+    },
     /// A return statement.
     ///
     /// The `Return` expression exits the current function and returns the value of the `bdy` expression.
@@ -206,13 +248,20 @@ pub enum Expr<IdentVars: Display, IdentFields, Lit, M> {
         /// The expression whose result is assigned to the variable.
         bnd: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
     },
+    /// A mutable reference to a variable.
+    ///
+    /// References can only be passed as arguments for parameters that are passed by reference.
+    RefMut { sym: IdentVars },
     /// An instance of a struct.
     ///
-    /// todo: documentation
+    /// Fields that are left out are copied from `base` if it is present, and otherwise take their
+    /// default values.
     Struct {
         sym: IdentVars,
         #[allow(clippy::type_complexity)]
         fields: Vec<(IdentFields, Meta<M, Expr<IdentVars, IdentFields, Lit, M>>)>,
+        #[allow(clippy::type_complexity)]
+        base: Option<Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>>,
     },
     /// A variant of an enum.
     ///
@@ -236,17 +285,78 @@ pub enum Expr<IdentVars: Display, IdentFields, Lit, M> {
         enm: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
         arms: Vec<SwitchArm<IdentVars, IdentFields, Lit, M>>,
     },
+    /// A match expression.
+    ///
+    /// The `Match` expression evaluates `scrut` once and tries the `arms` in order. The first arm
+    /// whose pattern matches the value, and whose guard evaluates to *true*, is taken. The value of
+    /// its body is the value of the whole expression.
+    Match {
+        scrut: Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
+        arms: Vec<MatchArm<IdentVars, IdentFields, Lit, M>>,
+    },
+    /// An inline assembly block.
+    ///
+    /// The `operands` are evaluated before the block and can be referred to from `instrs` as
+    /// `{sym}`. The instructions in `instrs` are inserted verbatim. Labels declared in the block are
+    /// only visible to jumps within that same block. The result of the block, of type `typ`, is the
+    /// value left in `%RAX`.
     Asm {
-        instrs: Vec<Instr<VarArg<IdentVars>, IdentVars>>,
+        operands: Vec<AsmOperand<IdentVars, IdentFields, Lit, M>>,
+        typ: Type<IdentVars>,
+        instrs: Vec<AsmItem<VarArg<IdentVars>, IdentVars>>,
     },
 }
 
+pub type AsmOperand<IdentVars, IdentFields, Lit, M> = (
+    IdentVars,
+    Type<IdentVars>,
+    Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
+);
+
 pub type SwitchArm<IdentVars, IdentFields, Lit, M> = (
     IdentVars,
     IdentFields,
     Box<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
 );
 
+/// An arm of a `match` expression.
+#[derive(Clone, Debug)]
+pub struct MatchArm<IdentVars: Display, IdentFields, Lit, M> {
+    /// The pattern that the value is matched against.
+    pub pat: Meta<M, Pattern<IdentVars, IdentFields, Lit, M>>,
+    /// An optional condition that must hold for the arm to be taken. The bindings of the pattern
+    /// are in scope.
+    pub guard: Option<Meta<M, Expr<IdentVars, IdentFields, Lit, M>>>,
+    /// The expression that is evaluated when the arm is taken. The bindings of the pattern are in
+    /// scope.
+    pub bdy: Meta<M, Expr<IdentVars, IdentFields, Lit, M>>,
+}
+
+/// A pattern in an arm of a `match` expression.
+#[derive(Clone, Debug)]
+pub enum Pattern<IdentVars: Display, IdentFields, Lit, M> {
+    /// Matches any value.
+    Wildcard,
+    /// Matches any value and binds it to `sym`.
+    Binding { sym: IdentVars },
+    /// Matches values equal to the literal.
+    Lit { val: Lit },
+    /// Matches integers from `lo` up to `hi`. The upper bound is part of the range if `inclusive`
+    /// is set.
+    Range { lo: Lit, hi: Lit, inclusive: bool },
+    /// Matches structs whose fields match the patterns in `fields`. Fields that are left out must be
+    /// explicitly ignored with `..`, in which case `rest` is set.
+    Struct {
+        sym: IdentVars,
+        #[allow(clippy::type_complexity)]
+        fields: Vec<(
+            IdentFields,
+            Meta<M, Pattern<IdentVars, IdentFields, Lit, M>>,
+        )>,
+        rest: bool,
+    },
+}
+
 #[derive(Clone, Display, Debug)]
 #[display(bound = "B: Display")]
 #[display(fmt = "{inner}")]
@@ -273,7 +383,7 @@ impl<M, B> Functor<B> for Meta<M, B> {
 pub type Span = (usize, usize);
 
 /// A unary operation.
-#[derive(Display, Debug)]
+#[derive(Display, Debug, Copy, Clone, PartialEq)]
 pub enum UnaryOp {
     /// Integer negation.
     #[display(fmt = "-")]
@@ -284,7 +394,7 @@ pub enum UnaryOp {
 }
 
 /// A primitive operation.
-#[derive(Display, Debug)]
+#[derive(Display, Debug, Copy, Clone, PartialEq)]
 pub enum BinaryOp {
     /// Integer addition.
     #[display(fmt = "+")]
@@ -316,7 +426,7 @@ pub enum BinaryOp {
     /// Greater Than or Equal To comparison.
     #[display(fmt = ">=")]
     GE,
-    /// Equality comparison. Operates on `Int` and `Bool`.
+    /// Equality comparison. Operates on `Int`, `Bool` and structs.
     #[display(fmt = "==")]
     EQ,
     /// Less Than or Equal To comparison.
@@ -325,13 +435,13 @@ pub enum BinaryOp {
     /// Less Than comparison.
     #[display(fmt = "<")]
     LT,
-    /// Inequality comparison. Operates on `Int` and `Bool`.
+    /// Inequality comparison. Operates on `Int`, `Bool` and structs.
     #[display(fmt = "!=")]
     NE,
 }
 
 /// A literal value.
-#[derive(Display)]
+#[derive(Clone, Display)]
 pub enum Lit<'p> {
     /// Integer literal, representing a signed 64-bit number.
     #[display(fmt = "{val}")]
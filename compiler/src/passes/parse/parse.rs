@@ -30,7 +30,7 @@ pub enum PrettyParseError {
     },
 }
 
-pub fn parse_program(src: &str) -> Result<PrgParsed, PrettyParseError> {
+pub fn parse_program(src: &str) -> Result<PrgParsed<'_>, PrettyParseError> {
     ProgramParser::new().parse(src).map_err(From::from)
 }
 
@@ -4,7 +4,7 @@ use derive_name::VariantName;
 use test_each_file::test_each_file;
 
 fn parse([test]: [&str; 1]) {
-    let (_, _, _, expected_error) = split_test(test);
+    let (_, _, _, expected_error, _) = split_test(test);
 
     let result = parse_program(test);
 
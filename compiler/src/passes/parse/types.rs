@@ -2,7 +2,7 @@ use derive_more::Display;
 use itertools::Itertools;
 use std::fmt::Display;
 
-#[derive(Debug, Clone, Display)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Display)]
 #[display(bound = "A: Display")]
 pub enum Type<A> {
     #[display(fmt = "I64")]
@@ -7,12 +7,12 @@ use crate::passes::select::std_lib::Std;
 use crate::passes::select::{Block, X86Selected};
 use crate::utils::gen_sym::UniqueSym;
 use functor_derive::Functor;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct X86Patched<'p> {
     pub blocks: HashMap<UniqueSym<'p>, Block<'p, Arg>>,
     pub entry: UniqueSym<'p>,
-    pub stack_space: usize,
+    pub fns: HashSet<UniqueSym<'p>>,
     pub std: Std<'p>,
 }
 
@@ -21,6 +21,7 @@ impl<'p> From<X86Patched<'p>> for X86Selected<'p> {
         X86Selected {
             blocks: value.blocks.fmap(|v| v.fmap(Into::into)),
             entry: value.entry,
+            fns: value.fns,
             std: value.std,
         }
     }
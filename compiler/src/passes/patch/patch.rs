@@ -1,8 +1,10 @@
 use crate::passes::assign::{Arg, X86Assigned};
 use crate::passes::patch::X86Patched;
-use crate::passes::select::{Block, Instr};
+use crate::passes::select::{Block, Instr, Reg};
 use crate::utils::gen_sym::UniqueSym;
-use crate::{addq, movq, reg, subq};
+use crate::{
+    addq, andq, cmpq, imulq, jmp_table, leaq, load_lbl, movq, orq, pushq, subq, testq, xorq,
+};
 
 impl<'p> X86Assigned<'p> {
     #[must_use]
@@ -14,7 +16,7 @@ impl<'p> X86Assigned<'p> {
                 .map(|(lbl, block)| (lbl, patch_block(block)))
                 .collect(),
             entry: self.entry,
-            stack_space: self.stack_space,
+            fns: self.fns,
             std: self.std,
         }
     }
@@ -25,27 +27,118 @@ fn patch_block(block: Block<'_, Arg>) -> Block<'_, Arg> {
         instrs: block
             .instrs
             .into_iter()
-            .flat_map(patch_instr)
+            .flat_map(|instr| patch_instr(instr, Reg::RAX))
             .collect::<Vec<_>>(),
     }
 }
 
-fn patch_instr(instr: Instr<Arg, UniqueSym<'_>>) -> Vec<Instr<Arg, UniqueSym<'_>>> {
+/// Rewrites the instruction into instructions that can be encoded, using `scratch` for
+/// intermediate values. Instructions that can already be encoded are left as they are.
+pub(crate) fn patch_instr(
+    instr: Instr<Arg, UniqueSym<'_>>,
+    scratch: Reg,
+) -> Vec<Instr<Arg, UniqueSym<'_>>> {
+    let tmp = Arg::Reg { reg: scratch };
     match instr {
-        Instr::Addq { src, dst } => patch_args(src, dst, |src, dst| addq!(src, dst)),
-        Instr::Subq { src, dst } => patch_args(src, dst, |src, dst| subq!(src, dst)),
-        Instr::Movq { src, dst } => patch_args(src, dst, |src, dst| movq!(src, dst)),
+        Instr::Addq { src, dst } => patch_args(src, dst, &tmp, |src, dst| addq!(src, dst)),
+        Instr::Subq { src, dst } => patch_args(src, dst, &tmp, |src, dst| subq!(src, dst)),
+        // Moves between variables that were assigned the same location do nothing.
+        Instr::Movq { src, dst } if src == dst => vec![],
+        Instr::Movq {
+            src,
+            dst: dst @ Arg::Reg { .. },
+        } => vec![movq!(src, dst)],
+        Instr::Movq { src, dst } => patch_args(src, dst, &tmp, |src, dst| movq!(src, dst)),
+        Instr::Testq { src, dst } => patch_args(src, dst, &tmp, |src, dst| testq!(src, dst)),
+        Instr::Cmpq { src, dst } => patch_args(src, dst, &tmp, |src, dst| cmpq!(src, dst)),
+        Instr::Andq { src, dst } => patch_args(src, dst, &tmp, |src, dst| andq!(src, dst)),
+        Instr::Orq { src, dst } => patch_args(src, dst, &tmp, |src, dst| orq!(src, dst)),
+        Instr::Xorq { src, dst } => patch_args(src, dst, &tmp, |src, dst| xorq!(src, dst)),
+        Instr::Pushq {
+            src: src @ Arg::Imm { val },
+        } if i32::try_from(val).is_err() => vec![movq!(src, tmp.clone()), pushq!(tmp.clone())],
+        Instr::Imulq {
+            src,
+            dst: dst @ Arg::Deref { .. },
+        } => vec![
+            movq!(dst.clone(), tmp.clone()),
+            imulq!(src, tmp.clone()),
+            movq!(tmp.clone(), dst),
+        ],
+        Instr::Leaq {
+            src,
+            dst: dst @ Arg::Deref { .. },
+        } => vec![leaq!(src, tmp.clone()), movq!(tmp.clone(), dst)],
+        Instr::LoadLbl {
+            sym,
+            dst: dst @ Arg::Deref { .. },
+        } => vec![load_lbl!(sym, tmp.clone()), movq!(tmp.clone(), dst)],
+        // The index of a jump table is encoded in the SIB byte, so it has to be a register.
+        Instr::JmpTable {
+            idx: idx @ Arg::Deref { .. },
+            lbls,
+        } => vec![movq!(idx, tmp.clone()), jmp_table!(tmp.clone(), lbls)],
         _ => vec![instr],
     }
 }
 
+/// Returns whether `patch_instr` rewrites the instruction with a scratch register.
+pub(crate) fn needs_scratch(instr: &Instr<Arg, UniqueSym<'_>>) -> bool {
+    match instr {
+        Instr::Movq { src, dst } if src == dst => false,
+        Instr::Movq {
+            dst: Arg::Reg { .. },
+            ..
+        } => false,
+        Instr::Addq { src, dst }
+        | Instr::Subq { src, dst }
+        | Instr::Movq { src, dst }
+        | Instr::Testq { src, dst }
+        | Instr::Cmpq { src, dst }
+        | Instr::Andq { src, dst }
+        | Instr::Orq { src, dst }
+        | Instr::Xorq { src, dst } => args_need_scratch(src, dst),
+        Instr::Pushq {
+            src: Arg::Imm { val },
+        } => i32::try_from(*val).is_err(),
+        Instr::Imulq {
+            dst: Arg::Deref { .. },
+            ..
+        }
+        | Instr::Leaq {
+            dst: Arg::Deref { .. },
+            ..
+        }
+        | Instr::LoadLbl {
+            dst: Arg::Deref { .. },
+            ..
+        }
+        | Instr::JmpTable {
+            idx: Arg::Deref { .. },
+            ..
+        } => true,
+        _ => false,
+    }
+}
+
 fn patch_args<'p>(
     src: Arg,
     dst: Arg,
+    tmp: &Arg,
     op: fn(Arg, Arg) -> Instr<Arg, UniqueSym<'p>>,
 ) -> Vec<Instr<Arg, UniqueSym<'p>>> {
-    match (&src, &dst) {
-        (Arg::Deref { .. }, Arg::Deref { .. }) => vec![movq!(src, reg!(RAX)), op(reg!(RAX), dst)],
-        _ => vec![op(src, dst)],
+    if args_need_scratch(&src, &dst) {
+        vec![movq!(src, tmp.clone()), op(tmp.clone(), dst)]
+    } else {
+        vec![op(src, dst)]
+    }
+}
+
+fn args_need_scratch(src: &Arg, dst: &Arg) -> bool {
+    match (src, dst) {
+        (Arg::Deref { .. }, Arg::Deref { .. }) => true,
+        // Only `movq` into a register can encode an immediate that does not fit in 32 bits.
+        (Arg::Imm { val }, _) => i32::try_from(*val).is_err(),
+        _ => false,
     }
 }
@@ -3,22 +3,27 @@ use crate::passes::parse::parse::parse_program;
 use crate::passes::select::X86Selected;
 use crate::utils::gen_sym::gen_sym;
 use crate::utils::split_test::split_test;
-use crate::{block, callq_direct, movq, reg};
+use crate::{block, callq_direct, deref, movq, reg};
 use test_each_file::test_each_file;
 
 fn patch([test]: [&str; 1]) {
-    let (input, expected_output, expected_return, _) = split_test(test);
+    let (input, expected_output, expected_return, _, args) = split_test(test);
 
     let mut program: X86Selected = parse_program(test)
         .unwrap()
         .validate()
         .unwrap()
         .reveal()
+        .decide()
+        .inline()
         .atomize()
+        .fold()
         .explicate()
+        .prune()
         .eliminate()
         .select()
         .assign()
+        .unwrap()
         .patch()
         .into();
 
@@ -27,14 +32,16 @@ fn patch([test]: [&str; 1]) {
     program.blocks.insert(
         new_entry,
         block!(
-            callq_direct!(program.entry, 0),
+            // The stack pointer points to `argc`, which is passed as the `Args` of `main`.
+            movq!(deref!(RSP, 0), reg!(RAX)),
+            callq_direct!(program.entry, 1),
             movq!(reg!(RAX), reg!(RDI)),
             callq_direct!(program.std["exit"], 1)
         ),
     );
     program.entry = new_entry;
 
-    let mut io = TestIO::new(input);
+    let mut io = TestIO::from_bytes(input).with_args(args);
     let result = program.interpret(&mut io);
 
     assert_eq!(result, expected_return.into(), "Incorrect program result.");
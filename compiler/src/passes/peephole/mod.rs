@@ -0,0 +1,51 @@
+pub mod peephole;
+#[cfg(test)]
+mod tests;
+
+use crate::jmp;
+use crate::passes::assign::Arg;
+use crate::passes::select::std_lib::Std;
+use crate::passes::select::{Block, Instr, X86Selected};
+use crate::utils::gen_sym::UniqueSym;
+use functor_derive::Functor;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+
+pub struct X86Peepholed<'p> {
+    pub blocks: HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+    /// The order in which the blocks are laid out. A block that does not end in a jump or return
+    /// falls through to the next block.
+    pub order: Vec<UniqueSym<'p>>,
+    pub entry: UniqueSym<'p>,
+    pub fns: HashSet<UniqueSym<'p>>,
+    pub std: Std<'p>,
+}
+
+impl<'p> From<X86Peepholed<'p>> for X86Selected<'p> {
+    fn from(value: X86Peepholed<'p>) -> Self {
+        X86Selected {
+            blocks: explicit_jumps(value.blocks, &value.order).fmap(|v| v.fmap(Into::into)),
+            entry: value.entry,
+            fns: value.fns,
+            std: value.std,
+        }
+    }
+}
+
+/// Adds a jump to the next block in `order` to every block that falls through to it, such that
+/// the blocks can be run without knowing their layout.
+pub fn explicit_jumps<'p>(
+    mut blocks: HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+    order: &[UniqueSym<'p>],
+) -> HashMap<UniqueSym<'p>, Block<'p, Arg>> {
+    for (sym, next) in order.iter().tuple_windows() {
+        let block = blocks.get_mut(sym).unwrap();
+        if !matches!(
+            block.instrs.last(),
+            Some(Instr::Jmp { .. } | Instr::JmpTable { .. } | Instr::Retq)
+        ) {
+            block.instrs.push(jmp!(*next));
+        }
+    }
+    blocks
+}
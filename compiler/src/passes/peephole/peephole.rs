@@ -0,0 +1,334 @@
+use crate::passes::assign::Arg;
+use crate::passes::patch::X86Patched;
+use crate::passes::peephole::X86Peepholed;
+use crate::passes::select::{handle_instr, Block, Cnd, Instr, ReadWriteOp, Reg, VarArg};
+use crate::utils::gen_sym::UniqueSym;
+use crate::{decq, incq, jcc, xorq};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+
+impl<'p> X86Patched<'p> {
+    /// Rewrites short sequences of instructions within blocks into cheaper ones, and lays out the
+    /// blocks such that jumps to the next block can be left out.
+    #[must_use]
+    pub fn peephole(self) -> X86Peepholed<'p> {
+        let mut blocks = self.blocks;
+        let live = live_regs(&blocks);
+        for block in blocks.values_mut() {
+            *block = fold_branches(block, &live);
+        }
+
+        let reads_flags = blocks
+            .iter()
+            .filter(|(_, block)| flags_live(&block.instrs, |_| true))
+            .map(|(sym, _)| *sym)
+            .collect::<HashSet<_>>();
+        for block in blocks.values_mut() {
+            rewrite_instrs(block, &reads_flags);
+        }
+
+        let order = layout(&mut blocks, &self.fns);
+
+        X86Peepholed {
+            blocks,
+            order,
+            entry: self.entry,
+            fns: self.fns,
+            std: self.std,
+        }
+    }
+}
+
+/// The result of a comparison that was stored with `setcc`.
+struct Stored {
+    cnd: Cnd,
+    /// The locations that hold the result.
+    locations: Vec<Arg>,
+    /// The positions of the instructions that store and copy the result.
+    instrs: Vec<usize>,
+    /// The locations that those instructions write to.
+    written: Vec<Arg>,
+}
+
+/// Branches on a comparison whose result was stored with `setcc` and compared to a constant, such
+/// as `if a < b` on a variable, directly on the flags of the original comparison. If the result is
+/// not used after the jump, the instructions that store it are removed as well.
+fn fold_branches<'p>(
+    block: &Block<'p, Arg>,
+    live: &HashMap<UniqueSym<'p>, HashSet<Reg>>,
+) -> Block<'p, Arg> {
+    let mut instrs = Vec::with_capacity(block.instrs.len());
+    // Only moves are allowed after the `setcc`, so the flags of the comparison are still intact.
+    let mut stored: Option<Stored> = None;
+
+    let mut iter = block.instrs.iter().enumerate().peekable();
+    while let Some((i, instr)) = iter.next() {
+        match (instr, &mut stored) {
+            (Instr::Setcc { cnd }, _) => {
+                // Only the lowest byte is set, so the rest of the register should be cleared.
+                let cleared = matches!(
+                    instrs.last(),
+                    Some(Instr::Movq {
+                        src: Arg::Imm { val: 0 },
+                        dst: Arg::Reg { reg: Reg::RAX },
+                    })
+                );
+                stored = cleared.then(|| Stored {
+                    cnd: *cnd,
+                    locations: vec![Arg::Reg { reg: Reg::RAX }],
+                    instrs: vec![instrs.len() - 1, instrs.len()],
+                    written: vec![Arg::Reg { reg: Reg::RAX }],
+                });
+            }
+            (Instr::Movq { src, dst }, Some(stored)) => {
+                let copied = stored.locations.contains(src);
+                stored
+                    .locations
+                    .retain(|location| !overwrites(dst, location));
+                if copied {
+                    stored.locations.push(dst.clone());
+                    stored.instrs.push(instrs.len());
+                    stored.written.push(dst.clone());
+                }
+            }
+            (
+                Instr::Cmpq {
+                    src: Arg::Imm { val: val @ (0 | 1) },
+                    dst,
+                },
+                Some(result),
+            ) if result.locations.contains(dst) => {
+                if let Some((
+                    _,
+                    Instr::Jcc {
+                        lbl,
+                        cnd: cmp @ (Cnd::EQ | Cnd::NE),
+                    },
+                )) = iter.peek()
+                {
+                    // The jump is taken if the result equals 1, or does not equal 0.
+                    let taken_if_true = (*val == 1) == (*cmp == Cnd::EQ);
+                    let cnd = if taken_if_true {
+                        result.cnd
+                    } else {
+                        negate(result.cnd)
+                    };
+
+                    // Memory is not tracked, so only results in registers can be removed.
+                    let live_after = live_before(&block.instrs[i + 1..], live);
+                    let unused = result.written.iter().all(
+                        |location| matches!(location, Arg::Reg { reg } if !live_after.contains(reg)),
+                    );
+                    if unused {
+                        for &i in result.instrs.iter().rev() {
+                            instrs.remove(i);
+                        }
+                        stored = None;
+                    }
+
+                    instrs.push(jcc!(*lbl, cnd));
+                    iter.next();
+                    continue;
+                }
+                stored = None;
+            }
+            _ => stored = None,
+        }
+        instrs.push(instr.clone());
+    }
+
+    Block { instrs }
+}
+
+/// Returns the registers that each block may read before writing to them.
+fn live_regs<'p>(
+    blocks: &HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+) -> HashMap<UniqueSym<'p>, HashSet<Reg>> {
+    let mut live = blocks
+        .keys()
+        .map(|sym| (*sym, HashSet::new()))
+        .collect::<HashMap<_, _>>();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (sym, block) in blocks {
+            let regs = live_before(&block.instrs, &live);
+            if regs != live[sym] {
+                live.insert(*sym, regs);
+                changed = true;
+            }
+        }
+    }
+    live
+}
+
+/// Returns the registers that the instructions may read before writing to them. `live` contains
+/// the registers that are live at the start of each block that is jumped to.
+fn live_before<'p>(
+    instrs: &[Instr<Arg, UniqueSym<'p>>],
+    live: &HashMap<UniqueSym<'p>, HashSet<Reg>>,
+) -> HashSet<Reg> {
+    let mut regs = HashSet::new();
+    for instr in instrs.iter().rev() {
+        match instr {
+            Instr::Jmp { lbl } => regs.clone_from(&live[lbl]),
+            Instr::Jcc { lbl, .. } => regs.extend(&live[lbl]),
+            Instr::JmpTable { lbls, .. } => {
+                regs = lbls.iter().flat_map(|lbl| &live[lbl]).copied().collect();
+            }
+            _ => {}
+        }
+
+        let mut reads = Vec::new();
+        handle_instr(&instr.clone().fmap(Into::into), |arg, op| match (arg, op) {
+            (VarArg::Reg { reg }, ReadWriteOp::Write) => {
+                regs.remove(reg);
+            }
+            (VarArg::Reg { reg } | VarArg::Deref { reg, .. }, _) => reads.push(*reg),
+            _ => {}
+        });
+        regs.extend(reads);
+    }
+    regs
+}
+
+/// Returns whether writing to `dst` can change the value at `location`.
+fn overwrites(dst: &Arg, location: &Arg) -> bool {
+    match (dst, location) {
+        // Memory can be reached through different addresses, so any write may change it.
+        (Arg::Deref { .. }, Arg::Deref { .. }) => true,
+        (Arg::Reg { reg }, Arg::Reg { reg: location } | Arg::Deref { reg: location, .. }) => {
+            reg == location
+        }
+        _ => false,
+    }
+}
+
+/// Rewrites single instructions into cheaper ones. Some of these change the flags, so they are
+/// only applied if the flags are not read afterwards.
+fn rewrite_instrs(block: &mut Block<'_, Arg>, reads_flags: &HashSet<UniqueSym<'_>>) {
+    let instrs = std::mem::take(&mut block.instrs);
+    for (i, instr) in instrs.iter().enumerate() {
+        let flags_dead = || !flags_live(&instrs[i + 1..], |lbl| reads_flags.contains(lbl));
+        let instr = match instr {
+            Instr::Movq {
+                src: Arg::Imm { val: 0 },
+                dst: dst @ Arg::Reg { .. },
+            } if flags_dead() => xorq!(dst.clone(), dst.clone()),
+            Instr::Addq {
+                src: Arg::Imm { val: 1 },
+                dst,
+            }
+            | Instr::Subq {
+                src: Arg::Imm { val: -1 },
+                dst,
+            } if flags_dead() => incq!(dst.clone()),
+            Instr::Addq {
+                src: Arg::Imm { val: -1 },
+                dst,
+            }
+            | Instr::Subq {
+                src: Arg::Imm { val: 1 },
+                dst,
+            } if flags_dead() => decq!(dst.clone()),
+            instr => instr.clone(),
+        };
+        block.instrs.push(instr);
+    }
+}
+
+/// Returns whether the flags may be read by the instructions before they are set again.
+/// `reads_flags` tells whether a block that is jumped to may read the flags.
+fn flags_live<'p>(
+    instrs: &[Instr<Arg, UniqueSym<'p>>],
+    reads_flags: impl Fn(&UniqueSym<'p>) -> bool,
+) -> bool {
+    for instr in instrs {
+        match instr {
+            Instr::Jcc { .. } | Instr::Setcc { .. } => return true,
+            Instr::Cmpq { .. }
+            | Instr::Testq { .. }
+            | Instr::Addq { .. }
+            | Instr::Subq { .. }
+            | Instr::Andq { .. }
+            | Instr::Orq { .. }
+            | Instr::Xorq { .. }
+            | Instr::Negq { .. } => return false,
+            // Functions do not preserve the flags for their caller.
+            Instr::CallqDirect { .. }
+            | Instr::CallqIndirect { .. }
+            | Instr::Syscall { .. }
+            | Instr::Retq => return false,
+            Instr::Jmp { lbl } => return reads_flags(lbl),
+            Instr::JmpTable { lbls, .. } => return lbls.iter().any(&reads_flags),
+            _ => {}
+        }
+    }
+    true
+}
+
+/// Orders the blocks into chains, where a block that jumps to a block that has not been placed
+/// yet is followed by it, and its jump is removed. If only the conditional jump of a block goes
+/// to such a block, the condition is negated so that it can be followed instead.
+fn layout<'p>(
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, Arg>>,
+    fns: &HashSet<UniqueSym<'p>>,
+) -> Vec<UniqueSym<'p>> {
+    let starts = fns
+        .iter()
+        .sorted()
+        .chain(blocks.keys().sorted())
+        .copied()
+        .collect::<Vec<_>>();
+
+    let mut order = Vec::new();
+    let mut placed = HashSet::new();
+    for mut sym in starts {
+        while placed.insert(sym) {
+            order.push(sym);
+
+            let instrs = &mut blocks.get_mut(&sym).unwrap().instrs;
+            if let [.., Instr::Jcc { lbl: thn, cnd }, Instr::Jmp { lbl: els }] =
+                instrs.as_mut_slice()
+            {
+                if placed.contains(els) && !placed.contains(thn) {
+                    std::mem::swap(thn, els);
+                    *cnd = negate(*cnd);
+                }
+            }
+            match instrs.last() {
+                Some(Instr::Jmp { lbl }) if !placed.contains(lbl) => {
+                    sym = *lbl;
+                    instrs.pop();
+                }
+                _ => break,
+            }
+        }
+    }
+    order
+}
+
+/// Returns the condition that holds exactly when `cnd` does not.
+fn negate(cnd: Cnd) -> Cnd {
+    match cnd {
+        Cnd::Above => Cnd::BelowOrEqual,
+        Cnd::AboveOrEqual => Cnd::Below,
+        Cnd::Below => Cnd::AboveOrEqual,
+        Cnd::BelowOrEqual => Cnd::Above,
+        Cnd::Carry => Cnd::NotCarry,
+        Cnd::EQ => Cnd::NE,
+        Cnd::GT => Cnd::LE,
+        Cnd::GE => Cnd::LT,
+        Cnd::LT => Cnd::GE,
+        Cnd::LE => Cnd::GT,
+        Cnd::NotCarry => Cnd::Carry,
+        Cnd::NE => Cnd::EQ,
+        Cnd::NotOverflow => Cnd::Overflow,
+        Cnd::NotSign => Cnd::Sign,
+        Cnd::Overflow => Cnd::NotOverflow,
+        Cnd::ParityEven => Cnd::ParityOdd,
+        Cnd::ParityOdd => Cnd::ParityEven,
+        Cnd::Sign => Cnd::NotSign,
+    }
+}
@@ -0,0 +1,124 @@
+use crate::interpreter::TestIO;
+use crate::passes::assign::Arg;
+use crate::passes::parse::parse::parse_program;
+use crate::passes::peephole::X86Peepholed;
+use crate::passes::select::{Instr, X86Selected};
+use crate::utils::gen_sym::{gen_sym, UniqueSym};
+use crate::utils::split_test::split_test;
+use crate::{block, callq_direct, deref, movq, reg};
+use std::collections::HashSet;
+use test_each_file::test_each_file;
+
+fn peephole([test]: [&str; 1]) {
+    let (input, expected_output, expected_return, _, args) = split_test(test);
+
+    let mut program: X86Selected = parse_program(test)
+        .unwrap()
+        .validate()
+        .unwrap()
+        .reveal()
+        .decide()
+        .inline()
+        .atomize()
+        .fold()
+        .explicate()
+        .prune()
+        .eliminate()
+        .select()
+        .assign()
+        .unwrap()
+        .patch()
+        .peephole()
+        .into();
+
+    // Redirect program to exit
+    let new_entry = gen_sym("tmp");
+    program.blocks.insert(
+        new_entry,
+        block!(
+            // The stack pointer points to `argc`, which is passed as the `Args` of `main`.
+            movq!(deref!(RSP, 0), reg!(RAX)),
+            callq_direct!(program.entry, 1),
+            movq!(reg!(RAX), reg!(RDI)),
+            callq_direct!(program.std["exit"], 1)
+        ),
+    );
+    program.entry = new_entry;
+
+    let mut io = TestIO::from_bytes(input).with_args(args);
+    let result = program.interpret(&mut io);
+
+    assert_eq!(result, expected_return.into(), "Incorrect program result.");
+    assert_eq!(io.outputs(), &expected_output, "Incorrect program output.");
+}
+
+test_each_file! { for ["test"] in "./programs/good" as peephole => peephole }
+
+/// Returns the instructions of the blocks that can be reached from the entry of the function.
+fn fn_instrs<'a, 'p>(
+    program: &'a X86Peepholed<'p>,
+    name: &str,
+) -> Vec<&'a Instr<Arg, UniqueSym<'p>>> {
+    let entry = *program.fns.iter().find(|sym| sym.sym == name).unwrap();
+    let mut worklist = vec![entry];
+    let mut visited = HashSet::new();
+    let mut instrs = Vec::new();
+    while let Some(sym) = worklist.pop() {
+        if !visited.insert(sym) {
+            continue;
+        }
+        for instr in &program.blocks[&sym].instrs {
+            match instr {
+                Instr::Jmp { lbl } | Instr::Jcc { lbl, .. } => worklist.push(*lbl),
+                Instr::JmpTable { lbls, .. } => worklist.extend(lbls),
+                _ => {}
+            }
+            instrs.push(instr);
+        }
+    }
+    instrs
+}
+
+#[test]
+fn fold_branch_on_stored_comparison() {
+    let program = parse_program(
+        "#[inline(never)]
+        fn less(a: I64, b: I64) -> I64 {
+            let c = a < b;
+            if c { 1 } else { 2 }
+        }
+
+        fn main() -> I64 {
+            less(read(), read())
+        }",
+    )
+    .unwrap()
+    .validate()
+    .unwrap()
+    .reveal()
+    .decide()
+    .inline()
+    .atomize()
+    .fold()
+    .explicate()
+    .prune()
+    .eliminate()
+    .select()
+    .assign()
+    .unwrap()
+    .patch()
+    .peephole();
+
+    let instrs = fn_instrs(&program, "less");
+    assert!(
+        instrs
+            .iter()
+            .all(|instr| !matches!(instr, Instr::Setcc { .. })),
+        "The comparison should not be stored, as it is only used by the jump."
+    );
+    let cmps = instrs
+        .iter()
+        .filter(|instr| matches!(instr, Instr::Cmpq { .. }))
+        .count();
+    assert_eq!(cmps, 1, "Only the original comparison should be left.");
+}
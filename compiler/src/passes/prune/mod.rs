@@ -0,0 +1,3 @@
+pub mod prune;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,136 @@
+use crate::passes::atomize::Atom;
+use crate::passes::explicate::{CExpr, CTail, PrgExplicated};
+use crate::passes::select::AsmItem;
+use crate::utils::gen_sym::UniqueSym;
+use std::collections::HashSet;
+
+impl<'p> PrgExplicated<'p> {
+    /// Removes bindings to variables that are never read and whose expression has no side effects,
+    /// and then removes the blocks and functions that cannot be reached from the entry.
+    #[must_use]
+    pub fn prune(mut self) -> PrgExplicated<'p> {
+        // Removing a binding can make the variables it reads unused, so repeat until nothing changes.
+        loop {
+            let mut read = HashSet::new();
+            for tail in self.blocks.values() {
+                collect_reads(tail, &mut read);
+            }
+
+            let mut changed = false;
+            self.blocks = self
+                .blocks
+                .into_iter()
+                .map(|(sym, tail)| (sym, prune_tail(tail, &read, &mut changed)))
+                .collect();
+
+            if !changed {
+                break;
+            }
+        }
+
+        let mut reachable = HashSet::new();
+        let mut worklist = vec![self.entry];
+        while let Some(sym) = worklist.pop() {
+            // Standard library functions are not in the blocks yet.
+            let Some(tail) = self.blocks.get(&sym) else {
+                continue;
+            };
+            if reachable.insert(sym) {
+                collect_targets(tail, &mut worklist);
+            }
+        }
+
+        PrgExplicated {
+            blocks: self
+                .blocks
+                .into_iter()
+                .filter(|(sym, _)| reachable.contains(sym))
+                .collect(),
+            fn_params: self
+                .fn_params
+                .into_iter()
+                .filter(|(sym, _)| reachable.contains(sym))
+                .collect(),
+            defs: self.defs,
+            entry: self.entry,
+            std: self.std,
+        }
+    }
+}
+
+fn prune_tail<'p>(tail: CTail<'p>, read: &HashSet<UniqueSym<'p>>, changed: &mut bool) -> CTail<'p> {
+    match tail {
+        CTail::Seq { sym, bnd, tail } => {
+            let tail = prune_tail(*tail, read, changed);
+            let pure = !matches!(bnd.inner, CExpr::Apply { .. } | CExpr::Asm { .. });
+            if pure && !read.contains(&sym) {
+                *changed = true;
+                return tail;
+            }
+            CTail::Seq {
+                sym,
+                bnd,
+                tail: Box::new(tail),
+            }
+        }
+        tail => tail,
+    }
+}
+
+/// Collects the variables that are read in the block.
+fn collect_reads<'p>(tail: &CTail<'p>, read: &mut HashSet<UniqueSym<'p>>) {
+    let mut atom = |atm: &Atom<'p>| {
+        if let Atom::Var { sym } = atm {
+            read.insert(*sym);
+        }
+    };
+
+    match tail {
+        CTail::Return { expr } => atom(&expr.inner),
+        CTail::Seq { bnd, tail, .. } => {
+            match &bnd.inner {
+                CExpr::Atom { atm }
+                | CExpr::UnaryOp { expr: atm, .. }
+                | CExpr::AccessField { strct: atm, .. } => atom(atm),
+                CExpr::BinaryOp { exprs, .. } => exprs.iter().for_each(atom),
+                CExpr::Apply { fun, args } => {
+                    atom(fun);
+                    args.iter().for_each(|(arg, _)| atom(arg));
+                }
+                CExpr::Struct { fields, .. } => fields.iter().for_each(|(_, field)| atom(field)),
+                CExpr::FunRef { .. } => {}
+                // Inline assembly can read any of the variables that it refers to.
+                CExpr::Asm { instrs } => read.extend(instrs.iter().flat_map(AsmItem::vars)),
+            }
+            collect_reads(tail, read);
+        }
+        CTail::IfStmt { cnd, .. } => match cnd {
+            CExpr::Atom { atm } | CExpr::UnaryOp { expr: atm, .. } => atom(atm),
+            CExpr::BinaryOp { exprs, .. } => exprs.iter().for_each(atom),
+            _ => unreachable!("Conditions are atoms or operations on atoms."),
+        },
+        CTail::Switch { scrut, .. } => atom(scrut),
+        CTail::Goto { .. } => {}
+    }
+}
+
+/// Collects the blocks and functions that the block can jump to or refers to.
+fn collect_targets<'p>(tail: &CTail<'p>, targets: &mut Vec<UniqueSym<'p>>) {
+    match tail {
+        CTail::Return { .. } => {}
+        CTail::Seq { bnd, tail, .. } => {
+            match &bnd.inner {
+                CExpr::FunRef { sym } => targets.push(*sym),
+                CExpr::Asm { instrs } => targets.extend(instrs.iter().flat_map(AsmItem::labels)),
+                _ => {}
+            }
+            collect_targets(tail, targets);
+        }
+        CTail::IfStmt { thn, els, .. } => targets.extend([*thn, *els]),
+        CTail::Goto { lbl } => targets.push(*lbl),
+        CTail::Switch { table, default, .. } => {
+            targets.extend(table);
+            targets.push(*default);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::passes::explicate::{CExpr, CTail, PrgExplicated};
+use crate::passes::parse::parse::parse_program;
+
+/// Returns the names of the variables that are bound in the blocks, with their expressions.
+fn bindings<'a, 'p>(program: &'a PrgExplicated<'p>) -> Vec<(&'p str, &'a CExpr<'p>)> {
+    let mut bindings = Vec::new();
+    for mut tail in program.blocks.values() {
+        while let CTail::Seq {
+            sym,
+            bnd,
+            tail: next,
+        } = tail
+        {
+            bindings.push((sym.sym, &bnd.inner));
+            tail = next;
+        }
+    }
+    bindings
+}
+
+#[test]
+fn prune_dead_code() {
+    let test = include_str!("../../../../programs/good/functions/dead_code.test");
+    let program = parse_program(test)
+        .unwrap()
+        .validate()
+        .unwrap()
+        .reveal()
+        .decide()
+        .inline()
+        .atomize()
+        .fold()
+        .explicate();
+
+    let has_unused = |program: &PrgExplicated| {
+        program.blocks.keys().any(|sym| sym.sym == "unused")
+            || program.fn_params.keys().any(|sym| sym.sym == "unused")
+    };
+    let binds =
+        |program: &PrgExplicated, name: &str| bindings(program).iter().any(|(sym, _)| *sym == name);
+    // The loop after the `return` is the only code that refers to `print`.
+    let prints = |program: &PrgExplicated| {
+        bindings(program)
+            .iter()
+            .any(|(_, bnd)| matches!(bnd, CExpr::FunRef { sym } if *sym == program.std["print"]))
+    };
+
+    assert!(has_unused(&program));
+    assert!(binds(&program, "dead"));
+    assert!(prints(&program));
+
+    let program = program.prune();
+
+    assert!(
+        !has_unused(&program),
+        "The unused function should be removed."
+    );
+    assert!(
+        !binds(&program, "dead"),
+        "The binding that is never read should be removed."
+    );
+    assert!(
+        !prints(&program),
+        "The blocks after the `return` should be removed."
+    );
+
+    let bindings = bindings(&program);
+    assert!(
+        bindings
+            .iter()
+            .any(|(sym, bnd)| *sym == "ignored" && matches!(bnd, CExpr::Apply { .. })),
+        "The call that is never read should be kept for its side effects."
+    );
+    assert!(
+        bindings
+            .iter()
+            .any(|(sym, bnd)| *sym == "clobbered" && matches!(bnd, CExpr::Asm { .. })),
+        "The asm block that is never read should be kept for its side effects."
+    );
+}
@@ -31,11 +31,11 @@ impl Display for RExpr<'_> {
                 bnd,
                 bdy,
             } => {
-                writeln!(
-                    f,
-                    "let {}{sym} = {bnd};",
-                    if *mutable { "mut " } else { "" }
-                )?;
+                write!(f, "let {}{sym}", if *mutable { "mut " } else { "" })?;
+                if let Some(bnd) = bnd {
+                    write!(f, " = {bnd}")?;
+                }
+                writeln!(f, ";")?;
                 write!(f, "{bdy}")
             }
             RExpr::If { cnd, thn, els } => {
@@ -48,16 +48,27 @@ impl Display for RExpr<'_> {
             RExpr::Apply { fun, args } => {
                 write!(f, "{fun}({})", args.iter().format(", "))
             }
-            RExpr::Loop { bdy } => {
+            RExpr::Loop { lbl, bdy } => {
+                if let Some(lbl) = lbl {
+                    write!(f, "{lbl}: ")?;
+                }
                 writeln!(f, "loop {{")?;
                 writeln!(indented(f), "{bdy}")?;
                 write!(f, "}}")
             }
-            RExpr::Break { bdy } => {
-                write!(f, "break {bdy}")
+            RExpr::Break { lbl, bdy } => {
+                write!(f, "break ")?;
+                if let Some(lbl) = lbl {
+                    write!(f, "{lbl} ")?;
+                }
+                write!(f, "{bdy}")
             }
-            RExpr::Continue => {
-                write!(f, "continue")
+            RExpr::Continue { lbl } => {
+                write!(f, "continue")?;
+                if let Some(lbl) = lbl {
+                    write!(f, " {lbl}")?;
+                }
+                Ok(())
             }
             RExpr::Return { bdy } => {
                 write!(f, "return {bdy}")
@@ -84,7 +95,40 @@ impl Display for RExpr<'_> {
             RExpr::AccessField { strct, field } => {
                 write!(f, "{strct}.{field}")
             }
-            RExpr::Asm { .. } => todo!(),
+            RExpr::Match { scrut, arms } => {
+                writeln!(f, "match {scrut} {{")?;
+                for arm in arms {
+                    write!(indented(f), "{}", arm.pat)?;
+                    if let Some(guard) = &arm.guard {
+                        write!(f, " if {guard}")?;
+                    }
+                    writeln!(f, " => {},", arm.bdy)?;
+                }
+                write!(f, "}}")
+            }
+            RExpr::Switch {
+                scrut,
+                lo,
+                table,
+                arms,
+            } => {
+                writeln!(
+                    f,
+                    "switch ({scrut} - {lo}) [{}] {{",
+                    table.iter().format(", ")
+                )?;
+                for (i, arm) in arms.iter().enumerate() {
+                    writeln!(indented(f), "{i} => {arm},")?;
+                }
+                write!(f, "}}")
+            }
+            RExpr::Asm { instrs } => {
+                writeln!(f, "asm {{")?;
+                for instr in instrs {
+                    writeln!(indented(f), "{instr}")?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
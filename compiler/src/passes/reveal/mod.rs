@@ -3,8 +3,8 @@ pub mod reveal;
 
 use crate::passes::parse::{BinaryOp, Def, Typed, UnaryOp};
 use crate::passes::select::std_lib::Std;
-use crate::passes::select::InstrSelected;
-use crate::passes::validate::TLit;
+use crate::passes::select::AsmItemSelected;
+use crate::passes::validate::{PatternValidated, TLit};
 use crate::utils::gen_sym::UniqueSym;
 use derive_more::Display;
 use itertools::Itertools;
@@ -41,7 +41,7 @@ pub enum RExpr<'p> {
     Let {
         sym: UniqueSym<'p>,
         mutable: bool,
-        bnd: Box<Typed<'p, RExpr<'p>>>,
+        bnd: Option<Box<Typed<'p, RExpr<'p>>>>,
         bdy: Box<Typed<'p, RExpr<'p>>>,
     },
     If {
@@ -54,15 +54,19 @@ pub enum RExpr<'p> {
         args: Vec<Typed<'p, RExpr<'p>>>,
     },
     Loop {
+        lbl: Option<UniqueSym<'p>>,
         bdy: Box<Typed<'p, RExpr<'p>>>,
     },
     Break {
+        lbl: Option<UniqueSym<'p>>,
         bdy: Box<Typed<'p, RExpr<'p>>>,
     },
     Return {
         bdy: Box<Typed<'p, RExpr<'p>>>,
     },
-    Continue,
+    Continue {
+        lbl: Option<UniqueSym<'p>>,
+    },
     Seq {
         stmt: Box<Typed<'p, RExpr<'p>>>,
         cnt: Box<Typed<'p, RExpr<'p>>>,
@@ -79,7 +83,25 @@ pub enum RExpr<'p> {
         strct: Box<Typed<'p, RExpr<'p>>>,
         field: &'p str,
     },
+    Match {
+        scrut: Box<Typed<'p, RExpr<'p>>>,
+        arms: Vec<RArm<'p>>,
+    },
+    /// Evaluates the arm at index `table[scrut - lo]`, or the last arm if `scrut - lo` is outside
+    /// of the table. This is created by `decide` for dense integer matches.
+    Switch {
+        scrut: Box<Typed<'p, RExpr<'p>>>,
+        lo: i64,
+        table: Vec<usize>,
+        arms: Vec<Typed<'p, RExpr<'p>>>,
+    },
     Asm {
-        instrs: Vec<InstrSelected<'p>>,
+        instrs: Vec<AsmItemSelected<'p>>,
     },
 }
+
+pub struct RArm<'p> {
+    pub pat: Typed<'p, PatternValidated<'p>>,
+    pub guard: Option<Typed<'p, RExpr<'p>>>,
+    pub bdy: Typed<'p, RExpr<'p>>,
+}
@@ -1,5 +1,5 @@
 use crate::passes::parse::{Meta, Typed};
-use crate::passes::reveal::{DefRevealed, PrgRevealed, RExpr};
+use crate::passes::reveal::{DefRevealed, PrgRevealed, RArm, RExpr};
 use crate::passes::validate::{DefValidated, ExprValidated, PrgValidated};
 use crate::utils::gen_sym::UniqueSym;
 use crate::utils::push_map::PushMap;
@@ -32,11 +32,13 @@ fn reveal_def<'p>(
             params,
             typ,
             bdy,
+            inline,
         } => DefRevealed::Fn {
             sym,
             params,
             typ,
             bdy: reveal_expr(bdy, scope),
+            inline,
         },
         DefValidated::TypeDef { sym, def } => DefRevealed::TypeDef { sym, def },
     }
@@ -76,7 +78,7 @@ fn reveal_expr<'p>(
             bdy,
             ..
         } => {
-            let bnd = Box::new(reveal_expr(*bnd, scope));
+            let bnd = bnd.map(|bnd| Box::new(reveal_expr(*bnd, scope)));
             scope.remove(sym, |scope| RExpr::Let {
                 sym,
                 mutable,
@@ -96,10 +98,12 @@ fn reveal_expr<'p>(
                 .map(|arg| reveal_expr(arg, scope))
                 .collect(),
         },
-        ExprValidated::Loop { bdy } => RExpr::Loop {
+        ExprValidated::Loop { lbl, bdy } => RExpr::Loop {
+            lbl,
             bdy: Box::new(reveal_expr(*bdy, scope)),
         },
-        ExprValidated::Break { bdy } => RExpr::Break {
+        ExprValidated::Break { lbl, bdy } => RExpr::Break {
+            lbl,
             bdy: Box::new(reveal_expr(*bdy, scope)),
         },
         ExprValidated::Seq { stmt, cnt } => RExpr::Seq {
@@ -110,11 +114,12 @@ fn reveal_expr<'p>(
             sym,
             bnd: Box::new(reveal_expr(*bnd, scope)),
         },
-        ExprValidated::Continue => RExpr::Continue,
+        ExprValidated::Continue { lbl } => RExpr::Continue { lbl },
         ExprValidated::Return { bdy } => RExpr::Return {
             bdy: Box::new(reveal_expr(*bdy, scope)),
         },
-        ExprValidated::Struct { sym, fields } => RExpr::Struct {
+        // The fields copied from a base are already added to `fields` during validation.
+        ExprValidated::Struct { sym, fields, .. } => RExpr::Struct {
             sym,
             fields: fields
                 .into_iter()
@@ -126,8 +131,39 @@ fn reveal_expr<'p>(
             field,
         },
         ExprValidated::Variant { .. } => todo!(),
+        ExprValidated::RefMut { .. } => unreachable!("References are lowered by `resolve`."),
         ExprValidated::Switch { .. } => todo!(),
-        ExprValidated::Asm { instrs } => RExpr::Asm { instrs },
+        ExprValidated::Match { scrut, arms } => RExpr::Match {
+            scrut: Box::new(reveal_expr(*scrut, scope)),
+            arms: arms
+                .into_iter()
+                .map(|arm| RArm {
+                    pat: arm.pat,
+                    guard: arm.guard.map(|guard| reveal_expr(guard, scope)),
+                    bdy: reveal_expr(arm.bdy, scope),
+                })
+                .collect(),
+        },
+        ExprValidated::Asm {
+            operands, instrs, ..
+        } => {
+            // The operands are bound to their symbols right before the asm block.
+            return operands.into_iter().rev().fold(
+                Meta {
+                    meta: expr.meta.clone(),
+                    inner: RExpr::Asm { instrs },
+                },
+                |bdy, (sym, _, bnd)| Meta {
+                    meta: expr.meta.clone(),
+                    inner: RExpr::Let {
+                        sym,
+                        mutable: false,
+                        bnd: Some(Box::new(reveal_expr(*bnd, scope))),
+                        bdy: Box::new(bdy),
+                    },
+                },
+            );
+        }
     };
 
     Meta {
@@ -1,16 +1,20 @@
 use crate::interpreter::IO;
 use crate::passes::conclude::X86Concluded;
 
+use crate::passes::select::std_lib::{runtime_memory, INITIAL_RSP, RUNTIME_OFFSET};
 use crate::passes::select::{
-    Block, Cnd, Instr, Reg, VarArg, X86Selected, CALLEE_SAVED, CALLER_SAVED,
+    Block, Cnd, Instr, InstrSelected, Reg, VarArg, X86Selected, CALLEE_SAVED, CALLER_SAVED,
 };
-use crate::passes::validate::TLit;
 use crate::utils::gen_sym::UniqueSym;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::mem;
-use zerocopy::AsBytes;
+
+/// Error code returned by syscalls that the interpreter does not emulate.
+const ENOSYS: i64 = 38;
 
 #[derive(Default)]
 pub struct Status {
@@ -31,6 +35,8 @@ pub struct Status {
 pub struct IStats {
     pub branches_taken: usize,
     pub instructions_executed: usize,
+    /// The number of operands that are read from or written to memory, such as spilled variables.
+    pub memory_operands: usize,
 }
 
 pub struct X86Interpreter<'p, I: IO> {
@@ -43,8 +49,8 @@ pub struct X86Interpreter<'p, I: IO> {
 
     pub memory: HashMap<i64, i64>,
     pub block_ids: HashMap<usize, UniqueSym<'p>>,
-    pub read_buffer: Vec<u8>,
-    pub write_buffer: Vec<u8>,
+    /// Maps blocks that do not end in a jump or return to the block that they fall through to.
+    pub fallthrough: HashMap<UniqueSym<'p>, UniqueSym<'p>>,
     pub status: Status,
     pub stats: IStats,
 }
@@ -52,12 +58,16 @@ pub struct X86Interpreter<'p, I: IO> {
 impl<'p> X86Concluded<'p> {
     pub fn interpret_with_stats(&self, io: &mut impl IO) -> (i64, IStats) {
         let block_ids = self.blocks.keys().map(|sym| (sym.id, *sym)).collect();
+        let fallthrough = self.order.iter().copied().tuple_windows().collect();
 
         let mut regs = HashMap::new();
-        for reg in CALLEE_SAVED.into_iter().chain(CALLER_SAVED.into_iter()) {
+        for reg in CALLEE_SAVED.into_iter().chain(CALLER_SAVED) {
             regs.insert(reg, 0);
         }
 
+        let mut memory = runtime_memory();
+        init_process_stack(&mut memory, regs[&Reg::RSP], io.args());
+
         let mut state = X86Interpreter {
             // todo: remove this clone
             blocks: &self
@@ -70,10 +80,9 @@ impl<'p> X86Concluded<'p> {
             regs,
             vars: HashMap::default(),
             var_stack: vec![],
-            memory: HashMap::default(),
+            memory,
             block_ids,
-            read_buffer: Vec::new(),
-            write_buffer: Vec::new(),
+            fallthrough,
             status: Status::default(),
             stats: IStats::default(),
         };
@@ -92,7 +101,7 @@ impl<'p> X86Selected<'p> {
         let block_ids = self.blocks.keys().map(|sym| (sym.id, *sym)).collect();
 
         let mut regs = HashMap::new();
-        for reg in CALLEE_SAVED.into_iter().chain(CALLER_SAVED.into_iter()) {
+        for reg in CALLEE_SAVED.into_iter().chain(CALLER_SAVED) {
             regs.insert(reg, 0);
         }
 
@@ -100,16 +109,18 @@ impl<'p> X86Selected<'p> {
         regs.insert(Reg::RBP, i64::MAX - 7);
         regs.insert(Reg::RSP, (i64::MAX - 7) - 0x1000);
 
+        let mut memory = runtime_memory();
+        init_process_stack(&mut memory, regs[&Reg::RSP], io.args());
+
         let mut state = X86Interpreter {
             blocks: &self.blocks,
             io,
             regs,
             vars: HashMap::default(),
             var_stack: vec![],
-            memory: HashMap::default(),
+            memory,
             block_ids,
-            read_buffer: Vec::new(),
-            write_buffer: Vec::new(),
+            fallthrough: HashMap::new(),
             status: Default::default(),
             stats: IStats::default(),
         };
@@ -118,163 +129,241 @@ impl<'p> X86Selected<'p> {
     }
 }
 
+/// Lays out `argc`, `argv` and an empty `envp` at `rsp` like the kernel does at program entry,
+/// and stores `rsp` as the initial stack pointer.
+fn init_process_stack(memory: &mut HashMap<i64, i64>, rsp: i64, args: &[String]) {
+    let argc = args.len() as i64;
+    memory.insert(RUNTIME_OFFSET + INITIAL_RSP, rsp);
+    memory.insert(rsp, argc);
+
+    // The strings come after `argv`, `envp` and their null terminators.
+    let mut string = rsp + 8 * (argc + 3);
+    for (i, arg) in (1..).zip(args) {
+        memory.insert(rsp + 8 * i, string);
+        for byte in arg.bytes().chain([0]) {
+            memory.insert(string, i64::from(byte));
+            string += 1;
+        }
+    }
+    memory.insert(rsp + 8 * (argc + 1), 0);
+    memory.insert(rsp + 8 * (argc + 2), 0);
+}
+
 impl<'p, I: IO> X86Interpreter<'p, I> {
     fn instr_to_addr(&self, block_name: UniqueSym, instr_id: usize) -> i64 {
         // Please do not make more than 2^32 blocks or blocks with more than 2^32 instructions!
         ((block_name.id << 32) | instr_id) as i64
     }
 
-    fn interpret_addr(&mut self, addr: i64) -> i64 {
+    fn addr_to_instr(&self, addr: i64) -> (UniqueSym<'p>, usize) {
         let block_id = (addr >> 32) as usize;
         let instr_id = (addr & 0xFF_FF_FF_FF) as usize;
-        self.interpret_block(self.block_ids[&block_id], instr_id)
+        (self.block_ids[&block_id], instr_id)
     }
 
-    pub fn interpret_block(&mut self, block_name: UniqueSym<'p>, offset: usize) -> i64 {
-        let block = &self.blocks[&block_name];
-
-        for (instr_id, instr) in block.instrs.iter().enumerate().skip(offset) {
-            self.stats.instructions_executed += 1;
-            match instr {
-                Instr::Addq { src, dst } => {
-                    self.set_arg(dst, self.get_arg(src) + self.get_arg(dst));
-                }
-                Instr::Subq { src, dst } => {
-                    self.set_arg(dst, self.get_arg(dst) - self.get_arg(src));
-                }
-                Instr::Negq { dst } => self.set_arg(dst, -self.get_arg(dst)),
-                Instr::Movq { src, dst } => self.set_arg(dst, self.get_arg(src)),
-                Instr::Pushq { src } => {
-                    let rsp = self.regs.get_mut(&Reg::RSP).unwrap();
-                    assert_eq!(*rsp % 8, 0, "Misaligned stack pointer.");
-                    *rsp -= 8;
-                    self.memory.insert(*rsp, self.get_arg(src));
-                }
-                Instr::Popq { dst } => {
-                    let rsp = self.regs[&Reg::RSP];
-                    assert_eq!(rsp % 8, 0, "Misaligned stack pointer.");
-                    self.set_arg(dst, self.memory[&rsp]);
-                    *self.regs.get_mut(&Reg::RSP).unwrap() += 8;
-                }
-                Instr::Jmp { lbl } => {
-                    return self.interpret_block(*lbl, 0);
-                }
-                Instr::Retq => {
-                    let rsp = self.regs[&Reg::RSP];
-                    assert_eq!(rsp % 8, 0, "Misaligned stack pointer.");
-                    let addr = self.memory[&rsp];
-                    *self.regs.get_mut(&Reg::RSP).unwrap() += 8;
-
-                    // Pop var context
-                    self.vars = self.var_stack.pop().expect(
-                        "Found more returns than we have had calls so far, ur program is weird m8",
-                    );
-
-                    return self.interpret_addr(addr);
-                }
-                Instr::Syscall { .. } => match self.regs[&Reg::RAX] {
-                    0x00 => self.syscall_read(),
-                    0x01 => self.syscall_write(),
-                    0x3C => {
-                        return self.regs[&Reg::RDI];
+    /// Interprets the program starting at the given instruction, until it exits.
+    /// Control flow is handled iteratively, so long-running loops do not overflow the stack.
+    pub fn interpret_block(&mut self, mut block_name: UniqueSym<'p>, mut offset: usize) -> i64 {
+        'blocks: loop {
+            let block = &self.blocks[&block_name];
+
+            for (instr_id, instr) in block.instrs.iter().enumerate().skip(offset) {
+                self.stats.instructions_executed += 1;
+                self.stats.memory_operands += memory_operands(instr);
+                match instr {
+                    Instr::Addq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(src).wrapping_add(self.get_arg(dst)));
                     }
-                    _ => unreachable!(),
-                },
-                Instr::Divq { divisor } => {
-                    let rax = self.regs[&Reg::RAX];
-                    let rdx = self.regs[&Reg::RDX];
-                    let dividend = (i128::from(rdx) << 64) | i128::from(rax);
-                    let divisor = i128::from(self.get_arg(divisor));
-
-                    self.regs.insert(Reg::RAX, (dividend / divisor) as i64);
-                    self.regs.insert(Reg::RDX, (dividend % divisor) as i64);
-                }
-                Instr::Mulq { src } => {
-                    let rax = self.regs[&Reg::RAX] as i128;
-                    let src = self.get_arg(src) as i128;
+                    Instr::Subq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(dst).wrapping_sub(self.get_arg(src)));
+                    }
+                    Instr::Negq { dst } => self.set_arg(dst, self.get_arg(dst).wrapping_neg()),
+                    Instr::Incq { dst } => self.set_arg(dst, self.get_arg(dst).wrapping_add(1)),
+                    Instr::Decq { dst } => self.set_arg(dst, self.get_arg(dst).wrapping_sub(1)),
+                    Instr::Movq { src, dst } => self.set_arg(dst, self.get_arg(src)),
+                    Instr::Pushq { src } => {
+                        let rsp = self.regs.get_mut(&Reg::RSP).unwrap();
+                        assert_eq!(*rsp % 8, 0, "Misaligned stack pointer.");
+                        *rsp -= 8;
+                        self.memory.insert(*rsp, self.get_arg(src));
+                    }
+                    Instr::Popq { dst } => {
+                        let rsp = self.regs[&Reg::RSP];
+                        assert_eq!(rsp % 8, 0, "Misaligned stack pointer.");
+                        self.set_arg(dst, self.memory[&rsp]);
+                        *self.regs.get_mut(&Reg::RSP).unwrap() += 8;
+                    }
+                    Instr::Jmp { lbl } => {
+                        (block_name, offset) = (*lbl, 0);
+                        continue 'blocks;
+                    }
+                    Instr::Retq => {
+                        let rsp = self.regs[&Reg::RSP];
+                        assert_eq!(rsp % 8, 0, "Misaligned stack pointer.");
+                        let addr = self.memory[&rsp];
+                        *self.regs.get_mut(&Reg::RSP).unwrap() += 8;
+
+                        // Pop var context
+                        self.vars = self.var_stack.pop().expect(
+                            "Found more returns than we have had calls so far, ur program is weird m8",
+                        );
+
+                        (block_name, offset) = self.addr_to_instr(addr);
+                        continue 'blocks;
+                    }
+                    Instr::Syscall { .. } => match self.regs[&Reg::RAX] {
+                        0x00 => self.syscall_read(),
+                        0x01 => self.syscall_write(),
+                        0x02 => self.syscall_open(),
+                        0x03 => {
+                            let res = self.io.close(self.regs[&Reg::RDI]);
+                            self.regs.insert(Reg::RAX, res);
+                        }
+                        0x3C => {
+                            return self.regs[&Reg::RDI];
+                        }
+                        // Like the kernel, fail with `ENOSYS` for syscalls that are not emulated.
+                        _ => {
+                            self.regs.insert(Reg::RAX, -ENOSYS);
+                        }
+                    },
+                    Instr::Divq { divisor } => {
+                        // `divq` is an unsigned division.
+                        let rax = self.regs[&Reg::RAX] as u64;
+                        let rdx = self.regs[&Reg::RDX] as u64;
+                        let dividend = (u128::from(rdx) << 64) | u128::from(rax);
+                        let divisor = u128::from(self.get_arg(divisor) as u64);
+
+                        self.regs.insert(Reg::RAX, (dividend / divisor) as i64);
+                        self.regs.insert(Reg::RDX, (dividend % divisor) as i64);
+                    }
+                    Instr::Mulq { src } => {
+                        let rax = self.regs[&Reg::RAX] as i128;
+                        let src = self.get_arg(src) as i128;
 
-                    let res = rax * src;
+                        let res = rax * src;
 
-                    self.regs.insert(Reg::RAX, (res & (-1i64 as i128)) as i64);
-                    self.regs.insert(Reg::RDX, (res >> 64) as i64);
-                }
-                Instr::Jcc { lbl, cnd } => {
-                    self.stats.branches_taken += 1;
-                    if self.evaluate_cnd(*cnd) {
-                        return self.interpret_block(*lbl, 0);
+                        self.regs.insert(Reg::RAX, (res & (-1i64 as i128)) as i64);
+                        self.regs.insert(Reg::RDX, (res >> 64) as i64);
                     }
-                }
-                Instr::Cmpq { src, dst } => {
-                    assert!(
-                        !matches!(dst, VarArg::Imm { .. }),
-                        "Destination cannot be an immediate."
-                    );
-
-                    let src = self.get_arg(src);
-                    let dst = self.get_arg(dst);
-
-                    let (res, overflow) = dst.overflowing_sub(src);
-
-                    // Maybe this can be done "prettier", but honestly it works.
-                    let src = u64::from_ne_bytes(src.to_ne_bytes());
-                    let dst = u64::from_ne_bytes(dst.to_ne_bytes());
-
-                    self.status = Status {
-                        carry: src > dst,
-                        parity_even: res % 2 == 0,
-                        zero: res == 0,
-                        sign: res < 0,
-                        overflow,
+                    Instr::Jcc { lbl, cnd } => {
+                        self.stats.branches_taken += 1;
+                        if self.evaluate_cnd(*cnd) {
+                            (block_name, offset) = (*lbl, 0);
+                            continue 'blocks;
+                        }
                     }
-                }
-                Instr::Andq { src, dst } => {
-                    self.set_arg(dst, self.get_arg(src) & self.get_arg(dst));
-                }
-                Instr::Orq { src, dst } => self.set_arg(dst, self.get_arg(src) | self.get_arg(dst)),
-                Instr::Xorq { src, dst } => {
-                    self.set_arg(dst, self.get_arg(src) ^ self.get_arg(dst));
-                }
-                Instr::Notq { dst } => self.set_arg(dst, !self.get_arg(dst)),
-                Instr::Setcc { cnd } => {
-                    let rax = self.regs[&Reg::RAX];
-                    let cnd = i64::from(self.evaluate_cnd(*cnd));
-                    self.regs.insert(Reg::RAX, rax & !0xFF | cnd);
-                }
-                Instr::LoadLbl { sym, dst } => {
-                    let val = self.instr_to_addr(*sym, 0);
-                    self.set_arg(dst, val);
-                }
-                Instr::CallqDirect { lbl, .. } => {
-                    let ret_addr = self.instr_to_addr(block_name, instr_id + 1);
+                    Instr::JmpTable { idx, lbls } => {
+                        self.stats.branches_taken += 1;
+                        (block_name, offset) = (lbls[self.get_arg(idx) as usize], 0);
+                        continue 'blocks;
+                    }
+                    Instr::Cmpq { src, dst } => {
+                        assert!(
+                            !matches!(dst, VarArg::Imm { .. }),
+                            "Destination cannot be an immediate."
+                        );
+
+                        let src = self.get_arg(src);
+                        let dst = self.get_arg(dst);
+
+                        let (res, overflow) = dst.overflowing_sub(src);
+
+                        // Maybe this can be done "prettier", but honestly it works.
+                        let src = u64::from_ne_bytes(src.to_ne_bytes());
+                        let dst = u64::from_ne_bytes(dst.to_ne_bytes());
+
+                        self.status = Status {
+                            carry: src > dst,
+                            parity_even: res % 2 == 0,
+                            zero: res == 0,
+                            sign: res < 0,
+                            overflow,
+                        }
+                    }
+                    Instr::Andq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(src) & self.get_arg(dst));
+                    }
+                    Instr::Orq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(src) | self.get_arg(dst))
+                    }
+                    Instr::Xorq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(src) ^ self.get_arg(dst));
+                    }
+                    Instr::Notq { dst } => self.set_arg(dst, !self.get_arg(dst)),
+                    Instr::Shlq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(dst) << (self.get_arg(src) & 0x3F));
+                    }
+                    Instr::Sarq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(dst) >> (self.get_arg(src) & 0x3F));
+                    }
+                    Instr::Imulq { src, dst } => {
+                        self.set_arg(dst, self.get_arg(dst).wrapping_mul(self.get_arg(src)));
+                    }
+                    Instr::Leaq { src, dst } => {
+                        let VarArg::Deref { reg, off } = src else {
+                            panic!("Tried to load the address of a non-memory operand.");
+                        };
+                        self.set_arg(dst, self.regs[reg] + off);
+                    }
+                    Instr::Testq { src, dst } => {
+                        let res = self.get_arg(dst) & self.get_arg(src);
+
+                        self.status = Status {
+                            carry: false,
+                            parity_even: res % 2 == 0,
+                            zero: res == 0,
+                            sign: res < 0,
+                            overflow: false,
+                        }
+                    }
+                    Instr::Setcc { cnd } => {
+                        let rax = self.regs[&Reg::RAX];
+                        let cnd = i64::from(self.evaluate_cnd(*cnd));
+                        self.regs.insert(Reg::RAX, rax & !0xFF | cnd);
+                    }
+                    Instr::LoadLbl { sym, dst } => {
+                        let val = self.instr_to_addr(*sym, 0);
+                        self.set_arg(dst, val);
+                    }
+                    Instr::CallqDirect { lbl, .. } => {
+                        let ret_addr = self.instr_to_addr(block_name, instr_id + 1);
 
-                    let rsp = self.regs.get_mut(&Reg::RSP).unwrap();
-                    assert_eq!(*rsp % 8, 0, "Misaligned stack pointer.");
-                    *rsp -= 8;
-                    self.memory.insert(*rsp, ret_addr);
+                        let rsp = self.regs.get_mut(&Reg::RSP).unwrap();
+                        assert_eq!(*rsp % 8, 0, "Misaligned stack pointer.");
+                        *rsp -= 8;
+                        self.memory.insert(*rsp, ret_addr);
 
-                    //Push old var context
-                    self.var_stack.push(mem::take(&mut self.vars));
+                        //Push old var context
+                        self.var_stack.push(mem::take(&mut self.vars));
 
-                    return self.interpret_block(*lbl, 0);
-                }
-                Instr::CallqIndirect { src, .. } => {
-                    let ret_addr = self.instr_to_addr(block_name, instr_id + 1);
+                        (block_name, offset) = (*lbl, 0);
+                        continue 'blocks;
+                    }
+                    Instr::CallqIndirect { src, .. } => {
+                        let ret_addr = self.instr_to_addr(block_name, instr_id + 1);
 
-                    let rsp = self.regs.get_mut(&Reg::RSP).unwrap();
-                    assert_eq!(*rsp % 8, 0, "Misaligned stack pointer.");
-                    *rsp -= 8;
-                    self.memory.insert(*rsp, ret_addr);
+                        let rsp = self.regs.get_mut(&Reg::RSP).unwrap();
+                        assert_eq!(*rsp % 8, 0, "Misaligned stack pointer.");
+                        *rsp -= 8;
+                        self.memory.insert(*rsp, ret_addr);
 
-                    let block = self.get_arg(src);
+                        let block = self.get_arg(src);
 
-                    //Push old var context
-                    self.var_stack.push(mem::take(&mut self.vars));
+                        //Push old var context
+                        self.var_stack.push(mem::take(&mut self.vars));
 
-                    return self.interpret_addr(block);
+                        (block_name, offset) = self.addr_to_instr(block);
+                        continue 'blocks;
+                    }
                 }
             }
+            if let Some(next) = self.fallthrough.get(&block_name) {
+                (block_name, offset) = (*next, 0);
+                continue 'blocks;
+            }
+            panic!("A block ran out of instructions.");
         }
-        panic!("A block ran out of instructions.");
     }
 
     fn evaluate_cnd(&self, cnd: Cnd) -> bool {
@@ -327,51 +416,60 @@ impl<'p, I: IO> X86Interpreter<'p, I> {
 
     fn syscall_read(&mut self) {
         let file = self.regs[&Reg::RDI];
-        assert_eq!(file, 0, "Only reading from stdin is supported right now.");
         let buffer = self.regs[&Reg::RSI];
         let buffer_len = self.regs[&Reg::RDX];
         assert!(buffer_len >= 1);
 
-        if self.read_buffer.is_empty() {
-            if let Some(read) = self.io.read() {
-                self.read_buffer = format!("{}\n", read.int()).into_bytes();
-                self.read_buffer.reverse();
-            } else {
-                self.memory.insert(buffer, 0);
-                self.regs.insert(Reg::RAX, 0);
-                return;
+        let res = match self.io.read(file, buffer_len as usize) {
+            Ok(bytes) => {
+                for (addr, byte) in (buffer..).zip(&bytes) {
+                    self.memory.insert(addr, i64::from(*byte));
+                }
+                bytes.len() as i64
             }
-        }
-        let val = self.read_buffer.pop().unwrap();
-        self.memory.insert(buffer, val as i64);
-        self.regs.insert(Reg::RAX, 1);
+            Err(err) => err,
+        };
+        self.regs.insert(Reg::RAX, res);
     }
 
     fn syscall_write(&mut self) {
         let file = self.regs[&Reg::RDI];
-        assert_eq!(file, 1, "Only writing to stdout is supported right now.");
         let buffer = self.regs[&Reg::RSI];
         let buffer_len = self.regs[&Reg::RDX];
-        assert_eq!(
-            buffer_len, 1,
-            "Only writing 1 byte at a time is supported right now."
-        );
-
-        let val = *self.memory.get(&buffer).unwrap();
-        match val as u8 {
-            b'\n' => {
-                let val = std::str::from_utf8(self.write_buffer.as_bytes())
-                    .unwrap()
-                    .parse()
-                    .unwrap();
-                self.io.print(TLit::I64 { val });
-                self.write_buffer.clear();
-            }
-            val => {
-                self.write_buffer.push(val);
+
+        let bytes = (buffer..buffer + buffer_len)
+            .map(|addr| self.memory[&addr] as u8)
+            .collect::<Vec<_>>();
+        let res = self.io.write(file, &bytes);
+        self.regs.insert(Reg::RAX, res);
+    }
+
+    fn syscall_open(&mut self) {
+        let mut path = Vec::new();
+        let mut addr = self.regs[&Reg::RDI];
+        loop {
+            let byte = self.memory[&addr] as u8;
+            if byte == 0 {
+                break;
             }
+            path.push(byte);
+            addr += 1;
         }
 
-        self.regs.insert(Reg::RAX, 1);
+        let res = self
+            .io
+            .open(&path, self.regs[&Reg::RSI], self.regs[&Reg::RDX]);
+        self.regs.insert(Reg::RAX, res);
     }
 }
+
+fn memory_operands(instr: &InstrSelected) -> usize {
+    let count = Cell::new(0);
+    instr.clone().fmap(|arg| {
+        if let VarArg::Deref { .. } = arg {
+            count.set(count.get() + 1);
+        }
+        arg
+    });
+    count.get()
+}
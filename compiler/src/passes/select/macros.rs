@@ -85,6 +85,56 @@ macro_rules! notq {
     };
 }
 
+#[macro_export]
+macro_rules! shlq {
+    ($src:expr, $dst:expr) => {
+        $crate::passes::select::Instr::Shlq {
+            src: $src,
+            dst: $dst,
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! sarq {
+    ($src:expr, $dst:expr) => {
+        $crate::passes::select::Instr::Sarq {
+            src: $src,
+            dst: $dst,
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! imulq {
+    ($src:expr, $dst:expr) => {
+        $crate::passes::select::Instr::Imulq {
+            src: $src,
+            dst: $dst,
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! leaq {
+    ($src:expr, $dst:expr) => {
+        $crate::passes::select::Instr::Leaq {
+            src: $src,
+            dst: $dst,
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! testq {
+    ($src:expr, $dst:expr) => {
+        $crate::passes::select::Instr::Testq {
+            src: $src,
+            dst: $dst,
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! negq {
     ($dst:expr) => {
@@ -92,6 +142,20 @@ macro_rules! negq {
     };
 }
 
+#[macro_export]
+macro_rules! incq {
+    ($dst:expr) => {
+        $crate::passes::select::Instr::Incq { dst: $dst }
+    };
+}
+
+#[macro_export]
+macro_rules! decq {
+    ($dst:expr) => {
+        $crate::passes::select::Instr::Decq { dst: $dst }
+    };
+}
+
 #[macro_export]
 macro_rules! movq {
     ($src:expr, $dst:expr) => {
@@ -153,6 +217,16 @@ macro_rules! jmp {
     };
 }
 
+#[macro_export]
+macro_rules! jmp_table {
+    ($idx:expr, $lbls:expr) => {
+        $crate::passes::select::Instr::JmpTable {
+            idx: $idx,
+            lbls: $lbls,
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! setcc {
     ($cnd:expr) => {
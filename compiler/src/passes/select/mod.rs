@@ -10,7 +10,8 @@ use crate::utils::gen_sym::UniqueSym;
 use derive_more::Display;
 use functor_derive::Functor;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 
 #[derive(Display)]
@@ -21,6 +22,8 @@ use std::fmt::Display;
 pub struct X86Selected<'p> {
     pub blocks: HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
     pub entry: UniqueSym<'p>,
+    /// The blocks that are the entries of functions defined by the program.
+    pub fns: HashSet<UniqueSym<'p>>,
     pub std: Std<'p>,
 }
 
@@ -66,6 +69,10 @@ pub enum Instr<Arg: Display, IdentVars: Display> {
     Mulq { src: Arg },
     #[display(fmt = "negq\t{dst}")]
     Negq { dst: Arg },
+    #[display(fmt = "incq\t{dst}")]
+    Incq { dst: Arg },
+    #[display(fmt = "decq\t{dst}")]
+    Decq { dst: Arg },
     #[display(fmt = "movq\t{src}\t{dst}")]
     Movq { src: Arg, dst: Arg },
     #[display(fmt = "pushq\t{src}")]
@@ -82,6 +89,9 @@ pub enum Instr<Arg: Display, IdentVars: Display> {
     Jmp { lbl: IdentVars },
     #[display(fmt = "jcc\t{cnd}\t{lbl}")]
     Jcc { lbl: IdentVars, cnd: Cnd },
+    /// Jumps to the label at index `idx` in `lbls`. The labels are stored as a table of addresses.
+    #[display(fmt = "jmp_table\t{idx}\t{}", r#"lbls.iter().format(", ")"#)]
+    JmpTable { idx: Arg, lbls: Vec<IdentVars> },
     #[display(fmt = "andq {src}\t{dst}")]
     Andq { src: Arg, dst: Arg },
     #[display(fmt = "orq {src}\t{dst}")]
@@ -90,6 +100,16 @@ pub enum Instr<Arg: Display, IdentVars: Display> {
     Xorq { src: Arg, dst: Arg },
     #[display(fmt = "notq\t{dst}")]
     Notq { dst: Arg },
+    #[display(fmt = "shlq\t{src}\t{dst}")]
+    Shlq { src: Arg, dst: Arg },
+    #[display(fmt = "sarq\t{src}\t{dst}")]
+    Sarq { src: Arg, dst: Arg },
+    #[display(fmt = "imulq\t{src}\t{dst}")]
+    Imulq { src: Arg, dst: Arg },
+    #[display(fmt = "leaq\t{src}\t{dst}")]
+    Leaq { src: Arg, dst: Arg },
+    #[display(fmt = "testq\t{src}\t{dst}")]
+    Testq { src: Arg, dst: Arg },
     #[display(fmt = "setcc\t{cnd}")]
     Setcc { cnd: Cnd }, //TODO allow setting other byteregs
     #[display(fmt = "loadlbl\t{sym}\t{dst}")]
@@ -100,6 +120,51 @@ pub enum Instr<Arg: Display, IdentVars: Display> {
     CallqIndirect { src: Arg, arity: usize },
 }
 
+/// An item of an inline `asm` block: either an instruction or a label local to the block.
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum AsmItem<Arg: Display, IdentVars: Display> {
+    #[display(fmt = "{instr}")]
+    Instr { instr: Instr<Arg, IdentVars> },
+    #[display(fmt = "{lbl}:")]
+    Label { lbl: IdentVars },
+}
+
+pub type AsmItemSelected<'p> = AsmItem<VarArg<UniqueSym<'p>>, UniqueSym<'p>>;
+
+impl<'p> AsmItemSelected<'p> {
+    /// Returns the variables that the item refers to.
+    pub fn vars(&self) -> Vec<UniqueSym<'p>> {
+        let AsmItem::Instr { instr } = self else {
+            return Vec::new();
+        };
+        let vars = RefCell::new(Vec::new());
+        instr.clone().fmap(|arg| {
+            if let VarArg::XVar { sym } = &arg {
+                vars.borrow_mut().push(*sym);
+            }
+            arg
+        });
+        vars.into_inner()
+    }
+
+    /// Returns the labels that the item refers to, such as the functions that it calls.
+    pub fn labels(&self) -> Vec<UniqueSym<'p>> {
+        match self {
+            AsmItem::Instr {
+                instr:
+                    Instr::Jmp { lbl }
+                    | Instr::Jcc { lbl, .. }
+                    | Instr::CallqDirect { lbl, .. }
+                    | Instr::LoadLbl { sym: lbl, .. },
+            } => vec![*lbl],
+            AsmItem::Instr {
+                instr: Instr::JmpTable { lbls, .. },
+            } => lbls.clone(),
+            _ => Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Display, Functor)]
 pub enum VarArg<IdentVars: Display> {
     #[display(fmt = "${val}")]
@@ -164,3 +229,101 @@ pub enum Reg {
     R14,
     R15,
 }
+
+pub enum ReadWriteOp {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Calls `arg` on every location that the instruction reads or writes. Jumps read the locations
+/// that are live at the start of their targets, which is left to the liveness analysis. Registers
+/// named in asm blocks, including RAX that holds their result, are locations like any other.
+pub fn handle_instr<A: Display, I: Display>(
+    instr: &Instr<VarArg<A>, I>,
+    mut arg: impl FnMut(&VarArg<A>, ReadWriteOp),
+) {
+    use ReadWriteOp::Read as R;
+    use ReadWriteOp::ReadWrite as RW;
+    use ReadWriteOp::Write as W;
+
+    match instr {
+        Instr::Addq { src, dst }
+        | Instr::Subq { src, dst }
+        | Instr::Andq { src, dst }
+        | Instr::Orq { src, dst }
+        | Instr::Xorq { src, dst }
+        | Instr::Shlq { src, dst }
+        | Instr::Sarq { src, dst }
+        | Instr::Imulq { src, dst } => {
+            arg(dst, RW);
+            arg(src, R);
+        }
+        Instr::Cmpq { src, dst } | Instr::Testq { src, dst } => {
+            arg(dst, R);
+            arg(src, R);
+        }
+        Instr::Movq { src, dst } | Instr::Leaq { src, dst } => {
+            arg(dst, W);
+            arg(src, R);
+        }
+        Instr::Pushq { src } => {
+            arg(src, R);
+        }
+        Instr::Popq { dst } => {
+            arg(dst, W);
+        }
+        Instr::Negq { dst } | Instr::Notq { dst } | Instr::Incq { dst } | Instr::Decq { dst } => {
+            arg(dst, RW);
+        }
+        Instr::CallqDirect { arity, .. } => {
+            for reg in CALLER_SAVED.into_iter().skip(*arity) {
+                arg(&VarArg::Reg { reg }, W);
+            }
+            for reg in CALLER_SAVED.into_iter().take(*arity) {
+                arg(&VarArg::Reg { reg }, RW);
+            }
+        }
+        Instr::Syscall { arity } => {
+            for reg in CALLER_SAVED {
+                arg(&VarArg::Reg { reg }, W);
+            }
+            for reg in SYSCALL_REGS.into_iter().take(*arity) {
+                arg(&VarArg::Reg { reg }, R);
+            }
+        }
+        Instr::Retq => {
+            // Because the return value of our function is in RAX, we need to consider it being read at the end of a block.
+            arg(&VarArg::Reg { reg: Reg::RAX }, R);
+        }
+        Instr::Setcc { .. } => {
+            arg(&VarArg::Reg { reg: Reg::RAX }, W);
+        }
+        Instr::Mulq { src } => {
+            arg(&VarArg::Reg { reg: Reg::RDX }, W);
+            arg(&VarArg::Reg { reg: Reg::RAX }, RW);
+            arg(src, R);
+        }
+        Instr::Divq { divisor } => {
+            arg(&VarArg::Reg { reg: Reg::RDX }, RW);
+            arg(&VarArg::Reg { reg: Reg::RAX }, RW);
+            arg(divisor, R);
+        }
+        Instr::Jmp { .. } | Instr::Jcc { .. } => {}
+        Instr::JmpTable { idx, .. } => {
+            arg(idx, R);
+        }
+        Instr::LoadLbl { dst, .. } => {
+            arg(dst, W);
+        }
+        Instr::CallqIndirect { src, arity } => {
+            for reg in CALLER_SAVED.into_iter().skip(*arity) {
+                arg(&VarArg::Reg { reg }, W);
+            }
+            for reg in CALLER_SAVED.into_iter().take(*arity) {
+                arg(&VarArg::Reg { reg }, RW);
+            }
+            arg(src, R);
+        }
+    }
+}
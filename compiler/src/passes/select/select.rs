@@ -2,32 +2,34 @@ use crate::passes::atomize::Atom;
 use crate::passes::eliminate::{EExpr, ETail, PrgEliminated};
 use crate::passes::parse::types::Type;
 use crate::passes::parse::{BinaryOp, Meta, Param, UnaryOp};
-use crate::passes::select::std_lib::add_std_library;
+use crate::passes::select::std_lib::{add_std_library, Std};
 use crate::passes::select::{
-    Block, Cnd, InstrSelected, VarArg, X86Selected, CALLEE_SAVED_NO_STACK, CALLER_SAVED,
+    AsmItem, AsmItemSelected, Block, Cnd, Instr, InstrSelected, VarArg, X86Selected, CALLER_SAVED,
 };
 use crate::utils::gen_sym::{gen_sym, UniqueSym};
 use crate::*;
 use std::collections::HashMap;
+use std::mem;
 
 impl<'p> PrgEliminated<'p> {
     #[must_use]
     pub fn select(self) -> X86Selected<'p> {
         let mut blocks = HashMap::new();
+        let mut std = self.std;
+        std.insert("syscall_flush", gen_sym("syscall_flush"));
 
-        blocks.extend(
-            self.blocks
-                .into_iter()
-                .map(|(sym, block)| (sym, select_block(sym, block, &self.fn_params))),
-        );
+        for (sym, block) in self.blocks {
+            select_block(sym, block, &self.fn_params, &std, &mut blocks);
+        }
 
-        add_std_library(&self.std, &mut blocks);
+        add_std_library(&std, &mut blocks);
 
         X86Selected {
             blocks,
             entry: self.entry,
+            fns: self.fn_params.keys().copied().collect(),
             // todo: technically we only need this for testing
-            std: self.std,
+            std,
         }
     }
 }
@@ -36,15 +38,14 @@ fn select_block<'p>(
     sym: UniqueSym<'p>,
     tail: ETail<'p>,
     fn_params: &HashMap<UniqueSym<'p>, Vec<Param<UniqueSym<'p>>>>,
-) -> Block<'p, VarArg<UniqueSym<'p>>> {
+    std: &Std<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
     let mut instrs = Vec::new();
 
     if let Some(params) = fn_params.get(&sym) {
         instrs.push(pushq!(reg!(RBP)));
         instrs.push(movq!(reg!(RSP), reg!(RBP)));
-        for reg in CALLEE_SAVED_NO_STACK {
-            instrs.push(pushq!(VarArg::Reg { reg }));
-        }
 
         for (reg, param) in CALLER_SAVED.into_iter().zip(params.iter()) {
             instrs.push(movq!(VarArg::Reg { reg }, VarArg::XVar { sym: param.sym }));
@@ -55,12 +56,20 @@ fn select_block<'p>(
         );
     }
 
-    select_tail(tail, &mut instrs);
+    // Labels in inline assembly split the block, so the symbol of the block being built can change.
+    let mut sym = sym;
+    select_tail(tail, &mut sym, &mut instrs, std, blocks);
 
-    Block { instrs }
+    blocks.insert(sym, Block { instrs });
 }
 
-fn select_tail<'p>(tail: ETail<'p>, instrs: &mut Vec<InstrSelected<'p>>) {
+fn select_tail<'p>(
+    tail: ETail<'p>,
+    sym: &mut UniqueSym<'p>,
+    instrs: &mut Vec<InstrSelected<'p>>,
+    std: &Std<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
     match tail {
         ETail::Return { exprs } => {
             assert!(
@@ -72,20 +81,30 @@ fn select_tail<'p>(tail: ETail<'p>, instrs: &mut Vec<InstrSelected<'p>>) {
                 instrs.push(movq!(select_atom(arg), VarArg::Reg { reg }));
             }
 
-            for reg in CALLEE_SAVED_NO_STACK.into_iter().rev() {
-                instrs.push(popq!(VarArg::Reg { reg }));
-            }
+            // The callee-saved registers that the function uses are saved by `assign`.
             instrs.push(popq!(reg!(RBP)));
 
             instrs.push(retq!());
         }
-        ETail::Seq {
-            syms: sym,
-            bnd,
-            tail,
-        } => {
-            instrs.extend(select_assign(&sym, bnd));
-            select_tail(*tail, instrs);
+        ETail::Seq { syms, bnd, tail } => {
+            match bnd.inner {
+                EExpr::Asm { instrs: items } => {
+                    select_asm(items, sym, instrs, std, blocks);
+
+                    // The result of an asm block is passed in RAX.
+                    if !matches!(bnd.meta[..], [Type::Unit | Type::Never]) {
+                        instrs.push(movq!(reg!(RAX), var!(syms[0])));
+                    }
+                }
+                inner => instrs.extend(select_assign(
+                    &syms,
+                    Meta {
+                        meta: bnd.meta,
+                        inner,
+                    },
+                )),
+            }
+            select_tail(*tail, sym, instrs, std, blocks);
         }
         ETail::IfStmt { cnd, thn, els } => match cnd {
             EExpr::BinaryOp {
@@ -106,6 +125,25 @@ fn select_tail<'p>(tail: ETail<'p>, instrs: &mut Vec<InstrSelected<'p>>) {
         ETail::Goto { lbl } => {
             instrs.push(jmp!(lbl));
         }
+        ETail::Switch {
+            scrut,
+            lo,
+            table,
+            default,
+        } => {
+            // After subtracting `lo`, values below it wrap around to large unsigned numbers, so a
+            // single unsigned comparison checks both bounds of the table.
+            let idx = gen_sym("tmp");
+            instrs.push(movq!(select_atom(scrut), var!(idx)));
+            if lo != 0 {
+                instrs.push(subq!(imm!(lo), var!(idx)));
+            }
+            instrs.extend(vec![
+                cmpq!(imm!(table.len() as i64), var!(idx)),
+                jcc!(default, Cnd::AboveOrEqual),
+                jmp_table!(var!(idx), table),
+            ]);
+        }
     }
 }
 
@@ -191,7 +229,7 @@ fn select_assign<'p>(
         EExpr::Apply { fun, args, .. } => {
             let mut instrs = vec![];
 
-            for (arg, reg) in args.iter().zip(CALLER_SAVED.into_iter()) {
+            for (arg, reg) in args.iter().zip(CALLER_SAVED) {
                 instrs.push(movq!(select_atom(*arg), VarArg::Reg { reg }));
             }
             assert!(
@@ -207,7 +245,41 @@ fn select_assign<'p>(
 
             instrs
         }
-        EExpr::Asm { instrs } => instrs,
+        EExpr::Asm { .. } => unreachable!("Inline assembly is selected by `select_asm`."),
+    }
+}
+
+/// Inserts the instructions of an inline assembly block. Every label ends the block that is
+/// currently being built with a jump to that label, after which selection continues in a new block.
+/// The output buffer is flushed before every syscall, so that printed output stays in order with
+/// writes and exits in the assembly.
+fn select_asm<'p>(
+    items: Vec<AsmItemSelected<'p>>,
+    sym: &mut UniqueSym<'p>,
+    instrs: &mut Vec<InstrSelected<'p>>,
+    std: &Std<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    for item in items {
+        match item {
+            AsmItem::Instr {
+                instr: instr @ Instr::Syscall { .. },
+            } => {
+                // All argument registers are passed, since the syscall reads them afterward.
+                instrs.push(callq_direct!(std["syscall_flush"], CALLER_SAVED.len()));
+                instrs.push(instr);
+            }
+            AsmItem::Instr { instr } => instrs.push(instr),
+            AsmItem::Label { lbl } => {
+                instrs.push(jmp!(lbl));
+                blocks.insert(
+                    mem::replace(sym, lbl),
+                    Block {
+                        instrs: mem::take(instrs),
+                    },
+                );
+            }
+        }
     }
 }
 
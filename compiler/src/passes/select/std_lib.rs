@@ -1,104 +1,414 @@
 use crate::passes::select::Cnd;
-use crate::passes::select::{Block, VarArg};
+use crate::passes::select::{Block, Instr, VarArg};
 use crate::utils::gen_sym::{gen_sym, UniqueSym};
 use crate::{
-    addq, block, cmpq, deref, divq, imm, jcc, jmp, movq, mulq, negq, popq, pushq, reg, retq, subq,
-    syscall,
+    addq, andq, block, callq_direct, cmpq, deref, divq, imm, jcc, jmp, movq, mulq, negq, popq,
+    pushq, reg, retq, shlq, subq, syscall,
 };
 use std::collections::HashMap;
 
 pub type Std<'p> = HashMap<&'p str, UniqueSym<'p>>;
 
+/// Address of the zero-initialized, writable memory used by the standard library.
+pub const RUNTIME_OFFSET: i64 = 0x1000_0000;
+/// Number of bytes read from stdin at once.
+pub const READ_BUFFER_SIZE: i64 = 0x1000;
+/// Number of bytes buffered before they are written to stdout.
+pub const WRITE_BUFFER_SIZE: i64 = 0x1000;
+/// Maximum length of a path passed to `open`, including the terminating null byte.
+pub const PATH_BUFFER_SIZE: i64 = 0x1000;
+
+/// Offset of the index of the next unread byte in the read buffer.
+const READ_POS: i64 = 0;
+/// Offset of the number of valid bytes in the read buffer.
+const READ_LEN: i64 = 8;
+/// Offset of the number of pending bytes in the write buffer.
+const WRITE_LEN: i64 = 16;
+/// Offset of the stack pointer at program entry, which points to `argc` followed by `argv` and `envp`.
+pub const INITIAL_RSP: i64 = 24;
+/// Offset of the number of bytes pushed onto the path buffer.
+const PATH_LEN: i64 = 32;
+/// Offset of the byte that is read from or written to a file.
+const FILE_BYTE: i64 = 40;
+const READ_BUFFER: i64 = 48;
+// Bytes are loaded and stored with 8-byte moves, so the buffers need 8 bytes of slack.
+const WRITE_BUFFER: i64 = READ_BUFFER + READ_BUFFER_SIZE + 8;
+const PATH_BUFFER: i64 = WRITE_BUFFER + WRITE_BUFFER_SIZE + 8;
+/// Total size of the runtime memory.
+pub const RUNTIME_SIZE: i64 = PATH_BUFFER + PATH_BUFFER_SIZE + 8;
+
+/// Error code returned by `open` when the path does not fit in the path buffer.
+const ENAMETOOLONG: i64 = 36;
+
+/// Flags of the `open` syscall, which programs get from the builtins of the same name.
+pub const O_RDONLY: i64 = 0o0;
+pub const O_WRONLY: i64 = 0o1;
+pub const O_RDWR: i64 = 0o2;
+pub const O_CREAT: i64 = 0o100;
+pub const O_EXCL: i64 = 0o200;
+pub const O_TRUNC: i64 = 0o1000;
+pub const O_APPEND: i64 = 0o2000;
+
+/// Returns the initial state of the runtime memory words that are read before they are written.
+#[must_use]
+pub fn runtime_memory() -> HashMap<i64, i64> {
+    [READ_POS, READ_LEN, WRITE_LEN, PATH_LEN]
+        .into_iter()
+        .map(|off| (RUNTIME_OFFSET + off, 0))
+        .collect()
+}
+
 pub fn add_std_library<'p>(
     std: &Std<'p>,
     blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
 ) {
-    add_exit_block(std["exit"], blocks);
-    add_print_block(std["print"], blocks);
-    add_read_block(std["read"], blocks, std["exit"]);
+    add_flush_block(std["flush"], blocks);
+    add_syscall_flush_block(std["syscall_flush"], blocks, std["flush"]);
+    add_exit_block(std["exit"], blocks, std["flush"]);
+    add_write_i64_block(
+        std["write_i64"],
+        blocks,
+        std["write_u64"],
+        std["write_byte"],
+    );
+    add_write_u64_block(std["write_u64"], blocks, std["write_byte"]);
+    add_write_bool_block(std["write_bool"], blocks, std["write_byte"]);
+    add_write_unit_block(std["write_unit"], blocks, std["write_byte"]);
+    for (print, write) in [
+        ("print", "write_i64"),
+        ("print_u64", "write_u64"),
+        ("print_bool", "write_bool"),
+        ("print_unit", "write_unit"),
+    ] {
+        add_print_block(std[print], blocks, std[write], std["write_byte"]);
+    }
+    add_read_byte_block(std["read_byte"], blocks);
+    add_write_byte_block(std["write_byte"], blocks, std["flush"]);
+    add_read_block(std["read"], blocks, std["exit"], std["read_byte"]);
+
+    let ptr_array_byte = gen_sym("ptr_array_byte");
+    add_ptr_array_byte_block(ptr_array_byte, blocks);
+    add_arg_count_block(std["arg_count"], blocks);
+    add_arg_byte_block(std["arg_byte"], blocks, ptr_array_byte);
+    add_env_count_block(std["env_count"], blocks);
+    add_env_byte_block(std["env_byte"], blocks, ptr_array_byte);
+
+    add_path_push_block(std["path_push"], blocks);
+    add_open_block(std["open"], blocks);
+    add_read_fd_block(std["read_fd"], blocks);
+    add_write_fd_block(std["write_fd"], blocks, std["flush"]);
+    add_close_block(std["close"], blocks, std["flush"]);
+    for (flag, val) in [
+        ("o_rdonly", O_RDONLY),
+        ("o_wronly", O_WRONLY),
+        ("o_rdwr", O_RDWR),
+        ("o_creat", O_CREAT),
+        ("o_excl", O_EXCL),
+        ("o_trunc", O_TRUNC),
+        ("o_append", O_APPEND),
+    ] {
+        blocks.insert(std[flag], block!(movq!(imm!(val), reg!(RAX)), retq!()));
+    }
+}
+
+/// Writes all pending bytes in the write buffer to stdout.
+fn add_flush_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let flush_write = gen_sym("flush_write");
+    let flush_exit = gen_sym("flush_exit");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(deref!(RCX, WRITE_LEN), reg!(RDX)), // bytes to write
+            cmpq!(imm!(0), reg!(RDX)),
+            jcc!(flush_exit, Cnd::EQ),
+            jmp!(flush_write)
+        ),
+    );
+    blocks.insert(
+        flush_write,
+        block!(
+            movq!(imm!(1), reg!(RAX)), // syscall 1: Write
+            movq!(imm!(1), reg!(RDI)), // STDOUT
+            movq!(imm!(RUNTIME_OFFSET + WRITE_BUFFER), reg!(RSI)),
+            syscall!(4),
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(imm!(0), deref!(RCX, WRITE_LEN)),
+            jmp!(flush_exit)
+        ),
+    );
+    blocks.insert(flush_exit, block!(retq!()));
+}
+
+/// Flushes the write buffer before a syscall in inline assembly, preserving the registers that
+/// hold the arguments of the syscall.
+fn add_syscall_flush_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    flush: UniqueSym<'p>,
+) {
+    blocks.insert(
+        entry,
+        block!(
+            pushq!(reg!(RAX)),
+            pushq!(reg!(RDI)),
+            pushq!(reg!(RSI)),
+            pushq!(reg!(RDX)),
+            callq_direct!(flush, 0),
+            popq!(reg!(RDX)),
+            popq!(reg!(RSI)),
+            popq!(reg!(RDI)),
+            popq!(reg!(RAX)),
+            retq!()
+        ),
+    );
 }
 
 fn add_exit_block<'p>(
     entry: UniqueSym<'p>,
     blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    flush: UniqueSym<'p>,
 ) {
     blocks.insert(
         entry,
         block!(
-            movq!(reg!(RAX), reg!(RDI)),
+            pushq!(reg!(RAX)),
+            callq_direct!(flush, 0),
+            popq!(reg!(RDI)),
             movq!(imm!(0x3C), reg!(RAX)),
             syscall!(2)
         ),
     );
 }
 
-fn add_print_block<'p>(
+/// Returns the next byte from stdin, or -1 at EOF.
+fn add_read_byte_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let read_byte_fill = gen_sym("read_byte_fill");
+    let read_byte_load = gen_sym("read_byte_load");
+    let read_byte_eof = gen_sym("read_byte_eof");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(deref!(RCX, READ_POS), reg!(RAX)),
+            cmpq!(deref!(RCX, READ_LEN), reg!(RAX)),
+            jcc!(read_byte_fill, Cnd::GE),
+            jmp!(read_byte_load)
+        ),
+    );
+    blocks.insert(
+        read_byte_fill,
+        block!(
+            movq!(imm!(0), reg!(RAX)), // syscall 0: Read
+            movq!(imm!(0), reg!(RDI)), // STDIN
+            movq!(imm!(RUNTIME_OFFSET + READ_BUFFER), reg!(RSI)),
+            movq!(imm!(READ_BUFFER_SIZE), reg!(RDX)),
+            syscall!(4),
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(reg!(RAX), deref!(RCX, READ_LEN)),
+            movq!(imm!(0), deref!(RCX, READ_POS)),
+            // A read of 0 bytes means EOF, a negative result is an error.
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(read_byte_eof, Cnd::LE),
+            jmp!(read_byte_load)
+        ),
+    );
+    blocks.insert(read_byte_eof, block!(movq!(imm!(-1), reg!(RAX)), retq!()));
+    blocks.insert(
+        read_byte_load,
+        block!(
+            movq!(deref!(RCX, READ_POS), reg!(RDX)),
+            movq!(reg!(RDX), reg!(RSI)),
+            addq!(reg!(RCX), reg!(RSI)),
+            movq!(deref!(RSI, READ_BUFFER), reg!(RAX)),
+            andq!(imm!(0xFF), reg!(RAX)),
+            addq!(imm!(1), reg!(RDX)),
+            movq!(reg!(RDX), deref!(RCX, READ_POS)),
+            retq!()
+        ),
+    );
+}
+
+/// Appends a byte to the write buffer, flushing it when it is full.
+fn add_write_byte_block<'p>(
     entry: UniqueSym<'p>,
     blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    flush: UniqueSym<'p>,
 ) {
-    let print_neg = gen_sym("print_neg");
-    let print_push_loop = gen_sym("print_push_loop");
-    let print_print_loop = gen_sym("print_print_loop");
-    let print_exit = gen_sym("print_exit");
+    let write_byte_exit = gen_sym("write_byte_exit");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(deref!(RCX, WRITE_LEN), reg!(RDX)),
+            movq!(reg!(RDX), reg!(RSI)),
+            addq!(reg!(RCX), reg!(RSI)),
+            movq!(reg!(RAX), deref!(RSI, WRITE_BUFFER)),
+            addq!(imm!(1), reg!(RDX)),
+            movq!(reg!(RDX), deref!(RCX, WRITE_LEN)),
+            cmpq!(imm!(WRITE_BUFFER_SIZE), reg!(RDX)),
+            jcc!(flush, Cnd::GE), // tail call
+            jmp!(write_byte_exit)
+        ),
+    );
+    blocks.insert(write_byte_exit, block!(retq!()));
+}
 
+/// Adds a block that writes the value in `RAX` using `write`, followed by a newline.
+/// The value is returned unchanged.
+fn add_print_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    write: UniqueSym<'p>,
+    write_byte: UniqueSym<'p>,
+) {
     blocks.insert(
         entry,
         block!(
             pushq!(reg!(RAX)),
-            movq!(imm!(10), reg!(RCX)),
-            pushq!(imm!(i64::from(b'\n'))),
-            movq!(imm!(0), reg!(RSI)),
+            callq_direct!(write, 1),
+            movq!(imm!(i64::from(b'\n')), reg!(RAX)),
+            callq_direct!(write_byte, 1),
+            popq!(reg!(RAX)),
+            retq!()
+        ),
+    );
+}
+
+/// Writes a signed integer in decimal.
+fn add_write_i64_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    write_u64: UniqueSym<'p>,
+    write_byte: UniqueSym<'p>,
+) {
+    let write_i64_neg = gen_sym("write_i64_neg");
+
+    blocks.insert(
+        entry,
+        block!(
             cmpq!(imm!(0), reg!(RAX)),
-            jcc!(print_neg, Cnd::Sign),
-            jmp!(print_push_loop)
+            jcc!(write_i64_neg, Cnd::Sign),
+            jmp!(write_u64)
         ),
     );
     blocks.insert(
-        print_neg,
+        write_i64_neg,
         block!(
-            movq!(imm!(1), reg!(RSI)),
+            pushq!(reg!(RAX)),
+            movq!(imm!(i64::from(b'-')), reg!(RAX)),
+            callq_direct!(write_byte, 1),
+            popq!(reg!(RAX)),
+            // Negating `i64::MIN` gives back `i64::MIN`, which is correct when read as unsigned.
             negq!(reg!(RAX)),
-            jmp!(print_push_loop)
+            jmp!(write_u64)
+        ),
+    );
+}
+
+/// Writes an unsigned integer in decimal.
+fn add_write_u64_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    write_byte: UniqueSym<'p>,
+) {
+    let write_u64_push_loop = gen_sym("write_u64_push_loop");
+    let write_u64_write_loop = gen_sym("write_u64_write_loop");
+    let write_u64_exit = gen_sym("write_u64_exit");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(10), reg!(RCX)),
+            pushq!(imm!(0)), // marks the end of the digits
+            jmp!(write_u64_push_loop)
         ),
     );
     blocks.insert(
-        print_push_loop,
+        write_u64_push_loop,
         block!(
             movq!(imm!(0), reg!(RDX)),
             divq!(reg!(RCX)),
             addq!(imm!(i64::from(b'0')), reg!(RDX)),
             pushq!(reg!(RDX)),
             cmpq!(imm!(0), reg!(RAX)),
-            jcc!(print_push_loop, Cnd::NE),
-            cmpq!(imm!(0), reg!(RSI)),
-            jcc!(print_print_loop, Cnd::EQ),
-            pushq!(imm!(i64::from(b'-'))),
-            jmp!(print_print_loop)
+            jcc!(write_u64_push_loop, Cnd::NE),
+            jmp!(write_u64_write_loop)
         ),
     );
     blocks.insert(
-        print_print_loop,
+        write_u64_write_loop,
         block!(
-            // Print top of stack
-            movq!(imm!(1), reg!(RAX)), // syscall 1: Write
-            movq!(imm!(1), reg!(RDI)), // STDOUT
-            movq!(reg!(RSP), reg!(RSI)),
-            movq!(imm!(1), reg!(RDX)),
-            syscall!(4),
-            // Check if we continue
+            // Write top of stack
             popq!(reg!(RAX)),
-            cmpq!(imm!(i64::from(b'\n')), reg!(RAX)),
-            jcc!(print_print_loop, Cnd::NE),
-            jmp!(print_exit)
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(write_u64_exit, Cnd::EQ),
+            callq_direct!(write_byte, 1),
+            jmp!(write_u64_write_loop)
         ),
     );
-    blocks.insert(print_exit, block!(popq!(reg!(RAX)), retq!()));
+    blocks.insert(write_u64_exit, block!(retq!()));
+}
+
+/// Writes `true` or `false`.
+fn add_write_bool_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    write_byte: UniqueSym<'p>,
+) {
+    let write_true = gen_sym("write_true");
+    let write_false = gen_sym("write_false");
+
+    blocks.insert(
+        entry,
+        block!(
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(write_false, Cnd::EQ),
+            jmp!(write_true)
+        ),
+    );
+    blocks.insert(write_true, write_str_block("true", write_byte));
+    blocks.insert(write_false, write_str_block("false", write_byte));
+}
+
+/// Writes `unit`.
+fn add_write_unit_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    write_byte: UniqueSym<'p>,
+) {
+    blocks.insert(entry, write_str_block("unit", write_byte));
+}
+
+/// Returns a block that writes the given string byte by byte and returns.
+fn write_str_block<'p>(s: &str, write_byte: UniqueSym<'p>) -> Block<'p, VarArg<UniqueSym<'p>>> {
+    let mut instrs = s
+        .bytes()
+        .flat_map(|byte| {
+            [
+                movq!(imm!(i64::from(byte)), reg!(RAX)),
+                callq_direct!(write_byte, 1),
+            ]
+        })
+        .collect::<Vec<_>>();
+    instrs.push(retq!());
+    Block { instrs }
 }
 
 fn add_read_block<'p>(
     entry: UniqueSym<'p>,
     blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
     exit: UniqueSym<'p>,
+    read_byte: UniqueSym<'p>,
 ) {
     let read_is_neg = gen_sym("read_is_neg");
     let read_loop = gen_sym("read_loop");
@@ -114,17 +424,10 @@ fn add_read_block<'p>(
             pushq!(reg!(R13)),
             movq!(imm!(0), reg!(R13)),
             movq!(imm!(0), reg!(RBX)), // zero out RBX
-            subq!(imm!(8), reg!(RSP)), // allocate some space on the stack for reading the next byte
             // read initial character
-            movq!(imm!(0), reg!(RAX)),   // READ = 0
-            movq!(imm!(0), reg!(RDI)),   // STDIN = 0
-            movq!(reg!(RSP), reg!(RSI)), // RSI is pointer to allocated byte
-            movq!(imm!(1), reg!(RDX)),   // bytes to read = 1
-            syscall!(4),
+            callq_direct!(read_byte, 0),
             // check if first character is -
-            movq!(deref!(RSP, 0), reg!(RAX)),
-            movq!(reg!(RAX), reg!(RCX)),
-            cmpq!(imm!(i64::from(b'-')), reg!(RCX)),
+            cmpq!(imm!(i64::from(b'-')), reg!(RAX)),
             jcc!(read_is_neg, Cnd::EQ),
             jmp!(read_first)
         ),
@@ -136,39 +439,30 @@ fn add_read_block<'p>(
 
     blocks.insert(
         read_loop,
-        block!(
-            movq!(imm!(0), reg!(RAX)),   // READ = 0
-            movq!(imm!(0), reg!(RDI)),   // STDIN = 0
-            movq!(reg!(RSP), reg!(RSI)), // RSI is pointer to allocated byte
-            movq!(imm!(1), reg!(RDX)),   // bytes to read = 1
-            syscall!(4),
-            jmp!(read_first)
-        ),
+        block!(callq_direct!(read_byte, 0), jmp!(read_first)),
     );
 
     blocks.insert(
         read_first,
         block!(
-            movq!(deref!(RSP, 0), reg!(RAX)),
-            // check if newline
             movq!(reg!(RAX), reg!(RCX)),
+            // check if newline or EOF
             cmpq!(imm!(i64::from(b'\n')), reg!(RCX)),
             jcc!(read_exit, Cnd::EQ),
-            movq!(imm!(66), reg!(RDI)),
+            cmpq!(imm!(-1), reg!(RCX)),
+            jcc!(read_exit, Cnd::EQ),
+            movq!(imm!(66), reg!(RAX)),
             // check if >b'9'
-            movq!(reg!(RAX), reg!(RCX)),
             cmpq!(imm!(i64::from(b'9')), reg!(RCX)),
             jcc!(exit, Cnd::GT),
             // check if <b'0'
-            movq!(reg!(RAX), reg!(RCX)),
             cmpq!(imm!(i64::from(b'0')), reg!(RCX)),
             jcc!(exit, Cnd::LT),
+            subq!(imm!(i64::from(b'0')), reg!(RCX)),
             movq!(imm!(10), reg!(RAX)),
             mulq!(reg!(RBX)),
             movq!(reg!(RAX), reg!(RBX)),
-            movq!(deref!(RSP, 0), reg!(RAX)),
-            subq!(imm!(i64::from(b'0')), reg!(RAX)),
-            addq!(reg!(RAX), reg!(RBX)),
+            addq!(reg!(RCX), reg!(RBX)),
             jmp!(read_loop)
         ),
     );
@@ -187,10 +481,355 @@ fn add_read_block<'p>(
         read_actual_exit,
         block!(
             movq!(reg!(RBX), reg!(RAX)),
-            addq!(imm!(8), reg!(RSP)),
             popq!(reg!(R13)),
             popq!(reg!(RBX)),
             retq!()
         ),
     );
 }
+
+/// Returns byte `RCX` of string `RAX` in the null-terminated array of string pointers at `R9`,
+/// or -1 if either index is out of bounds.
+fn add_ptr_array_byte_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let find_string = gen_sym("ptr_array_byte_find_string");
+    let find_byte = gen_sym("ptr_array_byte_find_byte");
+    let next_string = gen_sym("ptr_array_byte_next_string");
+    let next_byte = gen_sym("ptr_array_byte_next_byte");
+    let out_of_bounds = gen_sym("ptr_array_byte_out_of_bounds");
+
+    blocks.insert(
+        entry,
+        block!(
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(out_of_bounds, Cnd::LT),
+            cmpq!(imm!(0), reg!(RCX)),
+            jcc!(out_of_bounds, Cnd::LT),
+            jmp!(find_string)
+        ),
+    );
+    blocks.insert(
+        find_string,
+        block!(
+            movq!(deref!(R9, 0), reg!(RSI)),
+            cmpq!(imm!(0), reg!(RSI)),
+            jcc!(out_of_bounds, Cnd::EQ),
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(find_byte, Cnd::EQ),
+            jmp!(next_string)
+        ),
+    );
+    blocks.insert(
+        next_string,
+        block!(
+            addq!(imm!(8), reg!(R9)),
+            subq!(imm!(1), reg!(RAX)),
+            jmp!(find_string)
+        ),
+    );
+    blocks.insert(
+        find_byte,
+        block!(
+            movq!(deref!(RSI, 0), reg!(RAX)),
+            andq!(imm!(0xFF), reg!(RAX)),
+            // The string ends before the requested byte.
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(out_of_bounds, Cnd::EQ),
+            cmpq!(imm!(0), reg!(RCX)),
+            jcc!(next_byte, Cnd::NE),
+            retq!()
+        ),
+    );
+    blocks.insert(
+        next_byte,
+        block!(
+            addq!(imm!(1), reg!(RSI)),
+            subq!(imm!(1), reg!(RCX)),
+            jmp!(find_byte)
+        ),
+    );
+    blocks.insert(out_of_bounds, block!(movq!(imm!(-1), reg!(RAX)), retq!()));
+}
+
+/// Returns the number of command-line arguments, including the program name.
+fn add_arg_count_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(deref!(RCX, INITIAL_RSP), reg!(RCX)),
+            movq!(deref!(RCX, 0), reg!(RAX)),
+            retq!()
+        ),
+    );
+}
+
+/// Returns byte `j` of command-line argument `i`, or -1 if either index is out of bounds.
+fn add_arg_byte_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    ptr_array_byte: UniqueSym<'p>,
+) {
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(R9)),
+            movq!(deref!(R9, INITIAL_RSP), reg!(R9)),
+            addq!(imm!(8), reg!(R9)), // skip `argc`
+            jmp!(ptr_array_byte)
+        ),
+    );
+}
+
+/// Moves the address of `envp` into `R9`, it comes after `argv` and its terminating null pointer.
+fn load_envp<'p>() -> [Instr<VarArg<UniqueSym<'p>>, UniqueSym<'p>>; 6] {
+    [
+        movq!(imm!(RUNTIME_OFFSET), reg!(RDX)),
+        movq!(deref!(RDX, INITIAL_RSP), reg!(RDX)),
+        movq!(deref!(RDX, 0), reg!(R9)),
+        shlq!(imm!(3), reg!(R9)),
+        addq!(reg!(RDX), reg!(R9)),
+        addq!(imm!(16), reg!(R9)),
+    ]
+}
+
+/// Returns the number of environment variables.
+fn add_env_count_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let env_count_loop = gen_sym("env_count_loop");
+    let env_count_next = gen_sym("env_count_next");
+
+    let mut instrs = load_envp().to_vec();
+    instrs.extend([movq!(imm!(0), reg!(RAX)), jmp!(env_count_loop)]);
+    blocks.insert(entry, Block { instrs });
+    blocks.insert(
+        env_count_loop,
+        block!(
+            cmpq!(imm!(0), deref!(R9, 0)),
+            jcc!(env_count_next, Cnd::NE),
+            retq!()
+        ),
+    );
+    blocks.insert(
+        env_count_next,
+        block!(
+            addq!(imm!(1), reg!(RAX)),
+            addq!(imm!(8), reg!(R9)),
+            jmp!(env_count_loop)
+        ),
+    );
+}
+
+/// Returns byte `j` of environment variable `i`, or -1 if either index is out of bounds.
+fn add_env_byte_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    ptr_array_byte: UniqueSym<'p>,
+) {
+    let mut instrs = load_envp().to_vec();
+    instrs.push(jmp!(ptr_array_byte));
+    blocks.insert(entry, Block { instrs });
+}
+
+/// Appends a byte to the path that is opened by the next call to `open`.
+fn add_path_push_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let path_push_store = gen_sym("path_push_store");
+    let path_push_exit = gen_sym("path_push_exit");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(deref!(RCX, PATH_LEN), reg!(RDX)),
+            // Bytes that do not fit are only counted, so `open` can report the error.
+            cmpq!(imm!(PATH_BUFFER_SIZE - 1), reg!(RDX)),
+            jcc!(path_push_exit, Cnd::GE),
+            jmp!(path_push_store)
+        ),
+    );
+    blocks.insert(
+        path_push_store,
+        block!(
+            movq!(reg!(RDX), reg!(RSI)),
+            addq!(reg!(RCX), reg!(RSI)),
+            movq!(reg!(RAX), deref!(RSI, PATH_BUFFER)),
+            jmp!(path_push_exit)
+        ),
+    );
+    blocks.insert(
+        path_push_exit,
+        block!(
+            addq!(imm!(1), reg!(RDX)),
+            movq!(reg!(RDX), deref!(RCX, PATH_LEN)),
+            retq!()
+        ),
+    );
+}
+
+/// Opens the pushed path with flags `RAX` and mode `RCX`, and clears the path.
+/// Returns the file descriptor, or a negative error code.
+fn add_open_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let open_syscall = gen_sym("open_syscall");
+    let open_too_long = gen_sym("open_too_long");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RDI)),
+            movq!(deref!(RDI, PATH_LEN), reg!(RSI)),
+            movq!(imm!(0), deref!(RDI, PATH_LEN)),
+            cmpq!(imm!(PATH_BUFFER_SIZE - 1), reg!(RSI)),
+            jcc!(open_too_long, Cnd::GT),
+            jmp!(open_syscall)
+        ),
+    );
+    blocks.insert(
+        open_syscall,
+        block!(
+            // Terminate the path with a null byte.
+            addq!(reg!(RDI), reg!(RSI)),
+            movq!(imm!(0), deref!(RSI, PATH_BUFFER)),
+            movq!(reg!(RAX), reg!(RSI)), // flags
+            movq!(reg!(RCX), reg!(RDX)), // mode
+            movq!(imm!(2), reg!(RAX)),   // syscall 2: Open
+            addq!(imm!(PATH_BUFFER), reg!(RDI)),
+            syscall!(4),
+            retq!()
+        ),
+    );
+    blocks.insert(
+        open_too_long,
+        block!(movq!(imm!(-ENAMETOOLONG), reg!(RAX)), retq!()),
+    );
+}
+
+/// Reads a byte from file descriptor `RAX`.
+/// Returns the byte, -1 at EOF, or a negative error code. `read` never fails with `EPERM`,
+/// so -1 is unambiguous.
+fn add_read_fd_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+) {
+    let read_fd_load = gen_sym("read_fd_load");
+    let read_fd_eof = gen_sym("read_fd_eof");
+    let read_fd_exit = gen_sym("read_fd_exit");
+
+    blocks.insert(
+        entry,
+        block!(
+            movq!(reg!(RAX), reg!(RDI)),
+            movq!(imm!(0), reg!(RAX)), // syscall 0: Read
+            movq!(imm!(RUNTIME_OFFSET + FILE_BYTE), reg!(RSI)),
+            movq!(imm!(1), reg!(RDX)),
+            syscall!(4),
+            cmpq!(imm!(0), reg!(RAX)),
+            jcc!(read_fd_eof, Cnd::EQ),
+            jcc!(read_fd_exit, Cnd::LT),
+            jmp!(read_fd_load)
+        ),
+    );
+    blocks.insert(
+        read_fd_load,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RCX)),
+            movq!(deref!(RCX, FILE_BYTE), reg!(RAX)),
+            andq!(imm!(0xFF), reg!(RAX)),
+            retq!()
+        ),
+    );
+    blocks.insert(read_fd_eof, block!(movq!(imm!(-1), reg!(RAX)), retq!()));
+    blocks.insert(read_fd_exit, block!(retq!()));
+}
+
+/// Writes byte `RCX` to file descriptor `RAX`, bypassing the write buffer of stdout. That buffer
+/// is flushed first when writing to stdout or stderr, so that the output stays in order.
+/// Returns the number of bytes written, or a negative error code.
+fn add_write_fd_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    flush: UniqueSym<'p>,
+) {
+    let write_fd_syscall = gen_sym("write_fd_syscall");
+    add_flush_std_fd_blocks(entry, write_fd_syscall, blocks, flush);
+
+    blocks.insert(
+        write_fd_syscall,
+        block!(
+            movq!(imm!(RUNTIME_OFFSET), reg!(RSI)),
+            movq!(reg!(RCX), deref!(RSI, FILE_BYTE)),
+            movq!(reg!(RAX), reg!(RDI)),
+            movq!(imm!(1), reg!(RAX)), // syscall 1: Write
+            addq!(imm!(FILE_BYTE), reg!(RSI)),
+            movq!(imm!(1), reg!(RDX)),
+            syscall!(4),
+            retq!()
+        ),
+    );
+}
+
+/// Closes file descriptor `RAX`, flushing the write buffer first if it is stdout or stderr.
+/// Returns 0, or a negative error code.
+fn add_close_block<'p>(
+    entry: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    flush: UniqueSym<'p>,
+) {
+    let close_syscall = gen_sym("close_syscall");
+    add_flush_std_fd_blocks(entry, close_syscall, blocks, flush);
+
+    blocks.insert(
+        close_syscall,
+        block!(
+            movq!(reg!(RAX), reg!(RDI)),
+            movq!(imm!(3), reg!(RAX)), // syscall 3: Close
+            syscall!(2),
+            retq!()
+        ),
+    );
+}
+
+/// Flushes the write buffer if file descriptor `RAX` is stdout or stderr, and continues at `next`
+/// with `RAX` and `RCX` preserved.
+fn add_flush_std_fd_blocks<'p>(
+    entry: UniqueSym<'p>,
+    next: UniqueSym<'p>,
+    blocks: &mut HashMap<UniqueSym<'p>, Block<'p, VarArg<UniqueSym<'p>>>>,
+    flush: UniqueSym<'p>,
+) {
+    let flush_std_fd = gen_sym("flush_std_fd");
+
+    blocks.insert(
+        entry,
+        block!(
+            cmpq!(imm!(1), reg!(RAX)),
+            jcc!(next, Cnd::LT),
+            cmpq!(imm!(2), reg!(RAX)),
+            jcc!(next, Cnd::GT),
+            jmp!(flush_std_fd)
+        ),
+    );
+    blocks.insert(
+        flush_std_fd,
+        block!(
+            pushq!(reg!(RAX)),
+            pushq!(reg!(RCX)),
+            callq_direct!(flush, 0),
+            popq!(reg!(RCX)),
+            popq!(reg!(RAX)),
+            jmp!(next)
+        ),
+    );
+}
@@ -0,0 +1,415 @@
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{BinaryOp, Constrained, Def, Expr, Spanned};
+use crate::passes::select::{handle_instr, AsmItem, Instr, ReadWriteOp, VarArg};
+use crate::passes::validate::error::TypeError;
+use crate::passes::validate::uniquify::BUILT_INS;
+use crate::passes::validate::{ExprConstrained, PrgConstrained};
+use crate::utils::gen_sym::UniqueSym;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+/// The variables that have been assigned at some point in a function.
+#[derive(Clone)]
+struct Assigned<'p> {
+    /// Variables that are assigned on every path to this point.
+    definitely: HashSet<UniqueSym<'p>>,
+    /// Variables that are assigned on at least one path to this point.
+    possibly: HashSet<UniqueSym<'p>>,
+    /// Whether this point can be reached at all.
+    reachable: bool,
+}
+
+impl<'p> Assigned<'p> {
+    fn unreachable() -> Self {
+        Self {
+            definitely: HashSet::new(),
+            possibly: HashSet::new(),
+            reachable: false,
+        }
+    }
+
+    /// Combines the states of two paths that come together.
+    fn join(self, other: Self) -> Self {
+        match (self.reachable, other.reachable) {
+            (false, _) => other,
+            (_, false) => self,
+            (true, true) => Self {
+                definitely: &self.definitely & &other.definitely,
+                possibly: &self.possibly | &other.possibly,
+                reachable: true,
+            },
+        }
+    }
+
+    fn assign(&mut self, sym: UniqueSym<'p>) {
+        self.definitely.insert(sym);
+        self.possibly.insert(sym);
+    }
+}
+
+struct Local {
+    mutable: bool,
+    /// Whether the variable was declared without a binding.
+    deferred: bool,
+}
+
+/// The states at the jumps out of a loop.
+#[derive(Default)]
+struct Jumps<'p> {
+    /// States at the `break`s of the loop.
+    breaks: Vec<Assigned<'p>>,
+    /// States at the `continue`s of the loop.
+    continues: Vec<Assigned<'p>>,
+}
+
+struct Env<'a, 'p> {
+    /// Functions that are declared to never return, such as `exit`.
+    diverging: &'a HashSet<UniqueSym<'p>>,
+    locals: HashMap<UniqueSym<'p>, Local>,
+    /// Labels and jumps of the enclosing loops, with the innermost loop last.
+    loops: Vec<(Option<UniqueSym<'p>>, Jumps<'p>)>,
+}
+
+impl<'p> Env<'_, 'p> {
+    /// Returns the jumps of the loop with the given label, or of the innermost loop if there is
+    /// no label.
+    fn jumps(&mut self, lbl: Option<UniqueSym<'p>>) -> &mut Jumps<'p> {
+        self.loops
+            .iter_mut()
+            .rev()
+            .find(|(loop_lbl, _)| lbl.is_none() || *loop_lbl == lbl)
+            .map(|(_, jumps)| jumps)
+            .expect("Jumps outside loops should have been rejected by `constrain`.")
+    }
+}
+
+impl<'p> PrgConstrained<'p> {
+    /// Checks that variables are assigned before they are read, and that immutable variables are
+    /// assigned at most once.
+    pub fn check_assigned(&self) -> Result<(), TypeError> {
+        let diverging = self
+            .defs
+            .values()
+            .filter_map(|def| match def {
+                Def::Fn {
+                    sym,
+                    typ:
+                        Constrained {
+                            inner: Some(Type::Never),
+                            ..
+                        },
+                    ..
+                } => Some(sym.inner),
+                _ => None,
+            })
+            .chain(
+                BUILT_INS
+                    .iter()
+                    .filter(|(_, typ)| matches!(typ, Type::Fn { typ, .. } if matches!(**typ, Type::Never)))
+                    .map(|(sym, _)| self.std[sym]),
+            )
+            .collect();
+
+        for def in self.defs.values() {
+            let Def::Fn { params, bdy, .. } = def else {
+                continue;
+            };
+
+            let mut env = Env {
+                diverging: &diverging,
+                locals: HashMap::new(),
+                loops: Vec::new(),
+            };
+            let mut state = Assigned {
+                definitely: HashSet::new(),
+                possibly: HashSet::new(),
+                reachable: true,
+            };
+            for param in params {
+                env.locals.insert(
+                    param.sym.inner,
+                    Local {
+                        mutable: param.mutable,
+                        deferred: false,
+                    },
+                );
+                state.assign(param.sym.inner);
+            }
+
+            check_expr(bdy, &mut state, &mut env)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn check_expr<'p>(
+    expr: &Constrained<ExprConstrained<'p>>,
+    state: &mut Assigned<'p>,
+    env: &mut Env<'_, 'p>,
+) -> Result<(), TypeError> {
+    match &expr.inner {
+        Expr::Lit { .. } => {}
+        Expr::Var { sym } => check_read(sym, state, env)?,
+        Expr::RefMut { sym } => {
+            // The variable is read before the call, and assigned when the call returns.
+            if state.reachable {
+                match env.locals.get(&sym.inner) {
+                    Some(Local { mutable: true, .. }) if state.definitely.contains(&sym.inner) => {}
+                    Some(Local { mutable: true, .. }) => {
+                        return Err(TypeError::MaybeUninitialized {
+                            sym: sym.inner.sym.to_string(),
+                            span: sym.meta,
+                        })
+                    }
+                    _ => return Err(TypeError::ModifyImmutable { span: sym.meta }),
+                }
+            }
+        }
+        Expr::UnaryOp { expr, .. } => check_expr(expr, state, env)?,
+        Expr::BinaryOp {
+            op: BinaryOp::LAnd | BinaryOp::LOr,
+            exprs: [lhs, rhs],
+        } => {
+            // The right-hand side is only evaluated for some values of the left-hand side.
+            check_expr(lhs, state, env)?;
+            let mut rhs_state = state.clone();
+            check_expr(rhs, &mut rhs_state, env)?;
+            *state = mem::replace(state, Assigned::unreachable()).join(rhs_state);
+        }
+        Expr::BinaryOp { exprs, .. } => {
+            for expr in exprs {
+                check_expr(expr, state, env)?;
+            }
+        }
+        Expr::Let {
+            sym,
+            mutable,
+            bnd,
+            bdy,
+            ..
+        } => {
+            if let Some(bnd) = bnd {
+                check_expr(bnd, state, env)?;
+            }
+            // A `let` inside a loop declares a fresh variable in every iteration, so assignments
+            // from earlier iterations do not carry over.
+            state.definitely.remove(&sym.inner);
+            state.possibly.remove(&sym.inner);
+            if bnd.is_some() {
+                state.assign(sym.inner);
+            }
+            env.locals.insert(
+                sym.inner,
+                Local {
+                    mutable: *mutable,
+                    deferred: bnd.is_none(),
+                },
+            );
+            check_expr(bdy, state, env)?;
+        }
+        Expr::If { cnd, thn, els } => {
+            check_expr(cnd, state, env)?;
+            let mut thn_state = state.clone();
+            check_expr(thn, &mut thn_state, env)?;
+            check_expr(els, state, env)?;
+            *state = thn_state.join(mem::replace(state, Assigned::unreachable()));
+        }
+        Expr::Apply { fun, args } => {
+            check_expr(fun, state, env)?;
+            for arg in args {
+                check_expr(arg, state, env)?;
+            }
+
+            // Nothing after a call to a function that never returns is reachable.
+            if matches!(&fun.inner, Expr::Var { sym } if env.diverging.contains(&sym.inner)) {
+                *state = Assigned::unreachable();
+            }
+        }
+        Expr::Loop { lbl, bdy } => {
+            // Assignments at the end of an iteration reach the start of the next one, so check
+            // the body until the variables that may be assigned at its start no longer change.
+            let mut head = state.clone();
+            let breaks = loop {
+                env.loops
+                    .push((lbl.as_ref().map(|lbl| lbl.inner), Jumps::default()));
+                let mut bdy_state = head.clone();
+                let result = check_expr(bdy, &mut bdy_state, env);
+                let (_, jumps) = env.loops.pop().unwrap();
+                result?;
+
+                let next = jumps
+                    .continues
+                    .into_iter()
+                    .fold(head.clone().join(bdy_state), Assigned::join);
+                if next.possibly == head.possibly {
+                    break jumps.breaks;
+                }
+                head = next;
+            };
+
+            *state = breaks
+                .into_iter()
+                .fold(Assigned::unreachable(), Assigned::join);
+        }
+        Expr::Break { lbl, bdy } => {
+            check_expr(bdy, state, env)?;
+            let jump = mem::replace(state, Assigned::unreachable());
+            env.jumps(lbl.as_ref().map(|lbl| lbl.inner))
+                .breaks
+                .push(jump);
+        }
+        Expr::Continue { lbl } => {
+            let jump = mem::replace(state, Assigned::unreachable());
+            env.jumps(lbl.as_ref().map(|lbl| lbl.inner))
+                .continues
+                .push(jump);
+        }
+        Expr::Return { bdy } => {
+            check_expr(bdy, state, env)?;
+            *state = Assigned::unreachable();
+        }
+        Expr::Seq { stmt, cnt } => {
+            check_expr(stmt, state, env)?;
+            check_expr(cnt, state, env)?;
+        }
+        Expr::Assign { sym, bnd } => {
+            check_expr(bnd, state, env)?;
+            check_assign(sym, state, env)?;
+            state.assign(sym.inner);
+        }
+        Expr::Struct { fields, base, .. } => {
+            // The base is evaluated before the fields, see `resolve`.
+            if let Some(base) = base {
+                check_expr(base, state, env)?;
+            }
+            for (_, field) in fields {
+                check_expr(field, state, env)?;
+            }
+        }
+        Expr::AccessField { strct, .. } => check_expr(strct, state, env)?,
+        Expr::Asm {
+            operands, instrs, ..
+        } => {
+            for (sym, _, bnd) in operands {
+                check_expr(bnd, state, env)?;
+                // Operands are local to the block, which may use them as scratch space.
+                env.locals.insert(
+                    sym.inner,
+                    Local {
+                        mutable: true,
+                        deferred: false,
+                    },
+                );
+                state.assign(sym.inner);
+            }
+
+            // Instructions that are jumped over are not executed, so if the block jumps, the
+            // variables it writes are only possibly assigned after it.
+            let jumps = instrs.iter().any(|item| {
+                matches!(
+                    item,
+                    AsmItem::Instr {
+                        instr: Instr::Jmp { .. } | Instr::Jcc { .. } | Instr::JmpTable { .. }
+                    }
+                )
+            });
+
+            for item in instrs {
+                let AsmItem::Instr { instr } = item else {
+                    continue;
+                };
+
+                let mut reads = Vec::new();
+                let mut writes = Vec::new();
+                handle_instr(instr, |arg, op| {
+                    if let VarArg::XVar { sym } = arg {
+                        match op {
+                            ReadWriteOp::Read => reads.push(sym.clone()),
+                            ReadWriteOp::Write => writes.push(sym.clone()),
+                            ReadWriteOp::ReadWrite => {
+                                reads.push(sym.clone());
+                                writes.push(sym.clone());
+                            }
+                        }
+                    }
+                });
+
+                for sym in &reads {
+                    check_read(sym, state, env)?;
+                }
+                for sym in &writes {
+                    check_assign(sym, state, env)?;
+                    if jumps {
+                        state.possibly.insert(sym.inner);
+                    } else {
+                        state.assign(sym.inner);
+                    }
+                }
+            }
+        }
+        Expr::Variant { .. } => todo!(),
+        Expr::Switch { .. } => todo!(),
+        Expr::Match { scrut, arms } => {
+            check_expr(scrut, state, env)?;
+
+            // Each arm is only taken if the guards of the arms before it did not hold, so
+            // assignments in those guards carry over to later arms.
+            let mut arms_state = Assigned::unreachable();
+            for arm in arms {
+                if let Some(guard) = &arm.guard {
+                    check_expr(guard, state, env)?;
+                }
+                let mut bdy_state = state.clone();
+                check_expr(&arm.bdy, &mut bdy_state, env)?;
+                arms_state = arms_state.join(bdy_state);
+            }
+            *state = arms_state;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the variable is assigned on every path to a read of it.
+fn check_read<'p>(
+    sym: &Spanned<UniqueSym<'p>>,
+    state: &Assigned<'p>,
+    env: &Env<'_, 'p>,
+) -> Result<(), TypeError> {
+    if state.reachable
+        && env.locals.contains_key(&sym.inner)
+        && !state.definitely.contains(&sym.inner)
+    {
+        return Err(TypeError::MaybeUninitialized {
+            sym: sym.inner.sym.to_string(),
+            span: sym.meta,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the variable is mutable, or that it is an immutable variable that was declared
+/// without a binding and has not been assigned on any path yet.
+fn check_assign<'p>(
+    sym: &Spanned<UniqueSym<'p>>,
+    state: &Assigned<'p>,
+    env: &Env<'_, 'p>,
+) -> Result<(), TypeError> {
+    if !state.reachable {
+        return Ok(());
+    }
+
+    // Globals are not in `locals`, and cannot be assigned either.
+    match env.locals.get(&sym.inner) {
+        Some(Local { mutable: true, .. }) => Ok(()),
+        Some(Local { deferred: true, .. }) if state.possibly.contains(&sym.inner) => {
+            Err(TypeError::ReassignImmutable {
+                sym: sym.inner.sym.to_string(),
+                span: sym.meta,
+            })
+        }
+        Some(Local { deferred: true, .. }) => Ok(()),
+        _ => Err(TypeError::ModifyImmutable { span: sym.meta }),
+    }
+}
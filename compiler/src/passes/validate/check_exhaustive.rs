@@ -0,0 +1,160 @@
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{Pattern, Span, Typed};
+use crate::passes::validate::error::TypeError;
+use crate::passes::validate::{MatchArmValidated, PatternValidated, TLit};
+use crate::utils::gen_sym::UniqueSym;
+use std::collections::{BTreeSet, HashMap};
+
+/// A pattern reduced to what matters for exhaustiveness.
+#[derive(Clone)]
+enum Pat {
+    /// Matches any value.
+    Any,
+    Bool(bool),
+    /// Matches integers from the first bound up to and including the second.
+    Int(i128, i128),
+    /// Patterns for the fields of a struct, in the order of the struct definition.
+    Struct(Vec<Pat>),
+}
+
+type Structs<'a, 'p> = &'a HashMap<UniqueSym<'p>, Vec<(&'p str, Type<UniqueSym<'p>>)>>;
+
+/// Checks that every value of type `typ` is matched by an arm without a guard.
+pub fn check_exhaustive<'p>(
+    arms: &[MatchArmValidated<'p>],
+    typ: &Type<UniqueSym<'p>>,
+    structs: Structs<'_, 'p>,
+    span: Span,
+) -> Result<(), TypeError> {
+    let rows = arms
+        .iter()
+        .filter(|arm| arm.guard.is_none())
+        .map(|arm| vec![reduce(&arm.pat, structs)])
+        .collect::<Vec<_>>();
+
+    // The match is exhaustive if no value is left that a wildcard arm after all others would catch.
+    if is_useful(&rows, &[Pat::Any], std::slice::from_ref(typ), structs) {
+        return Err(TypeError::NonExhaustiveMatch { span });
+    }
+
+    Ok(())
+}
+
+fn reduce<'p>(pat: &Typed<'p, PatternValidated<'p>>, structs: Structs<'_, 'p>) -> Pat {
+    match &pat.inner {
+        Pattern::Wildcard | Pattern::Binding { .. } | Pattern::Lit { val: TLit::Unit } => Pat::Any,
+        Pattern::Lit {
+            val: TLit::Bool { val },
+        } => Pat::Bool(*val),
+        Pattern::Lit { val } => Pat::Int(int(*val), int(*val)),
+        Pattern::Range { lo, hi, inclusive } => {
+            let hi = if *inclusive { int(*hi) } else { int(*hi) - 1 };
+            Pat::Int(int(*lo), hi)
+        }
+        Pattern::Struct { sym, fields, .. } => Pat::Struct(
+            structs[sym]
+                .iter()
+                .map(|(def_field, _)| {
+                    fields
+                        .iter()
+                        .find(|(field, _)| field == def_field)
+                        .map_or(Pat::Any, |(_, pat)| reduce(pat, structs))
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn int(val: TLit) -> i128 {
+    match val {
+        TLit::I64 { val } => i128::from(val),
+        TLit::U64 { val } => i128::from(val),
+        TLit::Bool { .. } | TLit::Unit => unreachable!(),
+    }
+}
+
+/// Returns whether some value matches the patterns in `q` (one for each column, typed by `typs`)
+/// but none of the `rows`.
+fn is_useful<'p>(
+    rows: &[Vec<Pat>],
+    q: &[Pat],
+    typs: &[Type<UniqueSym<'p>>],
+    structs: Structs<'_, 'p>,
+) -> bool {
+    let Some((q_head, q_tail)) = q.split_first() else {
+        return rows.is_empty();
+    };
+
+    match &typs[0] {
+        Type::Var { sym } => {
+            let fields = &structs[sym];
+            let expand = |pat: &Pat| match pat {
+                Pat::Struct(pats) => pats.clone(),
+                _ => vec![Pat::Any; fields.len()],
+            };
+
+            let rows = rows
+                .iter()
+                .map(|row| [expand(&row[0]), row[1..].to_vec()].concat())
+                .collect::<Vec<_>>();
+            let q = [expand(q_head), q_tail.to_vec()].concat();
+            let typs = fields
+                .iter()
+                .map(|(_, typ)| typ.clone())
+                .chain(typs[1..].iter().cloned())
+                .collect::<Vec<_>>();
+
+            is_useful(&rows, &q, &typs, structs)
+        }
+        Type::Bool => {
+            let vals = match q_head {
+                Pat::Bool(val) => vec![*val],
+                _ => vec![false, true],
+            };
+
+            vals.into_iter().any(|val| {
+                let rows = specialize(rows, |pat| matches!(pat, Pat::Bool(b) if *b == val));
+                is_useful(&rows, q_tail, &typs[1..], structs)
+            })
+        }
+        Type::I64 | Type::U64 => {
+            let (min, max) = match typs[0] {
+                Type::I64 => (i128::from(i64::MIN), i128::from(i64::MAX)),
+                _ => (0, i128::from(u64::MAX)),
+            };
+
+            // Split the integers into intervals that are either fully inside or fully outside
+            // each of the ranges.
+            let mut cuts = BTreeSet::from([min, max + 1]);
+            for pat in rows.iter().map(|row| &row[0]).chain([q_head]) {
+                if let Pat::Int(lo, hi) = pat {
+                    cuts.insert((*lo).clamp(min, max + 1));
+                    cuts.insert((*hi + 1).clamp(min, max + 1));
+                }
+            }
+            let cuts = cuts.into_iter().collect::<Vec<_>>();
+
+            cuts.windows(2).any(|window| {
+                let (lo, hi) = (window[0], window[1] - 1);
+                let covers = |pat: &Pat| match pat {
+                    Pat::Int(pat_lo, pat_hi) => *pat_lo <= lo && hi <= *pat_hi,
+                    _ => true,
+                };
+
+                covers(q_head) && is_useful(&specialize(rows, covers), q_tail, &typs[1..], structs)
+            })
+        }
+        Type::Unit | Type::Never | Type::Fn { .. } => {
+            is_useful(&specialize(rows, |_| true), q_tail, &typs[1..], structs)
+        }
+    }
+}
+
+/// Keeps the rows whose first pattern matches `covers` or is a wildcard, and removes their first
+/// column.
+fn specialize(rows: &[Vec<Pat>], covers: impl Fn(&Pat) -> bool) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter(|row| matches!(row[0], Pat::Any) || covers(&row[0]))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
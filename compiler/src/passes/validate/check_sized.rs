@@ -14,7 +14,7 @@ impl<'p> PrgConstrained<'p> {
             #[allow(clippy::single_match)]
             match def {
                 Def::TypeDef { sym, def } => match def {
-                    TypeDef::Struct { fields } => {
+                    TypeDef::Struct { fields, .. } => {
                         for (_, field) in fields {
                             match field {
                                 Type::I64
@@ -1,10 +1,11 @@
-use crate::passes::parse::{Constrained, Span, Spanned};
-use crate::passes::validate::constrain::expr;
+use crate::passes::parse::{Constrained, Expr, Meta, Span, Spanned};
 use crate::passes::validate::constrain::uncover_globals::Env;
+use crate::passes::validate::constrain::{expr, var};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{ExprConstrained, ExprUniquified, MetaConstrained};
 use crate::utils::expect::expect;
+use std::collections::HashMap;
 
 pub fn constrain_apply<'p>(
     env: &mut Env<'_, 'p>,
@@ -12,13 +13,77 @@ pub fn constrain_apply<'p>(
     fun: Spanned<ExprUniquified<'p>>,
     args: Vec<Spanned<ExprUniquified<'p>>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
-    let fun = expr::constrain_expr(fun, env)?;
-    let args: Vec<_> = args
+    // Which parameters are passed by reference, if the function is called directly.
+    let (fun, by_ref) = match fun.inner {
+        Expr::Var { sym } if env.by_ref.contains_key(&sym.inner) => {
+            let by_ref = env.by_ref[&sym.inner].clone();
+            (var::constrain_sym(env, fun.meta, sym)?, by_ref)
+        }
+        inner => {
+            let fun = Meta {
+                meta: fun.meta,
+                inner,
+            };
+            (expr::constrain_expr(fun, env)?, Vec::new())
+        }
+    };
+
+    let args: Vec<Constrained<ExprConstrained<'p>>> = args
         .into_iter()
-        .map(|arg| expr::constrain_expr(arg, env))
+        .enumerate()
+        .map(|(i, arg)| match arg.inner {
+            Expr::RefMut { sym } if by_ref.get(i) == Some(&true) => {
+                let var = var::constrain_sym(env, arg.meta, sym.clone())?;
+                Ok(Constrained {
+                    meta: var.meta,
+                    inner: ExprConstrained::RefMut { sym },
+                })
+            }
+            _ if by_ref.get(i) == Some(&true) => Err(TypeError::ArgExpectRef { span: arg.meta }),
+            inner => {
+                let arg = Meta {
+                    meta: arg.meta,
+                    inner,
+                };
+                expr::constrain_expr(arg, env)
+            }
+        })
         .collect::<Result<_, _>>()?;
 
-    let p_typ = env.uf.get(fun.meta.index).clone();
+    // References are lowered to copy-in/copy-out, under which writes through two references to
+    // the same variable would not be visible to each other.
+    let mut refs = HashMap::new();
+    for arg in &args {
+        if let ExprConstrained::RefMut { sym } = &arg.inner {
+            if let Some(prev_span) = refs.insert(sym.inner, sym.meta) {
+                return Err(TypeError::AliasingRefs {
+                    span1: prev_span,
+                    span2: sym.meta,
+                    sym: sym.inner.sym.to_string(),
+                });
+            }
+        }
+    }
+
+    if matches!(&fun.inner, ExprConstrained::Var { sym } if sym.inner == env.std["print"]) {
+        return constrain_print(env, span, fun, args);
+    }
+
+    let mut p_typ = env.uf.get(fun.meta.index).clone();
+
+    // A parameter without annotation that is called must be a function of its arguments.
+    if let PartialType::Unknown = p_typ {
+        p_typ = PartialType::Fn {
+            params: args
+                .iter()
+                .map(|_| env.uf.add(PartialType::Unknown))
+                .collect(),
+            typ: env.uf.add(PartialType::Unknown),
+        };
+        env.uf
+            .expect_partial_type(fun.meta.index, p_typ.clone(), |_, _| unreachable!())?;
+    }
+
     let PartialType::Fn { params, typ } = p_typ else {
         return Err(TypeError::MismatchedExpectFn {
             got: p_typ.to_string(env.uf),
@@ -54,3 +119,30 @@ pub fn constrain_apply<'p>(
         },
     })
 }
+
+/// `print` accepts an argument of any type and returns it. Whether the type can actually be printed
+/// is checked once all types are known.
+fn constrain_print<'p>(
+    env: &mut Env<'_, 'p>,
+    span: Span,
+    fun: Constrained<ExprConstrained<'p>>,
+    args: Vec<Constrained<ExprConstrained<'p>>>,
+) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
+    let [arg] = args.as_slice() else {
+        return Err(TypeError::ArgCountMismatch {
+            got: args.len(),
+            expected: 1,
+            span,
+        });
+    };
+    let index = arg.meta.index;
+    env.defaulted.push(index);
+
+    Ok(Constrained {
+        meta: MetaConstrained { span, index },
+        inner: ExprConstrained::Apply {
+            fun: Box::new(fun),
+            args,
+        },
+    })
+}
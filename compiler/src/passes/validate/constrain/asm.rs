@@ -1,23 +1,110 @@
-use crate::passes::parse::{Constrained, Span};
-
-use crate::passes::validate::constrain::uncover_globals::Env;
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{AsmOperand, Constrained, Lit, Span, Spanned};
+use crate::passes::select::{AsmItem, Instr, VarArg};
+use crate::passes::validate::constrain::expr;
+use crate::passes::validate::constrain::uncover_globals::{Env, EnvEntry};
 use crate::passes::validate::error::TypeError;
-use crate::passes::validate::partial_type::PartialType;
-use crate::passes::validate::{ExprConstrained, InstrUniquified, MetaConstrained};
+use crate::passes::validate::{AsmItemUniquified, ExprConstrained, MetaConstrained};
+use crate::utils::expect::expect;
+use crate::utils::gen_sym::UniqueSym;
 
 pub fn constrain_asm<'p>(
     env: &mut Env<'_, 'p>,
     span: Span,
-    instrs: Vec<InstrUniquified<'p>>,
+    operands: Vec<AsmOperand<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, Span>>,
+    typ: Type<Spanned<UniqueSym<'p>>>,
+    instrs: Vec<AsmItemUniquified<'p>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
-    //TODO mutability checks
-    //TODO check that all vars are nums
+    let operands = operands
+        .into_iter()
+        .map(|(sym, typ, bnd)| {
+            expect_scalar(&typ, bnd.meta)?;
+
+            let bnd = expr::constrain_expr(*bnd, env)?;
+            env.uf
+                .expect_type(bnd.meta.index, typ.clone(), |got, expect| {
+                    TypeError::MismatchedAsmOperand {
+                        expect,
+                        got,
+                        span_got: bnd.meta.span,
+                    }
+                })?;
+
+            env.scope.insert(
+                sym.inner,
+                EnvEntry::Type {
+                    typ: bnd.meta.index,
+                },
+            );
+
+            Ok((sym, typ, Box::new(bnd)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for item in &instrs {
+        if let AsmItem::Instr { instr } = item {
+            check_instr(env, span, instr)?;
+        }
+    }
+
+    expect_scalar(&typ, span)?;
+    let index = env.uf.type_to_index(typ.clone());
 
     Ok(Constrained {
-        meta: MetaConstrained {
-            span,
-            index: env.uf.add(PartialType::Unit),
+        meta: MetaConstrained { span, index },
+        inner: ExprConstrained::Asm {
+            operands,
+            typ,
+            instrs,
         },
-        inner: ExprConstrained::Asm { instrs },
     })
 }
+
+/// Values passed into and out of asm blocks must fit in a single register.
+fn expect_scalar(typ: &Type<Spanned<UniqueSym<'_>>>, span: Span) -> Result<(), TypeError> {
+    match typ {
+        Type::Var { .. } => Err(TypeError::AsmOperandNotScalar {
+            typ: typ.to_string(),
+            span,
+        }),
+        Type::I64 | Type::U64 | Type::Bool | Type::Unit | Type::Never | Type::Fn { .. } => Ok(()),
+    }
+}
+
+/// Checks that calls and loaded labels refer to functions, since any other symbol has no address
+/// in the emitted code, and that immediates fit in their encoding.
+fn check_instr<'p>(
+    env: &Env<'_, 'p>,
+    span: Span,
+    instr: &Instr<VarArg<Spanned<UniqueSym<'p>>>, Spanned<UniqueSym<'p>>>,
+) -> Result<(), TypeError> {
+    match instr {
+        Instr::CallqDirect { lbl: sym, .. } | Instr::LoadLbl { sym, .. } => expect(
+            env.fns.contains(&sym.inner),
+            TypeError::AsmTargetNotFunction {
+                sym: sym.inner.sym.to_string(),
+                span: sym.meta,
+            },
+        ),
+        // The count of a shift is a single byte, of which only the lowest 6 bits are used.
+        Instr::Shlq {
+            src: VarArg::Imm { val },
+            ..
+        }
+        | Instr::Sarq {
+            src: VarArg::Imm { val },
+            ..
+        } => expect(
+            (0..64).contains(val),
+            TypeError::AsmImmediateOutOfRange { val: *val, span },
+        ),
+        Instr::Imulq {
+            src: VarArg::Imm { val },
+            ..
+        } => expect(
+            i32::try_from(*val).is_ok(),
+            TypeError::AsmImmediateOutOfRange { val: *val, span },
+        ),
+        _ => Ok(()),
+    }
+}
@@ -5,7 +5,6 @@ use crate::passes::validate::error::TypeError;
 use crate::passes::validate::error::TypeError::MismatchedAssignBinding;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{ExprConstrained, ExprUniquified, MetaConstrained};
-use crate::utils::expect::expect;
 use crate::utils::gen_sym::UniqueSym;
 
 pub fn constrain_assign<'p>(
@@ -16,12 +15,11 @@ pub fn constrain_assign<'p>(
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
     let bnd = expr::constrain_expr(bnd, env)?;
 
-    let EnvEntry::Type { mutable, typ } = env.scope[&sym.inner] else {
+    // Whether the variable can be assigned is checked by `check_assigned`.
+    let EnvEntry::Type { typ } = env.scope[&sym.inner] else {
         return Err(TypeError::SymbolShouldBeVariable { span: sym.meta });
     };
 
-    expect(mutable, TypeError::ModifyImmutable { span: sym.meta })?;
-
     env.uf
         .expect_equal(typ, bnd.meta.index, |sym_typ, bnd_type| {
             MismatchedAssignBinding {
@@ -4,13 +4,15 @@ use crate::passes::validate::constrain::uncover_globals::Env;
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{ExprConstrained, ExprUniquified, MetaConstrained};
+use crate::utils::gen_sym::UniqueSym;
 
 pub fn constrain_break<'p>(
     env: &mut Env<'_, 'p>,
     span: Span,
+    lbl: Option<Spanned<UniqueSym<'p>>>,
     bdy: Spanned<ExprUniquified<'p>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
-    let Some(loop_type) = env.loop_type else {
+    let Some(loop_type) = env.loop_type(lbl.as_ref().map(|lbl| lbl.inner)) else {
         return Err(TypeError::BreakOutsideLoop { span });
     };
 
@@ -29,6 +31,9 @@ pub fn constrain_break<'p>(
             span,
             index: env.uf.add(PartialType::Never),
         },
-        inner: ExprConstrained::Break { bdy: Box::new(bdy) },
+        inner: ExprConstrained::Break {
+            lbl,
+            bdy: Box::new(bdy),
+        },
     })
 }
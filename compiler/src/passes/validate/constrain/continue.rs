@@ -1,16 +1,18 @@
-use crate::passes::parse::{Constrained, Span};
+use crate::passes::parse::{Constrained, Span, Spanned};
 use crate::passes::validate::constrain::uncover_globals::Env;
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{ExprConstrained, MetaConstrained};
 use crate::utils::expect::expect;
+use crate::utils::gen_sym::UniqueSym;
 
 pub fn constrain_continue<'p>(
     env: &mut Env<'_, 'p>,
     span: Span,
+    lbl: Option<Spanned<UniqueSym<'p>>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
     expect(
-        env.loop_type.is_some(),
+        env.loop_type(lbl.as_ref().map(|lbl| lbl.inner)).is_some(),
         TypeError::ContinueOutsideLoop { span },
     )?;
 
@@ -19,6 +21,6 @@ pub fn constrain_continue<'p>(
             span,
             index: env.uf.add(PartialType::Never),
         },
-        inner: ExprConstrained::Continue,
+        inner: ExprConstrained::Continue { lbl },
     })
 }
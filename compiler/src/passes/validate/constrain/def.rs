@@ -1,17 +1,23 @@
-use crate::passes::parse::{Def, Spanned};
+use crate::passes::parse::{Constrained, Def, Param, Spanned, TypeDef};
+use crate::passes::select::std_lib::Std;
 use crate::passes::validate::constrain::expr::constrain_expr;
+use crate::passes::validate::constrain::lit::lit_type;
 use crate::passes::validate::constrain::uncover_globals::{Env, EnvEntry};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
-use crate::passes::validate::{DefConstrained, DefUniquified};
+use crate::passes::validate::{DefConstrained, DefUniquified, MetaConstrained};
 use crate::utils::gen_sym::UniqueSym;
-use crate::utils::union_find::UnionFind;
-use std::collections::HashMap;
+use crate::utils::union_find::{UnionFind, UnionIndex};
+use std::collections::{HashMap, HashSet};
 
 pub fn constrain_def<'p>(
     def: DefUniquified<'p>,
     scope: &mut HashMap<UniqueSym<'p>, EnvEntry<'p>>,
     uf: &mut UnionFind<PartialType<'p>>,
+    std: &Std<'p>,
+    defaulted: &mut Vec<UnionIndex>,
+    by_ref: &HashMap<UniqueSym<'p>, Vec<bool>>,
+    fns: &HashSet<UniqueSym<'p>>,
 ) -> Result<DefConstrained<'p>, TypeError> {
     let def = match def {
         Def::Fn {
@@ -19,28 +25,41 @@ pub fn constrain_def<'p>(
             params,
             typ,
             bdy,
+            inline,
         } => {
+            // The signature was added to the scope by `uncover_globals`.
+            let EnvEntry::Type { typ: fn_index } = scope[&sym.inner] else {
+                unreachable!("Functions are uncovered as variables.");
+            };
+            let PartialType::Fn {
+                params: param_indices,
+                typ: return_index,
+            } = uf.get(fn_index).clone()
+            else {
+                unreachable!("Functions are uncovered with a function type.");
+            };
+
             // Put function parameters in scope.
-            scope.extend(params.iter().map(|p| {
-                (
-                    p.sym.inner,
-                    EnvEntry::Type {
-                        mutable: p.mutable,
-                        typ: uf.type_to_index(p.typ.clone()),
-                    },
-                )
-            }));
+            scope.extend(
+                params
+                    .iter()
+                    .zip(&param_indices)
+                    .map(|(p, &typ)| (p.sym.inner, EnvEntry::Type { typ })),
+            );
 
             // Add return type to env and keep it for error handling.
-            let return_index = uf.type_to_index(typ.clone());
             let mut env = Env {
                 uf,
                 scope,
-                loop_type: None,
+                loop_types: Vec::new(),
                 return_type: &Spanned {
                     inner: return_index,
                     meta: sym.meta,
                 }, // TODO replace sym.meta with return type index
+                std,
+                defaulted,
+                by_ref,
+                fns,
             };
 
             // Constrain body of function.
@@ -57,13 +76,56 @@ pub fn constrain_def<'p>(
             })?;
 
             Def::Fn {
+                params: params
+                    .into_iter()
+                    .zip(param_indices)
+                    .map(|(param, index)| Param {
+                        typ: Constrained {
+                            meta: MetaConstrained {
+                                span: param.sym.meta,
+                                index,
+                            },
+                            inner: param.typ,
+                        },
+                        sym: param.sym,
+                        mutable: param.mutable,
+                        by_ref: param.by_ref,
+                    })
+                    .collect(),
+                typ: Constrained {
+                    meta: MetaConstrained {
+                        span: sym.meta,
+                        index: return_index,
+                    },
+                    inner: typ,
+                },
                 sym,
-                params,
                 bdy,
-                typ,
+                inline,
             }
         }
-        Def::TypeDef { sym, def } => Def::TypeDef { sym, def },
+        Def::TypeDef { sym, def } => {
+            // Default values of fields must have the type of their field.
+            if let TypeDef::Struct { fields, defaults } = &def {
+                for (default_sym, val) in defaults {
+                    let (field_sym, field_typ) = fields
+                        .iter()
+                        .find(|(field_sym, _)| field_sym.inner == default_sym.inner)
+                        .expect("The parser only allows defaults on declared fields.");
+                    let index = uf.add(lit_type(val));
+                    uf.expect_type(index, field_typ.clone(), |got, expect| {
+                        TypeError::MismatchedStructField {
+                            expect,
+                            got,
+                            span_expected: field_sym.meta,
+                            span_got: default_sym.meta,
+                        }
+                    })?;
+                }
+            }
+
+            Def::TypeDef { sym, def }
+        }
     };
 
     Ok(def)
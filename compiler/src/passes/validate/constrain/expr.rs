@@ -11,6 +11,7 @@ use crate::passes::validate::constrain::r#continue::constrain_continue;
 use crate::passes::validate::constrain::r#if::constrain_if;
 use crate::passes::validate::constrain::r#let::constrain_let;
 use crate::passes::validate::constrain::r#loop::constrain_loop;
+use crate::passes::validate::constrain::r#match::constrain_match;
 use crate::passes::validate::constrain::r#return::constrain_return;
 use crate::passes::validate::constrain::r#struct::constrain_struct;
 use crate::passes::validate::constrain::seq::constrain_seq;
@@ -29,6 +30,7 @@ pub fn constrain_expr<'p>(
     match expr.inner {
         Expr::Lit { val } => constrain_lit(env, span, val),
         Expr::Var { sym } => constrain_var(env, span, sym),
+        Expr::RefMut { .. } => Err(TypeError::ArgUnexpectedRef { span }),
         Expr::UnaryOp { op, expr } => constrain_unary_op(env, span, op, *expr),
         Expr::BinaryOp {
             op,
@@ -40,19 +42,26 @@ pub fn constrain_expr<'p>(
             typ,
             bnd,
             bdy,
-        } => constrain_let(env, span, sym, mutable, typ, *bnd, *bdy),
+        } => constrain_let(env, span, sym, mutable, typ, bnd.map(|bnd| *bnd), *bdy),
         Expr::If { cnd, thn, els } => constrain_if(env, span, *cnd, *thn, *els),
         Expr::Apply { fun, args } => constrain_apply(env, span, *fun, args),
-        Expr::Loop { bdy } => constrain_loop(env, span, *bdy),
-        Expr::Break { bdy } => constrain_break(env, span, *bdy),
-        Expr::Continue => constrain_continue(env, span),
+        Expr::Loop { lbl, bdy } => constrain_loop(env, span, lbl, *bdy),
+        Expr::Break { lbl, bdy } => constrain_break(env, span, lbl, *bdy),
+        Expr::Continue { lbl } => constrain_continue(env, span, lbl),
         Expr::Return { bdy } => constrain_return(env, span, *bdy),
         Expr::Seq { stmt, cnt } => constrain_seq(env, span, *stmt, *cnt),
         Expr::Assign { sym, bnd } => constrain_assign(env, span, sym, *bnd),
-        Expr::Struct { sym, fields } => constrain_struct(env, span, sym, fields),
+        Expr::Struct { sym, fields, base } => {
+            constrain_struct(env, span, sym, fields, base.map(|base| *base))
+        }
         Expr::AccessField { strct, field } => constrain_access_field(env, span, *strct, field),
-        Expr::Asm { instrs } => constrain_asm(env, span, instrs),
+        Expr::Asm {
+            operands,
+            typ,
+            instrs,
+        } => constrain_asm(env, span, operands, typ, instrs),
         Expr::Variant { .. } => todo!(),
         Expr::Switch { .. } => todo!(),
+        Expr::Match { scrut, arms } => constrain_match(env, span, *scrut, arms),
     }
 }
@@ -0,0 +1,200 @@
+use crate::passes::parse::{Def, Expr, Spanned};
+use crate::passes::select::{AsmItem, Instr};
+use crate::passes::validate::constrain::uncover_globals::EnvEntry;
+use crate::passes::validate::partial_type::PartialType;
+use crate::passes::validate::{DefUniquified, ExprUniquified};
+use crate::utils::gen_sym::UniqueSym;
+use crate::utils::union_find::{UnionFind, UnionIndex};
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use std::collections::{HashMap, HashSet};
+
+/// Returns the functions grouped by the strongly connected components of the call graph, with
+/// callees before their callers, together with the functions that asm blocks refer to.
+pub fn call_graph<'p>(
+    defs: &[DefUniquified<'p>],
+) -> (Vec<Vec<UniqueSym<'p>>>, HashSet<UniqueSym<'p>>) {
+    let mut graph = DiGraphMap::new();
+    let mut from_asm = HashSet::new();
+
+    for def in defs {
+        if let Def::Fn { sym, .. } = def {
+            graph.add_node(sym.inner);
+        }
+    }
+
+    for def in defs {
+        let Def::Fn { sym, bdy, .. } = def else {
+            continue;
+        };
+
+        let mut refs = Vec::new();
+        collect_refs(bdy, &mut refs);
+        for (target, asm) in refs {
+            if graph.contains_node(target) {
+                graph.add_edge(sym.inner, target, ());
+                if asm {
+                    from_asm.insert(target);
+                }
+            }
+        }
+    }
+
+    // Tarjan's algorithm finds the components in reverse topological order.
+    (tarjan_scc(&graph), from_asm)
+}
+
+/// Collects the symbols that the expression refers to, and whether they are referred to from an
+/// asm block.
+fn collect_refs<'p>(expr: &Spanned<ExprUniquified<'p>>, refs: &mut Vec<(UniqueSym<'p>, bool)>) {
+    match &expr.inner {
+        Expr::Lit { .. } | Expr::RefMut { .. } | Expr::Continue { .. } => {}
+        Expr::Var { sym } => refs.push((sym.inner, false)),
+        Expr::UnaryOp { expr, .. }
+        | Expr::Loop { bdy: expr, .. }
+        | Expr::Break { bdy: expr, .. }
+        | Expr::Return { bdy: expr }
+        | Expr::Assign { bnd: expr, .. }
+        | Expr::Variant { bdy: expr, .. }
+        | Expr::AccessField { strct: expr, .. } => collect_refs(expr, refs),
+        Expr::BinaryOp {
+            exprs: [e1, e2], ..
+        }
+        | Expr::Seq { stmt: e1, cnt: e2 } => {
+            collect_refs(e1, refs);
+            collect_refs(e2, refs);
+        }
+        Expr::Let { bnd, bdy, .. } => {
+            if let Some(bnd) = bnd {
+                collect_refs(bnd, refs);
+            }
+            collect_refs(bdy, refs);
+        }
+        Expr::If { cnd, thn, els } => {
+            collect_refs(cnd, refs);
+            collect_refs(thn, refs);
+            collect_refs(els, refs);
+        }
+        Expr::Apply { fun, args } => {
+            collect_refs(fun, refs);
+            for arg in args {
+                collect_refs(arg, refs);
+            }
+        }
+        Expr::Struct { fields, base, .. } => {
+            for (_, field) in fields {
+                collect_refs(field, refs);
+            }
+            if let Some(base) = base {
+                collect_refs(base, refs);
+            }
+        }
+        Expr::Switch { enm, arms } => {
+            collect_refs(enm, refs);
+            for (_, _, arm) in arms {
+                collect_refs(arm, refs);
+            }
+        }
+        Expr::Match { scrut, arms } => {
+            collect_refs(scrut, refs);
+            for arm in arms {
+                if let Some(guard) = &arm.guard {
+                    collect_refs(guard, refs);
+                }
+                collect_refs(&arm.bdy, refs);
+            }
+        }
+        Expr::Asm {
+            operands, instrs, ..
+        } => {
+            for (_, _, bnd) in operands {
+                collect_refs(bnd, refs);
+            }
+            for item in instrs {
+                if let AsmItem::Instr {
+                    instr: Instr::CallqDirect { lbl: sym, .. } | Instr::LoadLbl { sym, .. },
+                } = item
+                {
+                    refs.push((sym.inner, true));
+                }
+            }
+        }
+    }
+}
+
+/// Generalizes the signatures of the functions in a strongly connected component of the call
+/// graph over the types that are still unknown. Within the component the signatures are shared, so
+/// recursive calls use them at a single type.
+pub fn generalize<'p>(
+    scc: &[UniqueSym<'p>],
+    scope: &mut HashMap<UniqueSym<'p>, EnvEntry<'p>>,
+    uf: &mut UnionFind<PartialType<'p>>,
+) {
+    for sym in scc {
+        let EnvEntry::Type { typ } = scope[sym] else {
+            unreachable!("Functions are uncovered as variables.");
+        };
+
+        let mut vars = Vec::new();
+        collect_vars(typ, uf, &mut vars);
+        if !vars.is_empty() {
+            scope.insert(*sym, EnvEntry::Generic { typ, vars });
+        }
+    }
+}
+
+/// Collects the types in `index` that are not fully known yet.
+fn collect_vars(index: UnionIndex, uf: &mut UnionFind<PartialType>, vars: &mut Vec<UnionIndex>) {
+    let root = uf.find(index);
+    match uf.get(root).clone() {
+        PartialType::Unknown | PartialType::Int => {
+            if !vars.contains(&root) {
+                vars.push(root);
+            }
+        }
+        PartialType::Fn { params, typ } => {
+            for param in params {
+                collect_vars(param, uf, vars);
+            }
+            collect_vars(typ, uf, vars);
+        }
+        PartialType::I64
+        | PartialType::U64
+        | PartialType::Bool
+        | PartialType::Unit
+        | PartialType::Never
+        | PartialType::Var { .. } => {}
+    }
+}
+
+/// Returns a copy of the type at `index`, in which the generalized `vars` are replaced by fresh
+/// types. The fresh types are recorded in `fresh`.
+pub fn instantiate<'p>(
+    index: UnionIndex,
+    vars: &[UnionIndex],
+    fresh: &mut HashMap<UnionIndex, UnionIndex>,
+    uf: &mut UnionFind<PartialType<'p>>,
+) -> UnionIndex {
+    let root = uf.find(index);
+    if vars.contains(&root) {
+        if let Some(&var) = fresh.get(&root) {
+            return var;
+        }
+        let typ = uf.get(root).clone();
+        let var = uf.add(typ);
+        fresh.insert(root, var);
+        return var;
+    }
+
+    match uf.get(root).clone() {
+        PartialType::Fn { params, typ } => {
+            let params = params
+                .into_iter()
+                .map(|param| instantiate(param, vars, fresh, uf))
+                .collect();
+            let typ = instantiate(typ, vars, fresh, uf);
+            uf.add(PartialType::Fn { params, typ })
+        }
+        _ => root,
+    }
+}
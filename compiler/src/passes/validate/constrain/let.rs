@@ -12,28 +12,25 @@ pub fn constrain_let<'p>(
     sym: Spanned<UniqueSym<'p>>,
     mutable: bool,
     typ: Option<Type<Spanned<UniqueSym<'p>>>>,
-    bnd: Spanned<ExprUniquified<'p>>,
+    bnd: Option<Spanned<ExprUniquified<'p>>>,
     bdy: Spanned<ExprUniquified<'p>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
-    let bnd = expr::constrain_expr(bnd, env)?;
+    let bnd = bnd.map(|bnd| expr::constrain_expr(bnd, env)).transpose()?;
 
-    if let Some(typ) = &typ {
-        env.uf.expect_type(bnd.meta.index, typ.clone(), |got, _| {
+    let index = match (&bnd, &typ) {
+        (Some(bnd), Some(typ)) => env.uf.expect_type(bnd.meta.index, typ.clone(), |got, _| {
             TypeError::MismatchedLetBinding {
                 got,
                 span_expected: (0, 0), //TODO span of typ
                 span_got: bnd.meta.span,
             }
-        })?;
-    }
+        })?,
+        (Some(bnd), None) => bnd.meta.index,
+        (None, Some(typ)) => env.uf.type_to_index(typ.clone()),
+        (None, None) => unreachable!("The parser only allows omitting the binding with a type."),
+    };
 
-    env.scope.insert(
-        sym.inner,
-        EnvEntry::Type {
-            mutable,
-            typ: bnd.meta.index,
-        },
-    );
+    env.scope.insert(sym.inner, EnvEntry::Type { typ: index });
     let bdy = expr::constrain_expr(bdy, env)?;
 
     Ok(Constrained {
@@ -45,7 +42,7 @@ pub fn constrain_let<'p>(
             sym,
             mutable,
             typ,
-            bnd: Box::new(bnd),
+            bnd: bnd.map(Box::new),
             bdy: Box::new(bdy),
         },
     })
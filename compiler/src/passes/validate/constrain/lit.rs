@@ -9,20 +9,22 @@ pub fn constrain_lit<'p>(
     span: Span,
     val: Lit<'p>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
-    // Get the type of the literal.
-    let typ = match &val {
-        Lit::Int { val } if val.ends_with("i64") => PartialType::I64,
-        Lit::Int { val } if val.ends_with("u64") => PartialType::U64,
-        Lit::Int { .. } => PartialType::Int,
-        Lit::Bool { .. } => PartialType::Bool,
-        Lit::Unit => PartialType::Unit,
-    };
-
-    // Add the type to the constraints.
-    let index = env.uf.add(typ);
+    // Add the type of the literal to the constraints.
+    let index = env.uf.add(lit_type(&val));
 
     Ok(Constrained {
         meta: MetaConstrained { span, index },
         inner: ExprConstrained::Lit { val },
     })
 }
+
+/// Returns the type of the literal, where integers without a suffix can be any integer type.
+pub fn lit_type<'p>(val: &Lit) -> PartialType<'p> {
+    match val {
+        Lit::Int { val } if val.ends_with("i64") => PartialType::I64,
+        Lit::Int { val } if val.ends_with("u64") => PartialType::U64,
+        Lit::Int { .. } => PartialType::Int,
+        Lit::Bool { .. } => PartialType::Bool,
+        Lit::Unit => PartialType::Unit,
+    }
+}
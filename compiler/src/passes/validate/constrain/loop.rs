@@ -4,28 +4,29 @@ use crate::passes::validate::constrain::uncover_globals::Env;
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{ExprConstrained, ExprUniquified, MetaConstrained};
+use crate::utils::gen_sym::UniqueSym;
 
 pub fn constrain_loop<'p>(
     env: &mut Env<'_, 'p>,
     span: Span,
+    lbl: Option<Spanned<UniqueSym<'p>>>,
     bdy: Spanned<ExprUniquified<'p>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
     let loop_type = env.uf.add(PartialType::Never);
 
-    let mut env = Env {
-        uf: env.uf,
-        scope: env.scope,
-        loop_type: Some(loop_type),
-        return_type: env.return_type,
-    };
-
-    let bdy = expr::constrain_expr(bdy, &mut env)?;
+    env.loop_types
+        .push((lbl.as_ref().map(|lbl| lbl.inner), loop_type));
+    let bdy = expr::constrain_expr(bdy, env);
+    env.loop_types.pop();
 
     Ok(Constrained {
         meta: MetaConstrained {
             span,
             index: loop_type,
         },
-        inner: ExprConstrained::Loop { bdy: Box::new(bdy) },
+        inner: ExprConstrained::Loop {
+            lbl,
+            bdy: Box::new(bdy?),
+        },
     })
 }
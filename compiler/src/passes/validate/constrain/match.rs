@@ -0,0 +1,169 @@
+use crate::passes::parse::types::Type;
+use crate::passes::parse::{Constrained, MatchArm, Meta, Pattern, Span, Spanned, TypeDef};
+use crate::passes::validate::constrain::expr;
+use crate::passes::validate::constrain::lit::lit_type;
+use crate::passes::validate::constrain::uncover_globals::{Env, EnvEntry};
+use crate::passes::validate::error::TypeError;
+use crate::passes::validate::partial_type::PartialType;
+use crate::passes::validate::{
+    ExprConstrained, ExprUniquified, MatchArmUniquified, MetaConstrained, PatternConstrained,
+    PatternUniquified,
+};
+use crate::utils::expect::expect;
+use crate::utils::union_find::UnionIndex;
+use std::collections::HashSet;
+
+pub fn constrain_match<'p>(
+    env: &mut Env<'_, 'p>,
+    span: Span,
+    scrut: Spanned<ExprUniquified<'p>>,
+    arms: Vec<MatchArmUniquified<'p>>,
+) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
+    let scrut = expr::constrain_expr(scrut, env)?;
+
+    // A match without arms never produces a value.
+    let mut out_index = env.uf.add(PartialType::Never);
+    let mut first_span = None;
+
+    let arms = arms
+        .into_iter()
+        .map(|arm| {
+            let pat = constrain_pattern(arm.pat, scrut.meta.index, env)?;
+
+            let guard = arm
+                .guard
+                .map(|guard| {
+                    let guard = expr::constrain_expr(guard, env)?;
+                    env.uf.expect_type(guard.meta.index, Type::Bool, |got, _| {
+                        TypeError::IfExpectBool {
+                            got,
+                            span_got: guard.meta.span,
+                        }
+                    })?;
+                    Ok(guard)
+                })
+                .transpose()?;
+
+            let bdy = expr::constrain_expr(arm.bdy, env)?;
+            let span_expected = *first_span.get_or_insert(bdy.meta.span);
+            out_index = env
+                .uf
+                .expect_equal(out_index, bdy.meta.index, |expect, got| {
+                    TypeError::MatchExpectEqual {
+                        expect,
+                        got,
+                        span_expected,
+                        span_got: bdy.meta.span,
+                    }
+                })?;
+
+            Ok(MatchArm { pat, guard, bdy })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Constrained {
+        meta: MetaConstrained {
+            span,
+            index: out_index,
+        },
+        inner: ExprConstrained::Match {
+            scrut: Box::new(scrut),
+            arms,
+        },
+    })
+}
+
+/// Constrains a pattern that is matched against a value whose type is at `index`.
+fn constrain_pattern<'p>(
+    pat: Spanned<PatternUniquified<'p>>,
+    index: UnionIndex,
+    env: &mut Env<'_, 'p>,
+) -> Result<Constrained<PatternConstrained<'p>>, TypeError> {
+    let span = pat.meta;
+    let expect_pattern = |env: &mut Env<'_, 'p>, typ| {
+        let typ_index = env.uf.add(typ);
+        env.uf.expect_equal(index, typ_index, |expect, got| {
+            TypeError::MismatchedPattern { expect, got, span }
+        })
+    };
+
+    let inner = match pat.inner {
+        Pattern::Wildcard => Pattern::Wildcard,
+        Pattern::Binding { sym } => {
+            env.scope.insert(sym.inner, EnvEntry::Type { typ: index });
+            Pattern::Binding { sym }
+        }
+        Pattern::Lit { val } => {
+            expect_pattern(env, lit_type(&val))?;
+            Pattern::Lit { val }
+        }
+        Pattern::Range { lo, hi, inclusive } => {
+            expect_pattern(env, lit_type(&lo))?;
+            expect_pattern(env, lit_type(&hi))?;
+            Pattern::Range { lo, hi, inclusive }
+        }
+        Pattern::Struct { sym, fields, rest } => {
+            let EnvEntry::Def {
+                def: TypeDef::Struct {
+                    fields: def_fields, ..
+                },
+            } = &env.scope[&sym.inner]
+            else {
+                return Err(TypeError::SymbolShouldBeStruct { span: sym.meta });
+            };
+            let def_fields = def_fields.clone();
+
+            expect_pattern(env, PartialType::Var { sym: sym.inner })?;
+
+            // Set to keep track of the fields in the pattern. Used to make sure no duplicates occur.
+            let mut seen_fields = HashSet::new();
+
+            let fields = fields
+                .into_iter()
+                .map(|(field_sym, field_pat)| {
+                    expect(
+                        seen_fields.insert(field_sym.inner),
+                        TypeError::ConstructDuplicateField {
+                            sym: field_sym.to_string(),
+                            span: field_sym.meta,
+                        },
+                    )?;
+
+                    let Some((_, def_typ)) = def_fields
+                        .iter()
+                        .find(|(def_sym, _)| def_sym.inner == field_sym.inner)
+                    else {
+                        return Err(TypeError::UnknownStructField {
+                            sym: field_sym.to_string(),
+                            span: field_sym.meta,
+                        });
+                    };
+
+                    let field_index = env.uf.type_to_index(def_typ.clone());
+                    Ok((field_sym, constrain_pattern(field_pat, field_index, env)?))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Unless they are explicitly ignored, all fields from the struct definition must be present.
+            if !rest {
+                for (def_sym, _) in &def_fields {
+                    expect(
+                        seen_fields.contains(def_sym.inner),
+                        TypeError::ConstructMissingField {
+                            sym: def_sym.to_string(),
+                            struct_span: sym.meta,
+                            def_span: def_sym.meta,
+                        },
+                    )?;
+                }
+            }
+
+            Pattern::Struct { sym, fields, rest }
+        }
+    };
+
+    Ok(Meta {
+        meta: MetaConstrained { span, index },
+        inner,
+    })
+}
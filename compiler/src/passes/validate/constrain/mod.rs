@@ -1,13 +1,15 @@
 use crate::passes::parse::types::Type;
-use crate::passes::parse::Spanned;
+use crate::passes::parse::{Def, Spanned};
 use crate::passes::validate::constrain::def::constrain_def;
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
-use crate::passes::validate::uniquify::PrgUniquified;
+use crate::passes::validate::uniquify::{PrgUniquified, BUILT_INS};
 use crate::passes::validate::{partial_type, PrgConstrained};
 use crate::utils::gen_sym::UniqueSym;
 use crate::utils::union_find::{UnionFind, UnionIndex};
-use uncover_globals::uncover_globals;
+use generalize::{call_graph, generalize};
+use std::collections::{HashMap, HashSet};
+use uncover_globals::{uncover_globals, EnvEntry};
 
 mod access_field;
 mod apply;
@@ -18,10 +20,12 @@ mod r#break;
 mod r#continue;
 pub mod def;
 pub mod expr;
+mod generalize;
 mod r#if;
 mod r#let;
 mod lit;
 mod r#loop;
+mod r#match;
 mod r#return;
 mod seq;
 mod r#struct;
@@ -33,16 +37,106 @@ impl<'p> PrgUniquified<'p> {
     pub fn constrain(self) -> Result<PrgConstrained<'p>, TypeError> {
         let mut uf = UnionFind::new();
         let mut scope = uncover_globals(&self, &mut uf)?;
+        let mut defaulted = Vec::new();
+
+        let by_ref = self
+            .defs
+            .iter()
+            .filter_map(|def| match def {
+                Def::Fn { sym, params, .. } if params.iter().any(|param| param.by_ref) => {
+                    Some((sym.inner, params.iter().map(|param| param.by_ref).collect()))
+                }
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        let fns = self
+            .defs
+            .iter()
+            .filter_map(|def| match def {
+                Def::Fn { sym, .. } => Some(sym.inner),
+                _ => None,
+            })
+            .chain(BUILT_INS.keys().map(|sym| self.std[sym]))
+            .collect();
+
+        let (sccs, from_asm) = call_graph(&self.defs);
+
+        // Type definitions do not depend on functions, so they are constrained right away.
+        let mut defs = HashMap::new();
+        let mut fn_defs = HashMap::new();
+        for def in self.defs {
+            let sym = def.sym().inner;
+            match def {
+                Def::Fn { .. } => {
+                    fn_defs.insert(sym, def);
+                }
+                Def::TypeDef { .. } => {
+                    let def = constrain_def(
+                        def,
+                        &mut scope,
+                        &mut uf,
+                        &self.std,
+                        &mut defaulted,
+                        &by_ref,
+                        &fns,
+                    )?;
+                    defs.insert(sym, def);
+                }
+            }
+        }
+
+        // Functions are constrained callees first, so that their signatures can be generalized
+        // before they are used. Signatures of functions whose address is taken by asm blocks, or
+        // that have parameters passed by reference, stay monomorphic: both need a single copy.
+        for scc in sccs {
+            for sym in &scc {
+                let def = constrain_def(
+                    fn_defs.remove(sym).unwrap(),
+                    &mut scope,
+                    &mut uf,
+                    &self.std,
+                    &mut defaulted,
+                    &by_ref,
+                    &fns,
+                )?;
+                defs.insert(*sym, def);
+            }
+
+            if scc.iter().all(|sym| {
+                *sym != self.entry && !from_asm.contains(sym) && !by_ref.contains_key(sym)
+            }) {
+                generalize(&scc, &mut scope, &mut uf);
+            }
+        }
+
+        let mut generic = HashSet::new();
+        let mut vars = HashSet::new();
+        for (sym, entry) in &scope {
+            if let EnvEntry::Generic { vars: fn_vars, .. } = entry {
+                generic.insert(*sym);
+                vars.extend(fn_vars.iter().copied());
+            }
+        }
+
+        // Integers that are only printed or passed to generalized functions have no other
+        // constraints, so default them to `I64`. Signatures are inferred from every function, so
+        // this can only be done afterwards. The types that functions are generalized over are
+        // left alone, since they are only known for each use of the function.
+        for index in defaulted {
+            let index = uf.find(index);
+            if vars.contains(&index) {
+                continue;
+            }
+            if let PartialType::Int = uf.get(index) {
+                uf.expect_partial_type(index, PartialType::I64, |_, _| unreachable!())?;
+            }
+        }
 
         Ok(PrgConstrained {
-            defs: self
-                .defs
-                .into_iter()
-                .map(|def| {
-                    constrain_def(def, &mut scope, &mut uf).map(|def| (def.sym().inner, def))
-                })
-                .collect::<Result<_, _>>()?,
+            defs,
             entry: self.entry,
+            generic,
             uf,
             std: self.std,
         })
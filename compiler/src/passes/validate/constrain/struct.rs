@@ -1,11 +1,13 @@
 use crate::passes::parse::{Constrained, Span, Spanned, TypeDef};
 use crate::passes::validate::constrain::expr;
+use crate::passes::validate::constrain::lit::lit_type;
 use crate::passes::validate::constrain::uncover_globals::{Env, EnvEntry};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{ExprConstrained, ExprUniquified, MetaConstrained};
 use crate::utils::expect::expect;
 use crate::utils::gen_sym::UniqueSym;
+use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
 
 pub fn constrain_struct<'p>(
@@ -13,15 +15,20 @@ pub fn constrain_struct<'p>(
     span: Span,
     sym: Spanned<UniqueSym<'p>>,
     fields: Vec<(Spanned<&'p str>, Spanned<ExprUniquified<'p>>)>,
+    base: Option<Spanned<ExprUniquified<'p>>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
     // Get the `EnvEntry` from the scope.
     // This should exist after uniquify, but could potentially not be a struct definition.
     let EnvEntry::Def {
-        def: TypeDef::Struct { fields: def_fields },
+        def: TypeDef::Struct {
+            fields: def_fields,
+            defaults,
+        },
     } = &env.scope[&sym.inner]
     else {
         return Err(TypeError::SymbolShouldBeStruct { span });
     };
+    let defaults = defaults.clone();
 
     let def_fields = def_fields
         .iter()
@@ -66,22 +73,75 @@ pub fn constrain_struct<'p>(
         })
         .collect::<Result<Vec<_>, _>>()?;
 
-    // Verify that all fields from the struct definition are present.
-    for (def_sym, (def_span, _)) in def_fields {
-        expect(
-            seen_fields.contains(def_sym),
-            TypeError::ConstructMissingField {
-                sym: def_sym.to_string(),
-                struct_span: sym.meta,
-                def_span,
-            },
-        )?;
+    // The remaining fields are copied from the base by `resolve`.
+    let base = base
+        .map(|base| {
+            let base = expr::constrain_expr(base, env)?;
+            env.uf.expect_partial_type(
+                base.meta.index,
+                PartialType::Var { sym: sym.inner },
+                |got, expect| TypeError::MismatchedStructBase {
+                    expect,
+                    got,
+                    span: base.meta.span,
+                },
+            )?;
+            Ok(base)
+        })
+        .transpose()?;
+
+    let mut fields = fields;
+    if base.is_none() {
+        // Fields that are left out take their default value, all other fields must be present.
+        for (def_sym, (def_span, def_typ)) in
+            def_fields.into_iter().sorted_by_key(|(_, (span, _))| *span)
+        {
+            if seen_fields.contains(def_sym) {
+                continue;
+            }
+
+            let Some((field_sym, val)) = defaults
+                .iter()
+                .find(|(field_sym, _)| field_sym.inner == def_sym)
+            else {
+                return Err(TypeError::ConstructMissingField {
+                    sym: def_sym.to_string(),
+                    struct_span: sym.meta,
+                    def_span,
+                });
+            };
+
+            let index = env.uf.add(lit_type(val));
+            env.uf.expect_type(index, def_typ, |field_typ, def_typ| {
+                TypeError::MismatchedStructField {
+                    expect: def_typ,
+                    got: field_typ,
+                    span_expected: def_span,
+                    span_got: field_sym.meta,
+                }
+            })?;
+
+            fields.push((
+                field_sym.clone(),
+                Constrained {
+                    meta: MetaConstrained {
+                        span: field_sym.meta,
+                        index,
+                    },
+                    inner: ExprConstrained::Lit { val: val.clone() },
+                },
+            ));
+        }
     }
 
     let index = env.uf.add(PartialType::Var { sym: sym.inner });
 
     Ok(Constrained {
         meta: MetaConstrained { span, index },
-        inner: ExprConstrained::Struct { sym, fields },
+        inner: ExprConstrained::Struct {
+            sym,
+            fields,
+            base: base.map(Box::new),
+        },
     })
 }
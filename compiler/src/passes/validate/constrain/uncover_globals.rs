@@ -1,5 +1,6 @@
 use crate::passes::parse::types::Type;
-use crate::passes::parse::{Def, Spanned, TypeDef};
+use crate::passes::parse::{Def, Lit, Spanned, TypeDef};
+use crate::passes::select::std_lib::Std;
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::uniquify::{PrgUniquified, BUILT_INS};
@@ -7,22 +8,48 @@ use crate::passes::validate::DefUniquified;
 use crate::utils::gen_sym::UniqueSym;
 use crate::utils::union_find::{UnionFind, UnionIndex};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct Env<'a, 'p> {
     pub uf: &'a mut UnionFind<PartialType<'p>>,
     pub scope: &'a mut HashMap<UniqueSym<'p>, EnvEntry<'p>>,
-    pub loop_type: Option<UnionIndex>,
+    /// Labels and types of the enclosing loops, with the innermost loop last.
+    pub loop_types: Vec<(Option<UniqueSym<'p>>, UnionIndex)>,
     pub return_type: &'a Spanned<UnionIndex>,
+    pub std: &'a Std<'p>,
+    /// Types of the arguments passed to `print` and the fresh types of generalized functions at
+    /// their uses. Integers among them whose type is not known otherwise default to `I64`.
+    pub defaulted: &'a mut Vec<UnionIndex>,
+    /// Which parameters are passed by reference, for the functions that have any.
+    pub by_ref: &'a HashMap<UniqueSym<'p>, Vec<bool>>,
+    /// Functions defined in the program and the standard library, which asm blocks can call.
+    pub fns: &'a HashSet<UniqueSym<'p>>,
+}
+
+impl<'p> Env<'_, 'p> {
+    /// Returns the type of the loop with the given label, or of the innermost loop if there is no
+    /// label.
+    pub fn loop_type(&self, lbl: Option<UniqueSym<'p>>) -> Option<UnionIndex> {
+        self.loop_types
+            .iter()
+            .rev()
+            .find(|(loop_lbl, _)| lbl.is_none() || *loop_lbl == lbl)
+            .map(|&(_, typ)| typ)
+    }
 }
 
 pub enum EnvEntry<'p> {
     Type {
-        mutable: bool,
         typ: UnionIndex,
     },
+    /// A function whose signature is generalized over the types `vars`, which are instantiated
+    /// with fresh types at every use.
+    Generic {
+        typ: UnionIndex,
+        vars: Vec<UnionIndex>,
+    },
     Def {
-        def: TypeDef<Spanned<UniqueSym<'p>>, Spanned<&'p str>>,
+        def: TypeDef<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>>,
     },
 }
 
@@ -31,18 +58,11 @@ pub fn uncover_globals<'p>(
     program: &PrgUniquified<'p>,
     uf: &mut UnionFind<PartialType<'p>>,
 ) -> Result<HashMap<UniqueSym<'p>, EnvEntry<'p>>, TypeError> {
-    let builtins = program
-        .std
+    let builtins = BUILT_INS
         .iter()
-        .map(|(k, v)| {
-            let typ = uf.type_to_index(BUILT_INS[k].clone());
-            (
-                *v,
-                EnvEntry::Type {
-                    mutable: false,
-                    typ,
-                },
-            )
+        .map(|(k, typ)| {
+            let typ = uf.type_to_index(typ.clone());
+            (program.std[k], EnvEntry::Type { typ })
         })
         .collect::<Vec<_>>();
 
@@ -83,17 +103,31 @@ pub fn uncover_globals<'p>(
 
 fn uncover_def<'p>(def: &DefUniquified<'p>, uf: &mut UnionFind<PartialType<'p>>) -> EnvEntry<'p> {
     match def {
-        Def::Fn {
-            params: args, typ, ..
-        } => EnvEntry::Type {
-            mutable: false,
-            typ: uf.type_to_index(Type::Fn {
-                typ: Box::new(typ.clone()),
-                params: args.iter().map(|param| param.typ.clone()).collect(),
-            }),
-        },
+        Def::Fn { params, typ, .. } => {
+            // Types that are not annotated start out unknown. They are inferred from the body of
+            // the function and the functions it calls, after which `generalize` lets every use
+            // outside its strongly connected component instantiate them with its own types.
+            let params = params
+                .iter()
+                .map(|param| annotation_to_index(param.typ.clone(), uf))
+                .collect();
+            let typ = annotation_to_index(typ.clone(), uf);
+            EnvEntry::Type {
+                typ: uf.add(PartialType::Fn { params, typ }),
+            }
+        }
         Def::TypeDef { def, .. } => EnvEntry::Def {
             def: (*def).clone(),
         },
     }
 }
+
+fn annotation_to_index<'p>(
+    typ: Option<Type<Spanned<UniqueSym<'p>>>>,
+    uf: &mut UnionFind<PartialType<'p>>,
+) -> UnionIndex {
+    match typ {
+        Some(typ) => uf.type_to_index(typ),
+        None => uf.add(PartialType::Unknown),
+    }
+}
@@ -1,16 +1,44 @@
 use crate::passes::parse::{Constrained, Span, Spanned};
+use crate::passes::validate::constrain::generalize::instantiate;
 use crate::passes::validate::constrain::uncover_globals::{Env, EnvEntry};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::{ExprConstrained, MetaConstrained};
+use crate::utils::expect::expect;
 use crate::utils::gen_sym::UniqueSym;
+use std::collections::HashMap;
 
 pub fn constrain_var<'p>(
-    env: &Env<'_, 'p>,
+    env: &mut Env<'_, 'p>,
     span: Span,
     sym: Spanned<UniqueSym<'p>>,
 ) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
-    let EnvEntry::Type { typ, .. } = env.scope[&sym.inner] else {
-        return Err(TypeError::SymbolShouldBeVariable { span });
+    // Functions with parameters that are passed by reference can only be called directly, so that
+    // `constrain_apply` can check that their arguments are references.
+    expect(
+        !env.by_ref.contains_key(&sym.inner),
+        TypeError::RefFnAsValue { span },
+    )?;
+    constrain_sym(env, span, sym)
+}
+
+/// Constrains a variable, which may also be a function with parameters that are passed by
+/// reference. Every use of a generalized function gets fresh types for the types it is
+/// generalized over.
+pub fn constrain_sym<'p>(
+    env: &mut Env<'_, 'p>,
+    span: Span,
+    sym: Spanned<UniqueSym<'p>>,
+) -> Result<Constrained<ExprConstrained<'p>>, TypeError> {
+    let typ = match &env.scope[&sym.inner] {
+        EnvEntry::Type { typ } => *typ,
+        EnvEntry::Generic { typ, vars } => {
+            let (typ, vars) = (*typ, vars.clone());
+            let mut fresh = HashMap::new();
+            let typ = instantiate(typ, &vars, &mut fresh, env.uf);
+            env.defaulted.extend(fresh.into_values());
+            typ
+        }
+        EnvEntry::Def { .. } => return Err(TypeError::SymbolShouldBeVariable { span }),
     };
     Ok(Constrained {
         meta: MetaConstrained { span, index: typ },
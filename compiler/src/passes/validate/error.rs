@@ -12,6 +12,26 @@ pub enum TypeError {
         #[label = "This variable `{sym}` was not declared yet"]
         span: (usize, usize),
     },
+    #[error("Encountered an undeclared label.")]
+    UndeclaredLabel {
+        sym: String,
+        #[label = "The label `{sym}` was not declared in this asm block"]
+        span: (usize, usize),
+    },
+    #[error("Encountered an undeclared loop label.")]
+    UndeclaredLoopLabel {
+        sym: String,
+        #[label = "The label `{sym}` does not belong to an enclosing loop"]
+        span: (usize, usize),
+    },
+    #[error("Duplicate label.")]
+    DuplicateLabel {
+        #[label = "Label `{sym}` was first declared here"]
+        span1: (usize, usize),
+        #[label = "And was redeclared here"]
+        span2: (usize, usize),
+        sym: String,
+    },
     #[error("Duplicate global definition.")]
     DuplicateGlobal {
         #[label = "Global `{sym}` was first declared here"]
@@ -34,6 +54,14 @@ pub enum TypeError {
         span2: (usize, usize),
         sym: String,
     },
+    #[error("Duplicate binding in pattern.")]
+    DuplicateBinding {
+        #[label = "Binding `{sym}` was first declared here"]
+        span1: (usize, usize),
+        #[label = "And was redeclared here"]
+        span2: (usize, usize),
+        sym: String,
+    },
     #[error("Break outside loop.")]
     BreakOutsideLoop {
         #[label = "Found a break outside of a loop"]
@@ -49,6 +77,18 @@ pub enum TypeError {
         #[label = "This variable was declared as immutable."]
         span: (usize, usize),
     },
+    #[error("Read a variable that may be uninitialized.")]
+    MaybeUninitialized {
+        sym: String,
+        #[label = "The variable `{sym}` is not assigned on every path to this point."]
+        span: (usize, usize),
+    },
+    #[error("Tried to assign immutable variable twice.")]
+    ReassignImmutable {
+        sym: String,
+        #[label = "The immutable variable `{sym}` may already have been assigned here."]
+        span: (usize, usize),
+    },
     #[error("Tried to put type in variable.'")]
     SymbolShouldBeVariable {
         #[label = "This should be a variable."]
@@ -111,8 +151,19 @@ pub enum TypeError {
         #[label = "Could not determine the exact type of this integer"]
         span: (usize, usize),
     },
+    #[error("Type ambiguous.")]
+    TypeAmbiguous {
+        what: String,
+        #[label = "Could not infer the {what}"]
+        span: (usize, usize),
+    },
     #[error("The program doesn't have a main function.")]
     NoMain,
+    #[error("The main function has an unsupported signature.")]
+    MainSignature {
+        #[label = "`main` should take no parameters, or a single parameter of type `Args`."]
+        span: (usize, usize),
+    },
     #[error("Types did not match.")]
     MismatchedFnReturn {
         expect: String,
@@ -180,6 +231,37 @@ pub enum TypeError {
         #[label = "But got this type: `{got}`"]
         span_got: (usize, usize),
     },
+    #[error("Function used as a value.")]
+    RefFnAsValue {
+        #[label = "Functions with parameters that are passed by reference can only be called directly."]
+        span: (usize, usize),
+    },
+    #[error("Expected a reference.")]
+    ArgExpectRef {
+        #[label = "This parameter is passed by reference, so the argument should be `&mut` and a variable."]
+        span: (usize, usize),
+    },
+    #[error("Variable passed by reference more than once.")]
+    AliasingRefs {
+        #[label = "`{sym}` is passed by reference here"]
+        span1: (usize, usize),
+        #[label = "And again here, which would give the function two names for the same variable"]
+        span2: (usize, usize),
+        sym: String,
+    },
+    #[error("Unexpected reference.")]
+    ArgUnexpectedRef {
+        #[label = "References can only be passed to parameters that are declared with `&mut`."]
+        span: (usize, usize),
+    },
+    #[error("Types did not match.")]
+    MismatchedStructBase {
+        expect: String,
+        got: String,
+
+        #[label = "Expected the fields to be copied from a `{expect}`, but got: `{got}`"]
+        span: (usize, usize),
+    },
     #[error("Types did not match.")]
     IfExpectBool {
         got: String,
@@ -199,6 +281,30 @@ pub enum TypeError {
         span_els: (usize, usize),
     },
 
+    #[error("Types did not match.")]
+    MatchExpectEqual {
+        expect: String,
+        got: String,
+
+        #[label = "Expected the arms of this match to have type: `{expect}`"]
+        span_expected: (usize, usize),
+        #[label = "But got this type: `{got}`"]
+        span_got: (usize, usize),
+    },
+    #[error("Non-exhaustive match.")]
+    NonExhaustiveMatch {
+        #[label = "Not every value is matched by an arm without a guard"]
+        span: (usize, usize),
+    },
+    #[error("Types did not match.")]
+    MismatchedPattern {
+        expect: String,
+        got: String,
+
+        #[label = "Expected a pattern for type `{expect}`, but this pattern has type `{got}`"]
+        span: (usize, usize),
+    },
+
     #[error("Types did not match.")]
     MismatchedExpectFn {
         got: String,
@@ -216,6 +322,47 @@ pub enum TypeError {
         span_break: (usize, usize),
     },
 
+    #[error("Types did not match.")]
+    MismatchedAsmOperand {
+        expect: String,
+        got: String,
+
+        #[label = "Expected asm operand to have type `{expect}`, but got: `{got}`"]
+        span_got: (usize, usize),
+    },
+
+    #[error("Asm operand does not fit in a register.")]
+    AsmOperandNotScalar {
+        typ: String,
+
+        #[label = "Values of type `{typ}` cannot be passed into or out of an asm block"]
+        span: (usize, usize),
+    },
+
+    #[error("Asm target is not a function.")]
+    AsmTargetNotFunction {
+        sym: String,
+
+        #[label = "`{sym}` is not a function, so it cannot be called or loaded as a label"]
+        span: (usize, usize),
+    },
+
+    #[error("Asm immediate out of range.")]
+    AsmImmediateOutOfRange {
+        val: i64,
+
+        #[label = "The immediate `${val}` cannot be encoded in this asm block"]
+        span: (usize, usize),
+    },
+
+    #[error("Value cannot be printed.")]
+    NotPrintable {
+        typ: String,
+
+        #[label = "Values of type `{typ}` cannot be printed"]
+        span: (usize, usize),
+    },
+
     #[error("Types did not match.")]
     ArgCountMismatch {
         expected: usize,
@@ -1,3 +1,5 @@
+mod check_assigned;
+mod check_exhaustive;
 mod check_sized;
 mod constrain;
 pub mod error;
@@ -9,15 +11,17 @@ mod uniquify;
 pub mod validate;
 
 use crate::passes::parse::types::Type;
-use crate::passes::parse::{Constrained, Def, Expr, Lit, Span, Spanned, Typed};
+use crate::passes::parse::{
+    Constrained, Def, Expr, Lit, MatchArm, Param, Pattern, Span, Spanned, Typed,
+};
 use crate::passes::select::std_lib::Std;
-use crate::passes::select::{Instr, VarArg};
+use crate::passes::select::{AsmItem, Instr, VarArg};
 use crate::utils::gen_sym::UniqueSym;
 use crate::utils::union_find::{UnionFind, UnionIndex};
 use derive_more::Display;
 use itertools::Itertools;
 use partial_type::PartialType;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
 #[derive(Debug, Display)]
@@ -31,23 +35,49 @@ pub struct PrgValidated<'p> {
 pub struct PrgConstrained<'p> {
     pub defs: HashMap<UniqueSym<'p>, DefConstrained<'p>>,
     pub entry: UniqueSym<'p>,
+    /// Functions whose signatures are generalized, which are resolved once for every type they
+    /// are used at.
+    pub generic: HashSet<UniqueSym<'p>>,
     pub uf: UnionFind<PartialType<'p>>,
     pub std: Std<'p>,
 }
 
 pub type DefValidated<'p> = Def<UniqueSym<'p>, &'p str, Typed<'p, ExprValidated<'p>>>;
 pub type ExprValidated<'p> = Expr<UniqueSym<'p>, &'p str, TLit, Type<UniqueSym<'p>>>;
+pub type PatternValidated<'p> = Pattern<UniqueSym<'p>, &'p str, TLit, Type<UniqueSym<'p>>>;
+pub type MatchArmValidated<'p> = MatchArm<UniqueSym<'p>, &'p str, TLit, Type<UniqueSym<'p>>>;
 
-pub type DefConstrained<'p> =
-    Def<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Constrained<ExprConstrained<'p>>>;
+/// Functions keep their optional type annotations, together with the span and the index in the
+/// union-find of the annotated or inferred type.
+pub type DefConstrained<'p> = Def<
+    Spanned<UniqueSym<'p>>,
+    Spanned<&'p str>,
+    Constrained<ExprConstrained<'p>>,
+    Constrained<Option<Type<Spanned<UniqueSym<'p>>>>>,
+    Lit<'p>,
+>;
 pub type ExprConstrained<'p> =
     Expr<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, MetaConstrained>;
+pub type PatternConstrained<'p> =
+    Pattern<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, MetaConstrained>;
+pub type MatchArmConstrained<'p> =
+    MatchArm<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, MetaConstrained>;
 
-pub type DefUniquified<'p> =
-    Def<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Spanned<ExprUniquified<'p>>>;
+pub type DefUniquified<'p> = Def<
+    Spanned<UniqueSym<'p>>,
+    Spanned<&'p str>,
+    Spanned<ExprUniquified<'p>>,
+    Option<Type<Spanned<UniqueSym<'p>>>>,
+    Lit<'p>,
+>;
+pub type ParamUniquified<'p> = Param<Spanned<UniqueSym<'p>>, Option<Type<Spanned<UniqueSym<'p>>>>>;
 pub type ExprUniquified<'p> = Expr<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, Span>;
+pub type PatternUniquified<'p> = Pattern<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, Span>;
+pub type MatchArmUniquified<'p> = MatchArm<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>, Span>;
 pub type InstrUniquified<'p> = Instr<VarArg<Spanned<UniqueSym<'p>>>, Spanned<UniqueSym<'p>>>;
+pub type AsmItemUniquified<'p> = AsmItem<VarArg<Spanned<UniqueSym<'p>>>, Spanned<UniqueSym<'p>>>;
 
+#[derive(Copy, Clone)]
 pub struct MetaConstrained {
     pub span: Span,
     pub index: UnionIndex,
@@ -110,8 +140,11 @@ impl FromStr for TLit {
             "false" => TLit::Bool { val: false },
             "true" => TLit::Bool { val: true },
             "unit" => TLit::Unit,
-            s => TLit::I64 {
-                val: s.parse().map_err(|_| ())?,
+            s => match s.parse() {
+                Ok(val) => TLit::I64 { val },
+                Err(_) => TLit::U64 {
+                    val: s.parse().map_err(|_| ())?,
+                },
             },
         })
     }
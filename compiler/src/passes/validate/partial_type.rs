@@ -10,6 +10,8 @@ pub enum PartialType<'p> {
     Bool,
     Unit,
     Never,
+    /// The type of a parameter or return value without annotation, that has not been inferred yet.
+    Unknown,
     Var {
         sym: UniqueSym<'p>,
     },
@@ -28,6 +30,7 @@ impl<'p> PartialType<'p> {
             PartialType::Bool => "Bool".to_string(),
             PartialType::Unit => "Unit".to_string(),
             PartialType::Never => "Never".to_string(),
+            PartialType::Unknown => "{unknown}".to_string(),
             PartialType::Var { sym } => sym.sym.to_string(),
             PartialType::Fn { params, typ } => {
                 let params_string = params
@@ -60,6 +63,8 @@ pub fn combine_partial_types<'p>(
         (PartialType::Int, PartialType::Int) => PartialType::Int,
         (PartialType::Bool, PartialType::Bool) => PartialType::Bool,
         (PartialType::Unit, PartialType::Unit) => PartialType::Unit,
+        (PartialType::Unknown, t) => t.clone(),
+        (t, PartialType::Unknown) => t.clone(),
         (PartialType::Never, t) => t.clone(),
         (t, PartialType::Never) => t.clone(),
         (PartialType::Var { sym: sym_a }, PartialType::Var { sym: sym_b }) if sym_a == sym_b => {
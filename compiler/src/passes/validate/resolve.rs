@@ -1,26 +1,237 @@
 use crate::passes::parse::types::Type;
-use crate::passes::parse::{Constrained, Expr, Lit, Meta, Param, Span, Spanned, TypeDef, Typed};
-use crate::passes::select::{Instr, InstrSelected, VarArg};
+use crate::passes::parse::{
+    Constrained, Expr, Lit, MatchArm, Meta, Param, Pattern, Span, Spanned, TypeDef, Typed,
+};
+use crate::passes::select::std_lib::Std;
+use crate::passes::select::{AsmItem, AsmItemSelected, Instr, InstrSelected, VarArg};
+use crate::passes::validate::check_exhaustive::check_exhaustive;
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::partial_type::PartialType;
 use crate::passes::validate::{
-    DefConstrained, DefValidated, ExprConstrained, ExprValidated, PrgConstrained, PrgValidated,
-    TLit,
+    AsmItemUniquified, DefConstrained, DefValidated, ExprConstrained, ExprValidated,
+    PatternConstrained, PatternValidated, PrgConstrained, PrgValidated, TLit,
 };
-use crate::utils::gen_sym::UniqueSym;
+use crate::utils::gen_sym::{gen_sym, UniqueSym};
 use crate::utils::union_find::{UnionFind, UnionIndex};
 use crate::*;
 use functor_derive::Functor;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
 use std::num::ParseIntError;
+use std::{iter, mem};
+
+/// The resolved parameter and return types of every function.
+type Signatures<'p> = HashMap<UniqueSym<'p>, (Vec<Type<UniqueSym<'p>>>, Type<UniqueSym<'p>>)>;
+
+struct Env<'a, 'p> {
+    uf: &'a mut UnionFind<PartialType<'p>>,
+    std: &'a Std<'p>,
+    /// Fields of the structs in the program, used to print struct instances.
+    structs: &'a HashMap<UniqueSym<'p>, Vec<(&'p str, Type<UniqueSym<'p>>)>>,
+    /// Functions with parameters that are passed by reference.
+    by_ref: &'a HashMap<UniqueSym<'p>, ByRef<'p>>,
+    /// The function that is being resolved, if it has parameters that are passed by reference.
+    current: Option<&'a ByRef<'p>>,
+    /// Functions whose signatures are generalized.
+    generic: &'a HashSet<UniqueSym<'p>>,
+    /// Functions, structs and the standard library, which keep their symbols in copies.
+    globals: &'a HashSet<UniqueSym<'p>>,
+    /// The copy of a generalized function for every type it is used at.
+    instances: HashMap<(UniqueSym<'p>, Type<UniqueSym<'p>>), UniqueSym<'p>>,
+    /// Copies that still have to be resolved, with the function they copy and their type.
+    queue: Vec<(UniqueSym<'p>, UniqueSym<'p>, Type<UniqueSym<'p>>)>,
+    /// Types of the copy that is being resolved, for the types its function is generalized over.
+    subst: HashMap<UnionIndex, Type<UniqueSym<'p>>>,
+    /// Fresh symbols for the locals of the copy that is being resolved, so that copies do not
+    /// share them. This is `None` for functions that are not generalized.
+    locals: Option<HashMap<UniqueSym<'p>, UniqueSym<'p>>>,
+}
+
+impl<'p> Env<'_, 'p> {
+    /// Returns the symbol of a local variable or label in the function that is being resolved.
+    fn local(&mut self, sym: UniqueSym<'p>) -> UniqueSym<'p> {
+        match &mut self.locals {
+            Some(locals) if !self.globals.contains(&sym) => {
+                *locals.entry(sym).or_insert_with(|| gen_sym(sym.sym))
+            }
+            _ => sym,
+        }
+    }
+
+    /// Returns the copy of the generalized function `sym` for type `typ`.
+    fn instance(&mut self, sym: UniqueSym<'p>, typ: Type<UniqueSym<'p>>) -> UniqueSym<'p> {
+        *self.instances.entry((sym, typ.clone())).or_insert_with(|| {
+            let copy = gen_sym(sym.sym);
+            self.queue.push((copy, sym, typ));
+            copy
+        })
+    }
+}
+
+/// Parameters that are passed by reference are lowered to copy-in/copy-out: the function returns a
+/// struct with its return value and the final values of those parameters, which the caller then
+/// assigns back to the variables it passed.
+struct ByRef<'p> {
+    /// Struct returned by the function.
+    strct: UniqueSym<'p>,
+    /// The field of the struct and the type for every parameter that is passed by reference.
+    params: Vec<Option<(&'p str, UniqueSym<'p>, Type<UniqueSym<'p>>)>>,
+}
+
+/// Field of the struct returned by a function with parameters passed by reference that holds the
+/// return value. This is a keyword, so it never clashes with the name of a parameter.
+const RETURN_FIELD: &str = "return";
 
 impl<'p> PrgConstrained<'p> {
     pub fn resolve(mut self) -> Result<PrgValidated<'p>, TypeError> {
+        let structs = self
+            .defs
+            .iter()
+            .filter_map(|(sym, def)| match def {
+                DefConstrained::TypeDef {
+                    def: TypeDef::Struct { fields, .. },
+                    ..
+                } => Some((
+                    *sym,
+                    fields
+                        .iter()
+                        .map(|(field_sym, field_typ)| {
+                            (field_sym.inner, resolve_type(field_typ.clone()))
+                        })
+                        .collect(),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        let globals = self.defs.keys().chain(self.std.values()).copied().collect();
+        let (generic_defs, defs): (HashMap<_, _>, HashMap<_, _>) = self
+            .defs
+            .into_iter()
+            .partition(|(sym, _)| self.generic.contains(sym));
+
+        // The types in function bodies depend on the inferred signatures, so resolve those first.
+        // Generalized functions only get signatures once they are used.
+        let mut signatures = Signatures::new();
+        for def in defs.values().sorted_by_key(|def| def.sym().meta) {
+            if let DefConstrained::Fn {
+                sym, params, typ, ..
+            } = def
+            {
+                let params = params
+                    .iter()
+                    .map(|p| {
+                        let what = format!("type of parameter `{}`", p.sym.inner.sym);
+                        resolve_annotation(&p.typ, what, &mut self.uf)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let what = format!("return type of `{}`", sym.inner.sym);
+                let typ = resolve_annotation(typ, what, &mut self.uf)?;
+                signatures.insert(sym.inner, (params, typ));
+            }
+        }
+
+        let mut by_ref = HashMap::new();
+        let mut ref_defs = Vec::new();
+        for def in defs.values().sorted_by_key(|def| def.sym().meta) {
+            let DefConstrained::Fn { sym, params, .. } = def else {
+                continue;
+            };
+            if !params.iter().any(|param| param.by_ref) {
+                continue;
+            }
+
+            let (param_types, typ) = signatures.get_mut(&sym.inner).unwrap();
+            let strct = gen_sym(sym.inner.sym);
+            let params = params
+                .iter()
+                .zip(param_types.iter())
+                .map(|(param, typ)| {
+                    param
+                        .by_ref
+                        .then(|| (param.sym.inner.sym, param.sym.inner, typ.clone()))
+                })
+                .collect::<Vec<_>>();
+
+            let fields = iter::once((RETURN_FIELD, typ.clone()))
+                .chain(
+                    params
+                        .iter()
+                        .flatten()
+                        .map(|(field, _, typ)| (*field, typ.clone())),
+                )
+                .collect();
+            ref_defs.push((
+                strct,
+                DefValidated::TypeDef {
+                    sym: strct,
+                    def: TypeDef::Struct {
+                        fields,
+                        defaults: Vec::new(),
+                    },
+                },
+            ));
+
+            *typ = Type::Var { sym: strct };
+            by_ref.insert(sym.inner, ByRef { strct, params });
+        }
+
+        let mut env = Env {
+            uf: &mut self.uf,
+            std: &self.std,
+            structs: &structs,
+            by_ref: &by_ref,
+            current: None,
+            generic: &self.generic,
+            globals: &globals,
+            instances: HashMap::new(),
+            queue: Vec::new(),
+            subst: HashMap::new(),
+            locals: None,
+        };
+
+        let mut defs = defs
+            .into_iter()
+            .map(|(sym, def)| resolve_def(def, &mut signatures, &mut env).map(|def| (sym, def)))
+            .chain(ref_defs.into_iter().map(Ok))
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        // Generalized functions are resolved once for every type they are used at, starting from
+        // their uses in the other functions. Generalized functions that are never used are left
+        // out.
+        while let Some((copy, sym, typ)) = env.queue.pop() {
+            let mut def = generic_defs[&sym].clone();
+            let DefConstrained::Fn {
+                sym,
+                params,
+                typ: ret,
+                ..
+            } = &mut def
+            else {
+                unreachable!("Only functions are generalized.");
+            };
+            let Type::Fn {
+                params: param_types,
+                typ: ret_type,
+            } = typ
+            else {
+                unreachable!("Functions have a function type.");
+            };
+
+            env.subst.clear();
+            for (param, typ) in params.iter().zip(&param_types) {
+                bind_generic(param.typ.meta.index, typ, env.uf, &mut env.subst);
+            }
+            bind_generic(ret.meta.index, &ret_type, env.uf, &mut env.subst);
+
+            sym.inner = copy;
+            signatures.insert(copy, (param_types, *ret_type));
+            env.locals = Some(HashMap::new());
+            defs.insert(copy, resolve_def(def, &mut signatures, &mut env)?);
+        }
+
         Ok(PrgValidated {
-            defs: self
-                .defs
-                .into_iter()
-                .map(|(sym, def)| resolve_def(def, &mut self.uf).map(|def| (sym, def)))
-                .collect::<Result<_, _>>()?,
+            defs,
             entry: self.entry,
             std: self.std,
         })
@@ -29,27 +240,45 @@ impl<'p> PrgConstrained<'p> {
 
 fn resolve_def<'p>(
     def: DefConstrained<'p>,
-    uf: &mut UnionFind<PartialType<'p>>,
+    signatures: &mut Signatures<'p>,
+    env: &mut Env<'_, 'p>,
 ) -> Result<DefValidated<'p>, TypeError> {
     let def = match def {
         DefConstrained::Fn {
             sym,
             params,
-            typ,
             bdy,
-        } => DefValidated::Fn {
-            sym: sym.inner,
-            params: params.fmap(|p| Param {
-                sym: p.sym.inner,
-                typ: resolve_type(p.typ),
-                mutable: p.mutable,
-            }),
-            typ: resolve_type(typ),
-            bdy: resolve_expr(bdy, uf)?,
-        },
+            inline,
+            ..
+        } => {
+            let (param_types, typ) = signatures.remove(&sym.inner).unwrap();
+            env.current = env.by_ref.get(&sym.inner);
+            let bdy = resolve_expr(bdy, env)?;
+            let bdy = match env.current {
+                Some(by_ref) => return_by_ref(bdy, by_ref),
+                None => bdy,
+            };
+
+            DefValidated::Fn {
+                sym: sym.inner,
+                params: params
+                    .into_iter()
+                    .zip(param_types)
+                    .map(|(p, typ)| Param {
+                        sym: env.local(p.sym.inner),
+                        typ,
+                        mutable: p.mutable,
+                        by_ref: false,
+                    })
+                    .collect(),
+                typ,
+                bdy,
+                inline,
+            }
+        }
         DefConstrained::TypeDef { sym, def } => DefValidated::TypeDef {
             sym: sym.inner,
-            def: resolve_typedef(def),
+            def: resolve_typedef(def)?,
         },
     };
 
@@ -57,14 +286,44 @@ fn resolve_def<'p>(
 }
 
 fn resolve_typedef<'p>(
-    typedef: TypeDef<Spanned<UniqueSym<'p>>, Spanned<&'p str>>,
-) -> TypeDef<UniqueSym<'p>, &'p str> {
-    match typedef {
-        TypeDef::Struct { fields } => TypeDef::Struct {
-            fields: fields
-                .fmap(|(field_sym, field_typ)| (field_sym.inner, resolve_type(field_typ))),
-        },
+    typedef: TypeDef<Spanned<UniqueSym<'p>>, Spanned<&'p str>, Lit<'p>>,
+) -> Result<TypeDef<UniqueSym<'p>, &'p str>, TypeError> {
+    let typedef = match typedef {
+        TypeDef::Struct { fields, defaults } => {
+            let fields =
+                fields.fmap(|(field_sym, field_typ)| (field_sym.inner, resolve_type(field_typ)));
+            let defaults = defaults
+                .into_iter()
+                .map(|(field_sym, val)| {
+                    let typ = fields
+                        .iter()
+                        .find(|(sym, _)| *sym == field_sym.inner)
+                        .map(|(_, typ)| typ);
+                    resolve_lit(val, typ, field_sym.meta).map(|val| (field_sym.inner, val))
+                })
+                .collect::<Result<_, _>>()?;
+            TypeDef::Struct { fields, defaults }
+        }
         TypeDef::Enum { .. } => todo!(),
+    };
+
+    Ok(typedef)
+}
+
+/// Resolves the annotated type, or the inferred type if the annotation was omitted.
+fn resolve_annotation<'p>(
+    typ: &Constrained<Option<Type<Spanned<UniqueSym<'p>>>>>,
+    what: String,
+    uf: &mut UnionFind<PartialType<'p>>,
+) -> Result<Type<UniqueSym<'p>>, TypeError> {
+    match &typ.inner {
+        Some(typ) => Ok(resolve_type(typ.clone())),
+        None => partial_type_to_type(typ.meta.index, uf, &HashMap::new()).ok_or(
+            TypeError::TypeAmbiguous {
+                what,
+                span: typ.meta.span,
+            },
+        ),
     }
 }
 
@@ -83,15 +342,21 @@ fn resolve_type(typ: Type<Spanned<UniqueSym>>) -> Type<UniqueSym> {
     }
 }
 
-/// Panic if not possible
+/// Returns the type at `value`, with the types in `subst` for the types a function is generalized
+/// over, or `None` if it is not fully known.
 fn partial_type_to_type<'p>(
     value: UnionIndex,
     uf: &mut UnionFind<PartialType<'p>>,
+    subst: &HashMap<UnionIndex, Type<UniqueSym<'p>>>,
 ) -> Option<Type<UniqueSym<'p>>> {
+    if let Some(typ) = subst.get(&uf.find(value)) {
+        return Some(typ.clone());
+    }
+
     Some(match uf.get(value).clone() {
         PartialType::I64 => Type::I64,
         PartialType::U64 => Type::U64,
-        PartialType::Int => return None,
+        PartialType::Int | PartialType::Unknown => return None,
         PartialType::Bool => Type::Bool,
         PartialType::Unit => Type::Unit,
         PartialType::Never => Type::Never,
@@ -99,29 +364,71 @@ fn partial_type_to_type<'p>(
         PartialType::Fn { params, typ } => Type::Fn {
             params: params
                 .into_iter()
-                .map(|param| partial_type_to_type(uf.find(param), uf))
+                .map(|param| partial_type_to_type(param, uf, subst))
                 .collect::<Option<_>>()?,
-            typ: Box::new(partial_type_to_type(typ, uf)?),
+            typ: Box::new(partial_type_to_type(typ, uf, subst)?),
         },
     })
 }
 
-fn resolve_int_lit<T: From<u8>>(
+/// Records the types that a generalized function is used at in `subst`, by matching its signature
+/// against the type of the use.
+fn bind_generic<'p>(
+    value: UnionIndex,
+    typ: &Type<UniqueSym<'p>>,
+    uf: &mut UnionFind<PartialType<'p>>,
+    subst: &mut HashMap<UnionIndex, Type<UniqueSym<'p>>>,
+) {
+    let root = uf.find(value);
+    match (uf.get(root).clone(), typ) {
+        (PartialType::Unknown | PartialType::Int, typ) => {
+            subst.insert(root, typ.clone());
+        }
+        (
+            PartialType::Fn { params, typ: ret },
+            Type::Fn {
+                params: param_types,
+                typ: ret_type,
+            },
+        ) => {
+            for (param, typ) in params.into_iter().zip(param_types) {
+                bind_generic(param, typ, uf, subst);
+            }
+            bind_generic(ret, ret_type, uf, subst);
+        }
+        _ => {}
+    }
+}
+
+fn resolve_int_lit<T>(
     original_val: &str,
     span: Span,
     from_radix: fn(&str, u32) -> Result<T, ParseIntError>,
 ) -> Result<T, TypeError> {
+    let invalid = |error: ParseIntError| TypeError::InvalidInteger {
+        span,
+        val: original_val.to_string(),
+        typ: "I64",
+        err: error.to_string(),
+    };
+
     let mut val = original_val;
     if val.ends_with("i64") || val.ends_with("u64") {
         val = &val[..val.len() - 3];
     }
 
+    // Negative integers are only literals in patterns, elsewhere the `-` is a unary operator.
+    let (sign, val) = match val.strip_prefix('-') {
+        Some(val) => ("-", val.trim_start()),
+        None => ("", val),
+    };
+
     let (base, val) = match val {
         s if s.starts_with('b') => {
             let mut s = s[1..].chars();
 
             let int = match (s.next(), s.next(), s.next(), s.next(), s.next()) {
-                (Some('\''), Some(s), Some('\''), None, None) => T::from(s as u8),
+                (Some('\''), Some(s), Some('\''), None, None) => s as u8,
                 (Some('\''), Some('\\'), Some(s), Some('\''), None) => {
                     let s = match s {
                         'n' => '\n',
@@ -137,12 +444,12 @@ fn resolve_int_lit<T: From<u8>>(
                             })
                         }
                     };
-                    T::from(s as u8)
+                    s as u8
                 }
                 _ => unreachable!("Congrats you made an invalid byte lit, plx tell us how"),
             };
 
-            return Ok(int);
+            return from_radix(&format!("{sign}{int}"), 10).map_err(invalid);
         }
         s if s.starts_with("0b") => (2, &s[2..]),
         s if s.starts_with("0o") => (8, &s[2..]),
@@ -150,59 +457,67 @@ fn resolve_int_lit<T: From<u8>>(
         s => (10, s),
     };
 
-    from_radix(&val.replace('_', ""), base).map_err(|error| TypeError::InvalidInteger {
-        span,
-        val: original_val.to_string(),
-        typ: "I64",
-        err: error.to_string(),
-    })
+    from_radix(&format!("{sign}{}", val.replace('_', "")), base).map_err(invalid)
+}
+
+fn resolve_lit(val: Lit, typ: Option<&Type<UniqueSym>>, span: Span) -> Result<TLit, TypeError> {
+    let val = match val {
+        Lit::Int { val, .. } => match typ {
+            None => return Err(TypeError::IntegerAmbiguous { span }),
+            Some(typ) => match typ {
+                Type::I64 => TLit::I64 {
+                    val: resolve_int_lit(val, span, i64::from_str_radix)?,
+                },
+                Type::U64 => TLit::U64 {
+                    val: resolve_int_lit(val, span, u64::from_str_radix)?,
+                },
+                _ => unreachable!(),
+            },
+        },
+        Lit::Bool { val } => TLit::Bool { val },
+        Lit::Unit => TLit::Unit,
+    };
+
+    Ok(val)
 }
 
 fn resolve_expr<'p>(
     expr: Constrained<ExprConstrained<'p>>,
-    uf: &mut UnionFind<PartialType<'p>>,
+    env: &mut Env<'_, 'p>,
 ) -> Result<Typed<'p, ExprValidated<'p>>, TypeError> {
     // Type of the expression, if `None` then type is still ambiguous.
-    let typ = partial_type_to_type(expr.meta.index, uf);
+    let typ = partial_type_to_type(expr.meta.index, env.uf, &env.subst);
 
     let expr = match expr.inner {
-        Expr::Lit { val } => {
-            let val = match val {
-                Lit::Int { val, .. } => match &typ {
-                    None => {
-                        return Err(TypeError::IntegerAmbiguous {
-                            span: expr.meta.span,
-                        })
-                    }
-                    Some(typ) => match typ {
-                        Type::I64 => TLit::I64 {
-                            val: resolve_int_lit(val, expr.meta.span, i64::from_str_radix)?,
-                        },
-                        Type::U64 => TLit::U64 {
-                            val: resolve_int_lit(val, expr.meta.span, u64::from_str_radix)?,
-                        },
-                        _ => unreachable!(),
-                    },
-                },
-                Lit::Bool { val } => TLit::Bool { val },
-                Lit::Unit => TLit::Unit,
-            };
-            Expr::Lit { val }
+        Expr::Lit { val } => Expr::Lit {
+            val: resolve_lit(val, typ.as_ref(), expr.meta.span)?,
+        },
+        Expr::Var { sym } if env.generic.contains(&sym.inner) => {
+            let typ = typ.clone().ok_or(TypeError::TypeAmbiguous {
+                what: format!("type of `{}`", sym.inner.sym),
+                span: expr.meta.span,
+            })?;
+            Expr::Var {
+                sym: env.instance(sym.inner, typ),
+            }
         }
-        Expr::Var { sym } => Expr::Var { sym: sym.inner },
+        Expr::Var { sym } => Expr::Var {
+            sym: env.local(sym.inner),
+        },
+        Expr::RefMut { .. } => unreachable!("References are only passed as arguments."),
         Expr::UnaryOp {
             op,
             expr: expr_inner,
         } => Expr::UnaryOp {
             op,
-            expr: Box::new(resolve_expr(*expr_inner, uf)?),
+            expr: Box::new(resolve_expr(*expr_inner, env)?),
         },
         Expr::BinaryOp {
             op,
             exprs: [e1, e2],
         } => Expr::BinaryOp {
             op,
-            exprs: [resolve_expr(*e1, uf)?, resolve_expr(*e2, uf)?].map(Box::new),
+            exprs: [resolve_expr(*e1, env)?, resolve_expr(*e2, env)?].map(Box::new),
         },
         Expr::Let {
             sym,
@@ -211,59 +526,127 @@ fn resolve_expr<'p>(
             bnd,
             bdy,
         } => Expr::Let {
-            sym: sym.inner,
+            sym: env.local(sym.inner),
             mutable,
             typ: typ.map(resolve_type),
-            bnd: Box::new(resolve_expr(*bnd, uf)?),
-            bdy: Box::new(resolve_expr(*bdy, uf)?),
+            bnd: bnd
+                .map(|bnd| resolve_expr(*bnd, env).map(Box::new))
+                .transpose()?,
+            bdy: Box::new(resolve_expr(*bdy, env)?),
         },
         Expr::If { cnd, thn, els } => Expr::If {
-            cnd: Box::new(resolve_expr(*cnd, uf)?),
-            thn: Box::new(resolve_expr(*thn, uf)?),
-            els: Box::new(resolve_expr(*els, uf)?),
+            cnd: Box::new(resolve_expr(*cnd, env)?),
+            thn: Box::new(resolve_expr(*thn, env)?),
+            els: Box::new(resolve_expr(*els, env)?),
         },
+        Expr::Apply { fun, args } if matches!(&fun.inner, Expr::Var { sym } if sym.inner == env.std["print"]) =>
+        {
+            // `constrain` checked that `print` has exactly one argument.
+            return resolve_print(args.into_iter().next().unwrap(), env);
+        }
+        Expr::Apply { fun, args } if matches!(&fun.inner, Expr::Var { sym } if env.by_ref.contains_key(&sym.inner)) =>
+        {
+            return resolve_apply_by_ref(*fun, args, env);
+        }
         Expr::Apply { fun, args } => Expr::Apply {
-            fun: Box::new(resolve_expr(*fun, uf)?),
+            fun: Box::new(resolve_expr(*fun, env)?),
             args: args
                 .into_iter()
-                .map(|arg| resolve_expr(arg, uf))
+                .map(|arg| resolve_expr(arg, env))
                 .collect::<Result<_, _>>()?,
         },
-        Expr::Loop { bdy } => Expr::Loop {
-            bdy: Box::new(resolve_expr(*bdy, uf)?),
+        Expr::Loop { lbl, bdy } => Expr::Loop {
+            lbl: lbl.map(|lbl| env.local(lbl.inner)),
+            bdy: Box::new(resolve_expr(*bdy, env)?),
         },
-        Expr::Break { bdy } => Expr::Break {
-            bdy: Box::new(resolve_expr(*bdy, uf)?),
+        Expr::Break { lbl, bdy } => Expr::Break {
+            lbl: lbl.map(|lbl| env.local(lbl.inner)),
+            bdy: Box::new(resolve_expr(*bdy, env)?),
         },
-        Expr::Continue => Expr::Continue,
-        Expr::Return { bdy } => Expr::Return {
-            bdy: Box::new(resolve_expr(*bdy, uf)?),
+        Expr::Continue { lbl } => Expr::Continue {
+            lbl: lbl.map(|lbl| env.local(lbl.inner)),
         },
+        Expr::Return { bdy } => {
+            let bdy = resolve_expr(*bdy, env)?;
+            Expr::Return {
+                bdy: Box::new(match env.current {
+                    Some(by_ref) => return_by_ref(bdy, by_ref),
+                    None => bdy,
+                }),
+            }
+        }
         Expr::Seq { stmt, cnt } => Expr::Seq {
-            stmt: Box::new(resolve_expr(*stmt, uf)?),
-            cnt: Box::new(resolve_expr(*cnt, uf)?),
+            stmt: Box::new(resolve_expr(*stmt, env)?),
+            cnt: Box::new(resolve_expr(*cnt, env)?),
         },
         Expr::Assign { sym, bnd } => Expr::Assign {
-            sym: sym.inner,
-            bnd: Box::new(resolve_expr(*bnd, uf)?),
+            sym: env.local(sym.inner),
+            bnd: Box::new(resolve_expr(*bnd, env)?),
         },
-        Expr::Struct { sym, fields } => Expr::Struct {
-            sym: sym.inner,
-            fields: fields
+        Expr::Struct { sym, fields, base } => {
+            let fields = fields
                 .into_iter()
                 .map(|(field_sym, field_bnd)| {
-                    resolve_expr(field_bnd, uf).map(|bnd| (field_sym.inner, bnd))
+                    resolve_expr(field_bnd, env).map(|bnd| (field_sym.inner, bnd))
                 })
-                .collect::<Result<_, _>>()?,
-        },
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match base {
+                Some(base) => return resolve_struct_base(sym.inner, fields, *base, env),
+                None => Expr::Struct {
+                    sym: sym.inner,
+                    fields,
+                    base: None,
+                },
+            }
+        }
         Expr::AccessField { strct, field } => Expr::AccessField {
-            strct: Box::new(resolve_expr(*strct, uf)?),
+            strct: Box::new(resolve_expr(*strct, env)?),
             field: field.inner,
         },
         Expr::Variant { .. } => todo!(),
         Expr::Switch { .. } => todo!(),
-        ExprConstrained::Asm { instrs } => ExprValidated::Asm {
-            instrs: instrs.into_iter().map(resolve_instr).collect(),
+        Expr::Match { scrut, arms } => {
+            let span = expr.meta.span;
+            let scrut = resolve_expr(*scrut, env)?;
+            let arms = arms
+                .into_iter()
+                .map(|arm| {
+                    Ok(MatchArm {
+                        pat: resolve_pattern(arm.pat, env)?,
+                        guard: arm
+                            .guard
+                            .map(|guard| resolve_expr(guard, env))
+                            .transpose()?,
+                        bdy: resolve_expr(arm.bdy, env)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            check_exhaustive(&arms, &scrut.meta, env.structs, span)?;
+
+            Expr::Match {
+                scrut: Box::new(scrut),
+                arms,
+            }
+        }
+        ExprConstrained::Asm {
+            operands,
+            typ,
+            instrs,
+        } => ExprValidated::Asm {
+            operands: operands
+                .into_iter()
+                .map(|(sym, typ, bnd)| {
+                    let bnd = resolve_expr(*bnd, env)?;
+                    Ok((env.local(sym.inner), resolve_type(typ), Box::new(bnd)))
+                })
+                .collect::<Result<_, _>>()?,
+            typ: resolve_type(typ),
+            instrs: instrs
+                .into_iter()
+                .map(|item| resolve_asm_item(item, env))
+                .collect(),
         },
     };
 
@@ -273,37 +656,414 @@ fn resolve_expr<'p>(
     })
 }
 
-pub fn resolve_instr<'p>(
+fn resolve_pattern<'p>(
+    pat: Constrained<PatternConstrained<'p>>,
+    env: &mut Env<'_, 'p>,
+) -> Result<Typed<'p, PatternValidated<'p>>, TypeError> {
+    let span = pat.meta.span;
+    let typ = partial_type_to_type(pat.meta.index, env.uf, &env.subst);
+
+    let inner = match pat.inner {
+        Pattern::Wildcard => Pattern::Wildcard,
+        Pattern::Binding { sym } => Pattern::Binding {
+            sym: env.local(sym.inner),
+        },
+        Pattern::Lit { val } => Pattern::Lit {
+            val: resolve_lit(val, typ.as_ref(), span)?,
+        },
+        Pattern::Range { lo, hi, inclusive } => Pattern::Range {
+            lo: resolve_lit(lo, typ.as_ref(), span)?,
+            hi: resolve_lit(hi, typ.as_ref(), span)?,
+            inclusive,
+        },
+        Pattern::Struct { sym, fields, rest } => Pattern::Struct {
+            sym: sym.inner,
+            fields: fields
+                .into_iter()
+                .map(|(field, pat)| Ok((field.inner, resolve_pattern(pat, env)?)))
+                .collect::<Result<_, _>>()?,
+            rest,
+        },
+    };
+
+    Ok(Meta {
+        meta: typ.ok_or(TypeError::IntegerAmbiguous { span })?,
+        inner,
+    })
+}
+
+/// Returns an expression that calls a function with parameters that are passed by reference, and
+/// assigns the final values of those parameters back to the variables that were passed.
+fn resolve_apply_by_ref<'p>(
+    fun: Constrained<ExprConstrained<'p>>,
+    args: Vec<Constrained<ExprConstrained<'p>>>,
+    env: &mut Env<'_, 'p>,
+) -> Result<Typed<'p, ExprValidated<'p>>, TypeError> {
+    let mut fun = resolve_expr(fun, env)?;
+    let Expr::Var { sym } = fun.inner else {
+        unreachable!("Functions with parameters passed by reference can only be called directly.")
+    };
+    let by_ref = &env.by_ref[&sym];
+    let strct = Type::Var { sym: by_ref.strct };
+    let Type::Fn { typ, .. } = &mut fun.meta else {
+        unreachable!("Functions have a function type.")
+    };
+    let ret = mem::replace(&mut **typ, strct.clone());
+
+    let tmp = gen_sym("tmp");
+    let access = |field: &'p str, typ: Type<UniqueSym<'p>>| Meta {
+        meta: typ,
+        inner: Expr::AccessField {
+            strct: Box::new(Meta {
+                meta: strct.clone(),
+                inner: Expr::Var { sym: tmp },
+            }),
+            field,
+        },
+    };
+
+    let mut assigns = Vec::new();
+    let args = args
+        .into_iter()
+        .zip(&by_ref.params)
+        .map(|(arg, param)| match (arg.inner, param) {
+            (Expr::RefMut { sym }, Some((field, _, typ))) => {
+                let sym = env.local(sym.inner);
+                assigns.push(Meta {
+                    meta: Type::Unit,
+                    inner: Expr::Assign {
+                        sym,
+                        bnd: Box::new(access(field, typ.clone())),
+                    },
+                });
+                Ok(Meta {
+                    meta: typ.clone(),
+                    inner: Expr::Var { sym },
+                })
+            }
+            (inner, _) => resolve_expr(
+                Meta {
+                    meta: arg.meta,
+                    inner,
+                },
+                env,
+            ),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let apply = Meta {
+        meta: strct.clone(),
+        inner: Expr::Apply {
+            fun: Box::new(fun),
+            args,
+        },
+    };
+
+    Ok(Meta {
+        meta: ret.clone(),
+        inner: Expr::Let {
+            sym: tmp,
+            mutable: false,
+            typ: None,
+            bnd: Some(Box::new(apply)),
+            bdy: Box::new(seq(assigns, access(RETURN_FIELD, ret))),
+        },
+    })
+}
+
+/// Returns the struct with the return value and the final values of the parameters that are
+/// passed by reference.
+fn return_by_ref<'p>(
+    val: Typed<'p, ExprValidated<'p>>,
+    by_ref: &ByRef<'p>,
+) -> Typed<'p, ExprValidated<'p>> {
+    let params = by_ref.params.iter().flatten().map(|(field, sym, typ)| {
+        let var = Meta {
+            meta: typ.clone(),
+            inner: Expr::Var { sym: *sym },
+        };
+        (*field, var)
+    });
+
+    Meta {
+        meta: Type::Var { sym: by_ref.strct },
+        inner: Expr::Struct {
+            sym: by_ref.strct,
+            fields: iter::once((RETURN_FIELD, val)).chain(params).collect(),
+            base: None,
+        },
+    }
+}
+
+/// Binds the base of a struct instance to a fresh variable, and copies the fields that were left
+/// out from it.
+fn resolve_struct_base<'p>(
+    sym: UniqueSym<'p>,
+    mut fields: Vec<(&'p str, Typed<'p, ExprValidated<'p>>)>,
+    base: Constrained<ExprConstrained<'p>>,
+    env: &mut Env<'_, 'p>,
+) -> Result<Typed<'p, ExprValidated<'p>>, TypeError> {
+    let base = resolve_expr(base, env)?;
+    let typ = base.meta.clone();
+    let base_sym = gen_sym("tmp");
+
+    for (field, field_typ) in &env.structs[&sym] {
+        if fields.iter().any(|(sym, _)| sym == field) {
+            continue;
+        }
+        let access = Meta {
+            meta: field_typ.clone(),
+            inner: Expr::AccessField {
+                strct: Box::new(Meta {
+                    meta: typ.clone(),
+                    inner: Expr::Var { sym: base_sym },
+                }),
+                field: *field,
+            },
+        };
+        fields.push((field, access));
+    }
+
+    Ok(Meta {
+        meta: typ.clone(),
+        inner: Expr::Let {
+            sym: base_sym,
+            mutable: false,
+            typ: None,
+            bnd: Some(Box::new(base)),
+            bdy: Box::new(Meta {
+                meta: typ,
+                inner: Expr::Struct {
+                    sym,
+                    fields,
+                    base: None,
+                },
+            }),
+        },
+    })
+}
+
+/// Selects the standard library routine that prints the argument, based on its type.
+/// Struct instances are printed field by field, as `Name { a: 1, b: true }`.
+fn resolve_print<'p>(
+    arg: Constrained<ExprConstrained<'p>>,
+    env: &mut Env<'_, 'p>,
+) -> Result<Typed<'p, ExprValidated<'p>>, TypeError> {
+    let span = arg.meta.span;
+    let arg = resolve_expr(arg, env)?;
+    let typ = arg.meta.clone();
+
+    let print = match typ {
+        Type::I64 | Type::Never => "print",
+        Type::U64 => "print_u64",
+        Type::Bool => "print_bool",
+        Type::Unit => "print_unit",
+        Type::Var { .. } => {
+            let sym = gen_sym("tmp");
+            let var = || Meta {
+                meta: typ.clone(),
+                inner: Expr::Var { sym },
+            };
+            let bdy = seq(
+                vec![write_value(var(), span, env)?, write_str("\n", env)],
+                var(),
+            );
+            return Ok(Meta {
+                meta: typ.clone(),
+                inner: Expr::Let {
+                    sym,
+                    mutable: false,
+                    typ: None,
+                    bnd: Some(Box::new(arg)),
+                    bdy: Box::new(bdy),
+                },
+            });
+        }
+        Type::Fn { .. } => {
+            return Err(TypeError::NotPrintable {
+                typ: typ.to_string(),
+                span,
+            })
+        }
+    };
+
+    Ok(apply_std(print, arg, typ, env))
+}
+
+/// Returns an expression that writes the value without a trailing newline.
+fn write_value<'p>(
+    val: Typed<'p, ExprValidated<'p>>,
+    span: Span,
+    env: &Env<'_, 'p>,
+) -> Result<Typed<'p, ExprValidated<'p>>, TypeError> {
+    let write = match &val.meta {
+        Type::I64 => "write_i64",
+        Type::U64 => "write_u64",
+        Type::Bool => "write_bool",
+        Type::Unit => "write_unit",
+        Type::Var { sym } => {
+            let struct_sym = *sym;
+            let sym = gen_sym("tmp");
+
+            let mut stmts = vec![write_str(&format!("{} {{", struct_sym.sym), env)];
+            let fields = &env.structs[&struct_sym];
+            for (i, (field, field_typ)) in fields.iter().enumerate() {
+                let prefix = if i == 0 { " " } else { ", " };
+                stmts.push(write_str(&format!("{prefix}{field}: "), env));
+                let access = Meta {
+                    meta: field_typ.clone(),
+                    inner: Expr::AccessField {
+                        strct: Box::new(Meta {
+                            meta: val.meta.clone(),
+                            inner: Expr::Var { sym },
+                        }),
+                        field: *field,
+                    },
+                };
+                stmts.push(write_value(access, span, env)?);
+            }
+            if !fields.is_empty() {
+                stmts.push(write_str(" ", env));
+            }
+            stmts.push(write_str("}", env));
+            let last = stmts.pop().unwrap();
+
+            return Ok(Meta {
+                meta: Type::Unit,
+                inner: Expr::Let {
+                    sym,
+                    mutable: false,
+                    typ: None,
+                    bnd: Some(Box::new(val)),
+                    bdy: Box::new(seq(stmts, last)),
+                },
+            });
+        }
+        Type::Never | Type::Fn { .. } => {
+            return Err(TypeError::NotPrintable {
+                typ: val.meta.to_string(),
+                span,
+            })
+        }
+    };
+
+    Ok(apply_std(write, val, Type::Unit, env))
+}
+
+/// Returns an expression that writes the string byte by byte.
+fn write_str<'p>(s: &str, env: &Env<'_, 'p>) -> Typed<'p, ExprValidated<'p>> {
+    let mut writes = s
+        .bytes()
+        .map(|byte| {
+            let byte = Meta {
+                meta: Type::I64,
+                inner: Expr::Lit {
+                    val: TLit::I64 {
+                        val: i64::from(byte),
+                    },
+                },
+            };
+            apply_std("write_byte", byte, Type::Unit, env)
+        })
+        .collect::<Vec<_>>();
+    let last = writes.pop().unwrap();
+    seq(writes, last)
+}
+
+/// Returns an application of the standard library function `fun` to `arg`.
+fn apply_std<'p>(
+    fun: &str,
+    arg: Typed<'p, ExprValidated<'p>>,
+    typ: Type<UniqueSym<'p>>,
+    env: &Env<'_, 'p>,
+) -> Typed<'p, ExprValidated<'p>> {
+    Meta {
+        meta: typ.clone(),
+        inner: Expr::Apply {
+            fun: Box::new(Meta {
+                meta: Type::Fn {
+                    params: vec![arg.meta.clone()],
+                    typ: Box::new(typ),
+                },
+                inner: Expr::Var { sym: env.std[fun] },
+            }),
+            args: vec![arg],
+        },
+    }
+}
+
+/// Chains the statements in front of `cnt`.
+fn seq<'p>(
+    stmts: Vec<Typed<'p, ExprValidated<'p>>>,
+    cnt: Typed<'p, ExprValidated<'p>>,
+) -> Typed<'p, ExprValidated<'p>> {
+    stmts.into_iter().rfold(cnt, |cnt, stmt| Meta {
+        meta: cnt.meta.clone(),
+        inner: Expr::Seq {
+            stmt: Box::new(stmt),
+            cnt: Box::new(cnt),
+        },
+    })
+}
+
+fn resolve_asm_item<'p>(item: AsmItemUniquified<'p>, env: &mut Env<'_, 'p>) -> AsmItemSelected<'p> {
+    match item {
+        AsmItem::Instr { instr } => AsmItem::Instr {
+            instr: resolve_instr(instr, env),
+        },
+        AsmItem::Label { lbl } => AsmItem::Label {
+            lbl: env.local(lbl.inner),
+        },
+    }
+}
+
+fn resolve_instr<'p>(
     instr: Instr<VarArg<Spanned<UniqueSym<'p>>>, Spanned<UniqueSym<'p>>>,
+    env: &mut Env<'_, 'p>,
 ) -> InstrSelected<'p> {
-    let map = |arg: VarArg<Spanned<UniqueSym<'p>>>| match arg {
+    let map = |arg: VarArg<Spanned<UniqueSym<'p>>>, env: &mut Env<'_, 'p>| match arg {
         VarArg::Imm { val } => VarArg::Imm { val },
         VarArg::Reg { reg } => VarArg::Reg { reg },
         VarArg::Deref { reg, off } => VarArg::Deref { reg, off },
-        VarArg::XVar { sym } => VarArg::XVar { sym: sym.inner },
+        VarArg::XVar { sym } => VarArg::XVar {
+            sym: env.local(sym.inner),
+        },
     };
 
     match instr {
-        Instr::Addq { src, dst } => addq!(map(src), map(dst)),
-        Instr::Subq { src, dst } => subq!(map(src), map(dst)),
-        Instr::Divq { divisor } => divq!(map(divisor)),
-        Instr::Mulq { src } => mulq!(map(src)),
-        Instr::Negq { dst } => negq!(map(dst)),
-        Instr::Movq { src, dst } => movq!(map(src), map(dst)),
-        Instr::Pushq { src } => pushq!(map(src)),
-        Instr::Popq { dst } => popq!(map(dst)),
+        Instr::Addq { src, dst } => addq!(map(src, env), map(dst, env)),
+        Instr::Subq { src, dst } => subq!(map(src, env), map(dst, env)),
+        Instr::Divq { divisor } => divq!(map(divisor, env)),
+        Instr::Mulq { src } => mulq!(map(src, env)),
+        Instr::Negq { dst } => negq!(map(dst, env)),
+        Instr::Incq { dst } => incq!(map(dst, env)),
+        Instr::Decq { dst } => decq!(map(dst, env)),
+        Instr::Movq { src, dst } => movq!(map(src, env), map(dst, env)),
+        Instr::Pushq { src } => pushq!(map(src, env)),
+        Instr::Popq { dst } => popq!(map(dst, env)),
         Instr::Retq => retq!(),
         Instr::Syscall { arity } => syscall!(arity),
-        Instr::Cmpq { src, dst } => cmpq!(map(src), map(dst)),
-        Instr::Andq { src, dst } => andq!(map(src), map(dst)),
-        Instr::Orq { src, dst } => orq!(map(src), map(dst)),
-        Instr::Xorq { src, dst } => xorq!(map(src), map(dst)),
-        Instr::Notq { dst } => notq!(map(dst)),
+        Instr::Cmpq { src, dst } => cmpq!(map(src, env), map(dst, env)),
+        Instr::Andq { src, dst } => andq!(map(src, env), map(dst, env)),
+        Instr::Orq { src, dst } => orq!(map(src, env), map(dst, env)),
+        Instr::Xorq { src, dst } => xorq!(map(src, env), map(dst, env)),
+        Instr::Notq { dst } => notq!(map(dst, env)),
+        Instr::Shlq { src, dst } => shlq!(map(src, env), map(dst, env)),
+        Instr::Sarq { src, dst } => sarq!(map(src, env), map(dst, env)),
+        Instr::Imulq { src, dst } => imulq!(map(src, env), map(dst, env)),
+        Instr::Leaq { src, dst } => leaq!(map(src, env), map(dst, env)),
+        Instr::Testq { src, dst } => testq!(map(src, env), map(dst, env)),
         Instr::Setcc { cnd } => setcc!(cnd),
-        Instr::CallqDirect { .. } => todo!(),
-        Instr::Jmp { .. } => todo!(),
-        Instr::Jcc { .. } => todo!(),
-        Instr::LoadLbl { .. } => todo!(),
-        Instr::CallqIndirect { .. } => todo!(),
+        Instr::CallqDirect { lbl, arity } => callq_direct!(env.local(lbl.inner), arity),
+        Instr::Jmp { lbl } => jmp!(env.local(lbl.inner)),
+        Instr::Jcc { lbl, cnd } => jcc!(env.local(lbl.inner), cnd),
+        Instr::JmpTable { idx, lbls } => {
+            jmp_table!(
+                map(idx, env),
+                lbls.into_iter().map(|lbl| env.local(lbl.inner)).collect()
+            )
+        }
+        Instr::LoadLbl { sym, dst } => load_lbl!(env.local(sym.inner), map(dst, env)),
+        Instr::CallqIndirect { src, arity } => callq_indirect!(map(src, env), arity),
     }
 }
@@ -1,11 +1,12 @@
 use crate::passes::parse::parse::parse_program;
+use crate::passes::parse::Def;
 use crate::utils::split_test::split_test;
 use derive_name::VariantName;
 use miette::{NamedSource, Report};
 use test_each_file::test_each_file;
 
 fn validate([test]: [&str; 1], good: bool) {
-    let (_input, _expected_output, _expected_return, expected_error) = split_test(test);
+    let (_input, _expected_output, _expected_return, expected_error, _args) = split_test(test);
     assert_eq!(good, expected_error.is_none());
 
     let result = parse_program(test).unwrap().validate();
@@ -31,3 +32,16 @@ fn validate([test]: [&str; 1], good: bool) {
 
 test_each_file! { for ["test"] in "./programs/good" as validate_succeed => |i| validate(i, true) }
 test_each_file! { for ["test"] in "./programs/fail/validate" as validate_fail => |i| validate(i, false) }
+
+#[test]
+fn generic_fn_copied_per_type() {
+    let program = "fn id(x) { x } fn main() { id(1); id(2); id(true); }";
+    let program = parse_program(program).unwrap().validate().unwrap();
+
+    let copies = program
+        .defs
+        .values()
+        .filter(|def| matches!(def, Def::Fn { sym, .. } if sym.sym == "id"))
+        .count();
+    assert_eq!(copies, 2);
+}
@@ -16,7 +16,8 @@ pub fn uniquify_def<'p>(
             params,
             typ,
             bdy,
-        } => uniquify_fn(scope, sym, params, typ, bdy),
+            inline,
+        } => uniquify_fn(scope, sym, params, typ, bdy, inline),
         Def::TypeDef { sym, def } => uniquify_typedef(scope, sym, def),
     }
 }
@@ -1,12 +1,19 @@
-use crate::passes::parse::{Expr, ExprParsed, InstrParsed, Meta, Spanned};
-use crate::passes::select::VarArg;
+use crate::passes::parse::{
+    AsmItemParsed, Expr, ExprParsed, InstrParsed, MatchArm, MatchArmParsed, Meta, Pattern,
+    PatternParsed, Spanned,
+};
+use crate::passes::select::{AsmItem, VarArg};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::uniquify::r#type::uniquify_type;
 use crate::passes::validate::uniquify::{gen_spanned_sym, try_get};
-use crate::passes::validate::{uniquify, ExprUniquified, InstrUniquified};
+use crate::passes::validate::{
+    uniquify, AsmItemUniquified, ExprUniquified, InstrUniquified, MatchArmUniquified,
+    PatternUniquified,
+};
 use crate::utils::gen_sym::UniqueSym;
 use crate::utils::push_map::PushMap;
 use crate::*;
+use std::collections::HashMap;
 
 pub fn uniquify_expr<'p>(
     expr: Spanned<ExprParsed<'p>>,
@@ -20,7 +27,9 @@ pub fn uniquify_expr<'p>(
             bdy,
             mutable,
         } => {
-            let unique_bnd = uniquify_expr(*bnd, scope)?;
+            let unique_bnd = bnd
+                .map(|bnd| uniquify_expr(*bnd, scope).map(Box::new))
+                .transpose()?;
             let unique_sym = gen_spanned_sym(sym.clone());
             let unique_bdy = scope.push(sym.inner, unique_sym.inner, |scope| {
                 uniquify_expr(*bdy, scope)
@@ -30,7 +39,7 @@ pub fn uniquify_expr<'p>(
                 sym: unique_sym,
                 mutable,
                 typ: typ.map(|typ| uniquify_type(typ, scope)).transpose()?,
-                bnd: Box::new(unique_bnd),
+                bnd: unique_bnd,
                 bdy: Box::new(unique_bdy),
             }
         }
@@ -41,12 +50,18 @@ pub fn uniquify_expr<'p>(
             sym: uniquify::try_get(sym, scope)?,
             bnd: Box::new(uniquify_expr(*bnd, scope)?),
         },
-        Expr::Struct { sym, fields } => Expr::Struct {
+        Expr::RefMut { sym } => Expr::RefMut {
+            sym: uniquify::try_get(sym, scope)?,
+        },
+        Expr::Struct { sym, fields, base } => Expr::Struct {
             sym: uniquify::try_get(sym, scope)?,
             fields: fields
                 .into_iter()
                 .map(|(sym, expr)| uniquify_expr(expr, scope).map(|expr| (sym, expr)))
                 .collect::<Result<_, _>>()?,
+            base: base
+                .map(|base| uniquify_expr(*base, scope).map(Box::new))
+                .transpose()?,
         },
 
         Expr::Lit { val } => Expr::Lit { val },
@@ -73,17 +88,36 @@ pub fn uniquify_expr<'p>(
                 .map(|arg| uniquify_expr(arg, scope))
                 .collect::<Result<_, _>>()?,
         },
-        Expr::Loop { bdy } => Expr::Loop {
+        Expr::Loop { lbl: None, bdy } => Expr::Loop {
+            lbl: None,
             bdy: Box::new(uniquify_expr(*bdy, scope)?),
         },
-        Expr::Break { bdy } => Expr::Break {
+        Expr::Loop {
+            lbl: Some(lbl),
+            bdy,
+        } => {
+            // Labels start with a `'`, so they live in the same scope as variables without clashing.
+            let unique_lbl = gen_spanned_sym(lbl.clone());
+            let unique_bdy = scope.push(lbl.inner, unique_lbl.inner, |scope| {
+                uniquify_expr(*bdy, scope)
+            })?;
+
+            Expr::Loop {
+                lbl: Some(unique_lbl),
+                bdy: Box::new(unique_bdy),
+            }
+        }
+        Expr::Break { lbl, bdy } => Expr::Break {
+            lbl: lbl.map(|lbl| try_get_label(lbl, scope)).transpose()?,
             bdy: Box::new(uniquify_expr(*bdy, scope)?),
         },
         Expr::Seq { stmt, cnt } => Expr::Seq {
             stmt: Box::new(uniquify_expr(*stmt, scope)?),
             cnt: Box::new(uniquify_expr(*cnt, scope)?),
         },
-        Expr::Continue => Expr::Continue,
+        Expr::Continue { lbl } => Expr::Continue {
+            lbl: lbl.map(|lbl| try_get_label(lbl, scope)).transpose()?,
+        },
         Expr::Return { bdy } => Expr::Return {
             bdy: Box::new(uniquify_expr(*bdy, scope)?),
         },
@@ -93,12 +127,75 @@ pub fn uniquify_expr<'p>(
         },
         Expr::Variant { .. } => todo!(),
         Expr::Switch { .. } => todo!(),
-        ExprParsed::Asm { instrs } => ExprUniquified::Asm {
-            instrs: instrs
+        Expr::Match { scrut, arms } => Expr::Match {
+            scrut: Box::new(uniquify_expr(*scrut, scope)?),
+            arms: arms
                 .into_iter()
-                .map(|instr| uniquify_instr(instr, scope))
+                .map(|arm| uniquify_arm(arm, scope))
                 .collect::<Result<_, _>>()?,
         },
+        ExprParsed::Asm {
+            operands,
+            typ,
+            instrs,
+        } => {
+            // Operands are evaluated outside the asm block, so they cannot refer to each other.
+            let operands = operands
+                .into_iter()
+                .map(|(sym, typ, bnd)| {
+                    Ok((
+                        gen_spanned_sym(sym),
+                        uniquify_type(typ, scope)?,
+                        Box::new(uniquify_expr(*bnd, scope)?),
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            // Check that there are no duplicate operand names.
+            let mut operand_syms = HashMap::new();
+            for (sym, _, _) in &operands {
+                if let Some(prev_span) = operand_syms.insert(sym.inner.sym, sym.meta) {
+                    return Err(TypeError::DuplicateArg {
+                        span1: prev_span,
+                        span2: sym.meta,
+                        sym: sym.inner.sym.to_string(),
+                    });
+                }
+            }
+
+            // Labels are local to the asm block, so they get their own scope.
+            let mut labels = HashMap::new();
+            for instr in &instrs {
+                if let AsmItem::Label { lbl } = instr {
+                    let unique_lbl = gen_spanned_sym(lbl.clone());
+                    if let Some(prev) = labels.insert(lbl.inner, unique_lbl) {
+                        return Err(TypeError::DuplicateLabel {
+                            span1: prev.meta,
+                            span2: lbl.meta,
+                            sym: lbl.inner.to_string(),
+                        });
+                    }
+                }
+            }
+
+            let instrs = scope.push_iter(
+                operands
+                    .iter()
+                    .map(|(sym, _, _)| (sym.inner.sym, sym.inner)),
+                |scope| {
+                    instrs
+                        .into_iter()
+                        .map(|instr| uniquify_asm_item(instr, scope, &labels))
+                        .collect::<Result<_, _>>()
+                },
+            )?;
+
+            ExprUniquified::Asm {
+                operands,
+                typ: uniquify_type(typ, scope)?,
+                instrs,
+            }
+        }
     };
 
     Ok(Meta {
@@ -107,9 +204,104 @@ pub fn uniquify_expr<'p>(
     })
 }
 
+fn uniquify_arm<'p>(
+    arm: MatchArmParsed<'p>,
+    scope: &mut PushMap<&'p str, UniqueSym<'p>>,
+) -> Result<MatchArmUniquified<'p>, TypeError> {
+    let mut bindings = HashMap::new();
+    let pat = uniquify_pattern(arm.pat, scope, &mut bindings)?;
+
+    // The bindings of the pattern are in scope for both the guard and the body.
+    scope.push_iter(
+        bindings
+            .into_iter()
+            .map(|(sym, unique_sym)| (sym, unique_sym.inner)),
+        |scope| {
+            Ok(MatchArm {
+                pat,
+                guard: arm
+                    .guard
+                    .map(|guard| uniquify_expr(guard, scope))
+                    .transpose()?,
+                bdy: uniquify_expr(arm.bdy, scope)?,
+            })
+        },
+    )
+}
+
+fn uniquify_pattern<'p>(
+    pat: Spanned<PatternParsed<'p>>,
+    scope: &PushMap<&'p str, UniqueSym<'p>>,
+    bindings: &mut HashMap<&'p str, Spanned<UniqueSym<'p>>>,
+) -> Result<Spanned<PatternUniquified<'p>>, TypeError> {
+    let inner = match pat.inner {
+        Pattern::Wildcard => Pattern::Wildcard,
+        Pattern::Binding { sym } => {
+            let unique_sym = gen_spanned_sym(sym.clone());
+            if let Some(prev) = bindings.insert(sym.inner, unique_sym.clone()) {
+                return Err(TypeError::DuplicateBinding {
+                    span1: prev.meta,
+                    span2: sym.meta,
+                    sym: sym.inner.to_string(),
+                });
+            }
+            Pattern::Binding { sym: unique_sym }
+        }
+        Pattern::Lit { val } => Pattern::Lit { val },
+        Pattern::Range { lo, hi, inclusive } => Pattern::Range { lo, hi, inclusive },
+        Pattern::Struct { sym, fields, rest } => Pattern::Struct {
+            sym: try_get(sym, scope)?,
+            fields: fields
+                .into_iter()
+                .map(|(field, pat)| Ok((field, uniquify_pattern(pat, scope, bindings)?)))
+                .collect::<Result<_, _>>()?,
+            rest,
+        },
+    };
+
+    Ok(Meta {
+        inner,
+        meta: pat.meta,
+    })
+}
+
+fn try_get_label<'p>(
+    lbl: Spanned<&'p str>,
+    scope: &PushMap<&'p str, UniqueSym<'p>>,
+) -> Result<Spanned<UniqueSym<'p>>, TypeError> {
+    scope
+        .get(&lbl.inner)
+        .ok_or(TypeError::UndeclaredLoopLabel {
+            sym: lbl.inner.to_string(),
+            span: lbl.meta,
+        })
+        .map(|&inner| Meta {
+            meta: lbl.meta,
+            inner,
+        })
+}
+
+fn uniquify_asm_item<'p>(
+    item: AsmItemParsed<'p>,
+    scope: &PushMap<&'p str, UniqueSym<'p>>,
+    labels: &HashMap<&'p str, Spanned<UniqueSym<'p>>>,
+) -> Result<AsmItemUniquified<'p>, TypeError> {
+    let item = match item {
+        AsmItem::Instr { instr } => AsmItem::Instr {
+            instr: uniquify_instr(instr, scope, labels)?,
+        },
+        AsmItem::Label { lbl } => AsmItem::Label {
+            lbl: labels[&lbl.inner].clone(),
+        },
+    };
+
+    Ok(item)
+}
+
 fn uniquify_instr<'p>(
     instr: InstrParsed<'p>,
     scope: &PushMap<&'p str, UniqueSym<'p>>,
+    labels: &HashMap<&'p str, Spanned<UniqueSym<'p>>>,
 ) -> Result<InstrUniquified<'p>, TypeError> {
     let map = |arg: VarArg<Spanned<&'p str>>| {
         Ok(match arg {
@@ -122,12 +314,27 @@ fn uniquify_instr<'p>(
         })
     };
 
+    let get_label = |lbl: Spanned<&'p str>| {
+        labels
+            .get(&lbl.inner)
+            .map(|unique_lbl| Meta {
+                meta: lbl.meta,
+                inner: unique_lbl.inner,
+            })
+            .ok_or(TypeError::UndeclaredLabel {
+                sym: lbl.inner.to_string(),
+                span: lbl.meta,
+            })
+    };
+
     let instr = match instr {
         InstrParsed::Addq { src, dst } => addq!(map(src)?, map(dst)?),
         InstrParsed::Subq { src, dst } => subq!(map(src)?, map(dst)?),
         InstrParsed::Divq { divisor } => divq!(map(divisor)?),
         InstrParsed::Mulq { src } => mulq!(map(src)?),
         InstrParsed::Negq { dst } => negq!(map(dst)?),
+        InstrParsed::Incq { dst } => incq!(map(dst)?),
+        InstrParsed::Decq { dst } => decq!(map(dst)?),
         InstrParsed::Movq { src, dst } => movq!(map(src)?, map(dst)?),
         InstrParsed::Pushq { src } => pushq!(map(src)?),
         InstrParsed::Popq { dst } => popq!(map(dst)?),
@@ -138,12 +345,21 @@ fn uniquify_instr<'p>(
         InstrParsed::Orq { src, dst } => orq!(map(src)?, map(dst)?),
         InstrParsed::Xorq { src, dst } => xorq!(map(src)?, map(dst)?),
         InstrParsed::Notq { dst } => notq!(map(dst)?),
+        InstrParsed::Shlq { src, dst } => shlq!(map(src)?, map(dst)?),
+        InstrParsed::Sarq { src, dst } => sarq!(map(src)?, map(dst)?),
+        InstrParsed::Imulq { src, dst } => imulq!(map(src)?, map(dst)?),
+        InstrParsed::Leaq { src, dst } => leaq!(map(src)?, map(dst)?),
+        InstrParsed::Testq { src, dst } => testq!(map(src)?, map(dst)?),
         InstrParsed::Setcc { cnd } => setcc!(cnd),
-        InstrParsed::CallqDirect { .. } => todo!(),
-        InstrParsed::Jmp { .. } => todo!(),
-        InstrParsed::Jcc { .. } => todo!(),
-        InstrParsed::LoadLbl { .. } => todo!(),
-        InstrParsed::CallqIndirect { .. } => todo!(),
+        InstrParsed::CallqDirect { lbl, arity } => callq_direct!(try_get(lbl, scope)?, arity),
+        InstrParsed::Jmp { lbl } => jmp!(get_label(lbl)?),
+        InstrParsed::Jcc { lbl, cnd } => jcc!(get_label(lbl)?, cnd),
+        InstrParsed::JmpTable { idx, lbls } => jmp_table!(
+            map(idx)?,
+            lbls.into_iter().map(get_label).collect::<Result<_, _>>()?
+        ),
+        InstrParsed::LoadLbl { sym, dst } => load_lbl!(try_get(sym, scope)?, map(dst)?),
+        InstrParsed::CallqIndirect { src, arity } => callq_indirect!(map(src)?, arity),
     };
 
     Ok(instr)
@@ -1,10 +1,10 @@
 use crate::passes::parse::types::Type;
-use crate::passes::parse::{Def, ExprParsed, Param, Spanned};
+use crate::passes::parse::{Def, ExprParsed, Inline, Param, ParamParsed, Spanned};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::uniquify::expr::uniquify_expr;
 use crate::passes::validate::uniquify::gen_spanned_sym;
 use crate::passes::validate::uniquify::r#type::uniquify_type;
-use crate::passes::validate::{uniquify, DefUniquified};
+use crate::passes::validate::{uniquify, DefUniquified, ParamUniquified};
 use crate::utils::gen_sym::UniqueSym;
 use crate::utils::push_map::PushMap;
 use std::collections::HashMap;
@@ -12,9 +12,10 @@ use std::collections::HashMap;
 pub fn uniquify_fn<'p>(
     scope: &mut PushMap<&'p str, UniqueSym<'p>>,
     sym: Spanned<&'p str>,
-    params: Vec<Param<Spanned<&'p str>>>,
-    typ: Type<Spanned<&'p str>>,
+    params: Vec<ParamParsed<'p>>,
+    typ: Option<Type<Spanned<&'p str>>>,
     bdy: Spanned<ExprParsed<'p>>,
+    inline: Inline,
 ) -> Result<DefUniquified<'p>, TypeError> {
     // Generate unique names for the parameters.
     let iterator = params
@@ -48,19 +49,25 @@ pub fn uniquify_fn<'p>(
         Ok(Def::Fn {
             sym: uniquify::try_get(sym, scope)?,
             params,
-            typ: uniquify_type(typ, scope)?,
+            typ: typ.map(|typ| uniquify_type(typ, scope)).transpose()?,
             bdy,
+            inline,
         })
     })
 }
 
 fn uniquify_param<'p>(
-    param: &Param<Spanned<&'p str>>,
+    param: &ParamParsed<'p>,
     scope: &mut PushMap<&'p str, UniqueSym<'p>>,
-) -> Result<Param<Spanned<UniqueSym<'p>>>, TypeError> {
+) -> Result<ParamUniquified<'p>, TypeError> {
     Ok(Param {
         sym: uniquify::try_get(param.sym.clone(), scope)?,
         mutable: param.mutable,
-        typ: uniquify_type(param.typ.clone(), scope)?,
+        by_ref: param.by_ref,
+        typ: param
+            .typ
+            .clone()
+            .map(|typ| uniquify_type(typ, scope))
+            .transpose()?,
     })
 }
@@ -1,8 +1,10 @@
 use crate::passes::parse::types::Type;
-use crate::passes::parse::{Meta, PrgParsed, Spanned};
+use crate::passes::parse::{Def, DefParsed, Meta, Param, PrgParsed, Spanned, TypeDef};
 use crate::passes::select::std_lib::Std;
 use crate::passes::validate::error::TypeError;
-use crate::passes::validate::error::TypeError::{NoMain, UndeclaredVar};
+use crate::passes::validate::error::TypeError::{
+    DuplicateGlobalBuiltin, MainSignature, NoMain, UndeclaredVar,
+};
 use crate::passes::validate::DefUniquified;
 use crate::utils::gen_sym::{gen_sym, UniqueSym};
 use crate::utils::push_map::PushMap;
@@ -48,34 +50,237 @@ pub static BUILT_INS: Lazy<HashMap<&'static str, Type<Spanned<UniqueSym<'static>
                     typ: Box::new(Type::I64),
                 },
             ),
+            (
+                "flush",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::Unit),
+                },
+            ),
+            (
+                "read_byte",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "write_byte",
+                Type::Fn {
+                    params: vec![Type::I64],
+                    typ: Box::new(Type::Unit),
+                },
+            ),
+            (
+                "arg_count",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "arg_byte",
+                Type::Fn {
+                    params: vec![Type::I64, Type::I64],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "env_count",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "env_byte",
+                Type::Fn {
+                    params: vec![Type::I64, Type::I64],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "path_push",
+                Type::Fn {
+                    params: vec![Type::I64],
+                    typ: Box::new(Type::Unit),
+                },
+            ),
+            (
+                "open",
+                Type::Fn {
+                    params: vec![Type::I64, Type::I64],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "read_fd",
+                Type::Fn {
+                    params: vec![Type::I64],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "write_fd",
+                Type::Fn {
+                    params: vec![Type::I64, Type::I64],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "close",
+                Type::Fn {
+                    params: vec![Type::I64],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_rdonly",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_wronly",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_rdwr",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_creat",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_excl",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_trunc",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
+            (
+                "o_append",
+                Type::Fn {
+                    params: vec![],
+                    typ: Box::new(Type::I64),
+                },
+            ),
         ])
     });
 
+/// Standard library functions that `print` is resolved to based on the type of its argument.
+/// These cannot be referred to by name.
+pub const PRINT_INTERNALS: [&str; 7] = [
+    "print_u64",
+    "print_bool",
+    "print_unit",
+    "write_i64",
+    "write_u64",
+    "write_bool",
+    "write_unit",
+];
+
+/// The type of the optional parameter of `main`. It holds the number of command-line arguments,
+/// whose bytes can be read with `arg_byte`.
+pub const ARGS: &str = "Args";
+
 impl<'p> PrgParsed<'p> {
     pub fn uniquify(self) -> Result<PrgUniquified<'p>, TypeError> {
         let std: Std<'p> = BUILT_INS
-            .iter()
-            .map(|(sym, _)| (*sym, gen_sym(sym)))
+            .keys()
+            .chain(&PRINT_INTERNALS)
+            .map(|sym| (*sym, gen_sym(sym)))
             .collect();
 
+        // `Args` is defined by the compiler, so the program cannot define it as well.
+        if let Some(def) = self.defs.iter().find(|def| def.sym().inner == ARGS) {
+            return Err(DuplicateGlobalBuiltin {
+                span: def.sym().meta,
+                sym: ARGS.to_string(),
+            });
+        }
+        let defs = self
+            .defs
+            .into_iter()
+            .chain([args_def()])
+            .collect::<Vec<_>>();
+
         let mut scope = PushMap::from_iter(
-            self.defs
-                .iter()
+            defs.iter()
                 .map(|def| (def.sym().inner, gen_sym(def.sym().inner)))
-                .chain(std.iter().map(|(&k, &v)| (k, v))),
+                .chain(BUILT_INS.keys().map(|&k| (k, std[k]))),
         );
 
         let entry = *scope.get(&"main").ok_or(NoMain)?;
+        let args = scope[&ARGS];
 
-        Ok(PrgUniquified {
-            defs: self
-                .defs
-                .into_iter()
-                .map(|def| def::uniquify_def(def, &mut scope))
-                .collect::<Result<_, _>>()?,
-            entry,
-            std,
-        })
+        let defs = defs
+            .into_iter()
+            .map(|def| def::uniquify_def(def, &mut scope))
+            .collect::<Result<Vec<_>, _>>()?;
+        check_main(&defs, entry, args)?;
+
+        Ok(PrgUniquified { defs, entry, std })
+    }
+}
+
+/// Returns the definition of `Args`, which has no span because it is not part of the program.
+fn args_def<'p>() -> DefParsed<'p> {
+    Def::TypeDef {
+        sym: Meta {
+            meta: (0, 0),
+            inner: ARGS,
+        },
+        def: TypeDef::Struct {
+            fields: vec![(
+                Meta {
+                    meta: (0, 0),
+                    inner: "count",
+                },
+                Type::I64,
+            )],
+            defaults: Vec::new(),
+        },
+    }
+}
+
+/// Checks that `main` takes either no parameters or only the command-line arguments, since those
+/// are all that the entry of the program can pass to it.
+fn check_main(
+    defs: &[DefUniquified<'_>],
+    entry: UniqueSym<'_>,
+    args: UniqueSym<'_>,
+) -> Result<(), TypeError> {
+    let Some(Def::Fn { sym, params, .. }) = defs.iter().find(|def| def.sym().inner == entry) else {
+        return Ok(());
+    };
+
+    match params.as_slice() {
+        [] => Ok(()),
+        [Param {
+            typ: Some(Type::Var { sym: typ }),
+            by_ref: false,
+            ..
+        }] if typ.inner == args => Ok(()),
+        _ => Err(MainSignature { span: sym.meta }),
     }
 }
 
@@ -95,7 +300,7 @@ fn try_get<'p>(
         })
 }
 
-fn gen_spanned_sym(sym: Spanned<&str>) -> Spanned<UniqueSym> {
+fn gen_spanned_sym(sym: Spanned<&str>) -> Spanned<UniqueSym<'_>> {
     Meta {
         inner: gen_sym(sym.inner),
         meta: sym.meta,
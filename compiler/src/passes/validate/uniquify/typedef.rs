@@ -1,4 +1,4 @@
-use crate::passes::parse::{Def, Spanned, TypeDef};
+use crate::passes::parse::{Def, Lit, Spanned, TypeDef};
 use crate::passes::validate::error::TypeError;
 use crate::passes::validate::uniquify::r#type::uniquify_type;
 use crate::passes::validate::uniquify::try_get;
@@ -9,14 +9,15 @@ use crate::utils::push_map::PushMap;
 pub fn uniquify_typedef<'p>(
     scope: &mut PushMap<&'p str, UniqueSym<'p>>,
     sym: Spanned<&'p str>,
-    def: TypeDef<Spanned<&'p str>, Spanned<&'p str>>,
+    def: TypeDef<Spanned<&'p str>, Spanned<&'p str>, Lit<'p>>,
 ) -> Result<DefUniquified<'p>, TypeError> {
     let def = match def {
-        TypeDef::Struct { fields } => TypeDef::Struct {
+        TypeDef::Struct { fields, defaults } => TypeDef::Struct {
             fields: fields
                 .into_iter()
                 .map(|(sym, typ)| Ok((sym, uniquify_type(typ, scope)?)))
                 .collect::<Result<_, _>>()?,
+            defaults,
         },
         TypeDef::Enum { .. } => todo!(),
     };
@@ -1,29 +1,45 @@
 use crate::passes::validate::TLit;
 use std::cell::OnceCell;
 
-/// Splits the inputs, expected outputs and expected return from the test.
-/// The values must be preceded by `//*` and `inp:`, `out:` or `ret:`.
+/// Splits the input, expected outputs, expected return, expected error and command-line
+/// arguments from the test. The values must be preceded by `//*` and `inp:`, `raw:`, `out:`, `ret:`,
+/// `err:` or `args:`. Expected outputs are compared line by line against the printed text.
+///
+/// The input is returned as the bytes that are fed to stdin. The literals after `inp:` are fed
+/// on a line each, while `raw:` feeds the rest of its line with escapes such as `\n` replaced.
 #[must_use]
-pub fn split_test(test: &str) -> (Vec<TLit>, Vec<TLit>, TLit, Option<&str>) {
+pub fn split_test(test: &str) -> (Vec<u8>, Vec<String>, TLit, Option<&str>, Vec<String>) {
     let mut input = OnceCell::new();
     let mut output = OnceCell::new();
     let mut expected_return = OnceCell::new();
     let mut expected_error = OnceCell::new();
+    let mut args = OnceCell::new();
 
     for line in test.lines() {
         let mut parts = line.split_whitespace();
 
         match (parts.next(), parts.next()) {
             (Some("//*"), Some("inp:")) => input
-                .set(parts.map(str::parse).collect::<Result<_, _>>().unwrap())
-                .unwrap(),
-            (Some("//*"), Some("out:")) => output
-                .set(parts.map(str::parse).collect::<Result<_, _>>().unwrap())
+                .set(
+                    parts
+                        .flat_map(|part| {
+                            format!("{}\n", part.parse::<TLit>().unwrap()).into_bytes()
+                        })
+                        .collect(),
+                )
                 .unwrap(),
+            (Some("//*"), Some("raw:")) => {
+                let (_, raw) = line.split_once("raw:").unwrap();
+                input
+                    .set(unescape(raw.strip_prefix(' ').unwrap_or(raw)))
+                    .unwrap();
+            }
+            (Some("//*"), Some("out:")) => output.set(split_outputs(parts)).unwrap(),
             (Some("//*"), Some("ret:")) => expected_return
                 .set(parts.next().unwrap().parse().unwrap())
                 .unwrap(),
             (Some("//*"), Some("err:")) => expected_error.set(parts.next().unwrap()).unwrap(),
+            (Some("//*"), Some("args:")) => args.set(parts.map(str::to_string).collect()).unwrap(),
             _ => {}
         }
     }
@@ -33,5 +49,56 @@ pub fn split_test(test: &str) -> (Vec<TLit>, Vec<TLit>, TLit, Option<&str>) {
         output.take().unwrap_or_default(),
         expected_return.take().unwrap_or(TLit::Unit),
         expected_error.take(),
+        args.take().unwrap_or_default(),
     )
 }
+
+/// Replaces the escapes `\n`, `\r`, `\t`, `\0`, `\\` and `\xHH` by the bytes they stand for.
+fn unescape(raw: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut raw = raw.bytes();
+
+    while let Some(byte) = raw.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        bytes.push(match raw.next() {
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'0') => b'\0',
+            Some(b'\\') => b'\\',
+            Some(b'x') => {
+                let hex = [raw.next().unwrap(), raw.next().unwrap()];
+                u8::from_str_radix(std::str::from_utf8(&hex).unwrap(), 16).unwrap()
+            }
+            escape => panic!("Unknown escape in raw input: {escape:?}."),
+        });
+    }
+
+    bytes
+}
+
+/// Splits the expected outputs on whitespace, except inside printed structs such as `Point { x: 1, y: 2 }`.
+fn split_outputs<'a>(parts: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut parts = parts.peekable();
+    let mut outputs = Vec::new();
+
+    while let Some(part) = parts.next() {
+        let mut output = part.to_string();
+        let mut depth = 0;
+
+        while depth > 0 || parts.peek().is_some_and(|next| next.starts_with('{')) {
+            let part = parts.next().unwrap();
+            depth += part.matches('{').count();
+            depth -= part.matches('}').count();
+            output.push(' ');
+            output.push_str(part);
+        }
+
+        outputs.push(output);
+    }
+
+    outputs
+}
@@ -6,7 +6,7 @@ pub struct UnionFind<T> {
     data: Vec<T>,
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct UnionIndex(usize);
 
 impl<T> Default for UnionFind<T> {
@@ -1,5 +1,6 @@
 #![cfg(unix)]
 
+use compiler::passes::assign::Allocator;
 use compiler::passes::parse::parse::parse_program;
 use compiler::passes::validate::TLit;
 use compiler::utils::split_test::split_test;
@@ -11,15 +12,24 @@ use tempfile::TempDir;
 use test_each_file::test_each_file;
 
 fn integration([test]: [&str; 1]) {
+    integration_with(test, Allocator::Coloring);
+}
+
+fn integration_linear_scan([test]: [&str; 1]) {
+    integration_with(test, Allocator::LinearScan);
+}
+
+fn integration_with(test: &str, allocator: Allocator) {
     let tempdir = TempDir::with_prefix("rust-compiler-construction-integration").unwrap();
 
-    let (input, expected_output, expected_return, _) = split_test(test);
+    let (input, expected_output, expected_return, _, args) = split_test(test);
     let expected_return: i64 = expected_return.into();
 
     let input_path = tempdir.path().join("output");
     let mut output = OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .mode(0o777)
         .open(input_path)
         .unwrap();
@@ -29,12 +39,18 @@ fn integration([test]: [&str; 1]) {
         .validate()
         .unwrap()
         .reveal()
+        .decide()
+        .inline()
         .atomize()
+        .fold()
         .explicate()
+        .prune()
         .eliminate()
         .select()
-        .assign()
+        .assign_with(allocator)
+        .unwrap()
         .patch()
+        .peephole()
         .conclude()
         .emit()
         .write(&mut output);
@@ -45,6 +61,7 @@ fn integration([test]: [&str; 1]) {
     let mut program;
     loop {
         let sub_res = Command::new("./output")
+            .args(&args)
             .current_dir(&tempdir)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -60,9 +77,9 @@ fn integration([test]: [&str; 1]) {
     }
 
     let mut stdin = program.stdin.take().unwrap();
-    for num in input {
-        writeln!(stdin, "{num}").unwrap();
-    }
+    stdin.write_all(&input).unwrap();
+    // Close stdin so the program sees EOF.
+    drop(stdin);
 
     let out = program.wait_with_output().unwrap();
     assert_eq!(
@@ -71,8 +88,10 @@ fn integration([test]: [&str; 1]) {
     );
 
     for (got, expected) in out.stdout.lines().map(|r| r.unwrap()).zip(expected_output) {
-        assert_eq!(got.parse::<TLit>().unwrap(), expected);
+        let got = got.parse::<TLit>().map_or(got, |lit| lit.to_string());
+        assert_eq!(got, expected);
     }
 }
 
 test_each_file! { for ["test"] in "./programs/good" as integration => integration }
+test_each_file! { for ["test"] in "./programs/good" as integration_linear_scan => integration_linear_scan }